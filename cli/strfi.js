@@ -70,6 +70,19 @@ const initLayout = BufferLayout.struct([
     BufferLayout.blob(8, "starttime"),
     BufferLayout.blob(8, "endtime"),
     BufferLayout.blob(8, "amount"),
+    BufferLayout.blob(8, "clifftime"),
+    BufferLayout.blob(8, "cliffamount"),
+    BufferLayout.blob(8, "period"),
+    BufferLayout.blob(8, "amountperperiod"),
+    BufferLayout.u8("periodunit"),
+    BufferLayout.u8("breakpoints"),
+    BufferLayout.blob(8, "rate"),
+    BufferLayout.u8("transferbysenderonly"),
+    BufferLayout.u8("cancelablebysender"),
+    BufferLayout.u8("cancelablebyrecipient"),
+    BufferLayout.blob(32, "rentdestination"),
+    BufferLayout.u16("crankfeebps"),
+    BufferLayout.blob(8, "nonce"),
 ]);
 
 // This is the structure for the withdraw instruction
@@ -97,17 +110,53 @@ async function initStream(connection) {
             endtime: new spl.u64(now + 610).toBuffer(),
             // Lamports to stream
             amount: new spl.u64(100000000).toBuffer(),
+            // Unix timestamp of the cliff, 0 for no cliff.
+            clifftime: new spl.u64(0).toBuffer(),
+            // Lamports unlocked at once at the cliff.
+            cliffamount: new spl.u64(0).toBuffer(),
+            // Seconds between releases, 0 to unlock continuously.
+            period: new spl.u64(0).toBuffer(),
+            // Lamports released every period.
+            amountperperiod: new spl.u64(0).toBuffer(),
+            // 0 for seconds, 1 for calendar months, 2 for calendar years.
+            periodunit: 0,
+            // Number of piecewise-linear schedule breakpoints, none here.
+            breakpoints: 0,
+            // Lamports per second for an open-ended stream, 0 for a fixed end.
+            rate: new spl.u64(0).toBuffer(),
+            // 1 if only the sender may transfer the stream to someone else.
+            transferbysenderonly: 0,
+            // Who of the two parties may cancel the stream.
+            cancelablebysender: 1,
+            cancelablebyrecipient: 0,
+            // Who gets the rent back once the stream is closed, zeroes
+            // for the sender.
+            rentdestination: Buffer.alloc(32),
+            // Basis points of every crank withdrawal paid to whoever
+            // sends it, 0 for none.
+            crankfeebps: 0,
+            // Nonce used to derive the pda, so the same parties can
+            // have more than one stream.
+            nonce: new spl.u64(now).toBuffer(),
         },
         data,
     );
 
-    // pda is a new keypair where the funds are sent, and program metadata
-    // is kept and updated by the program.
-    const pda = new sol.Keypair();
+    // pda is the account where the funds are sent, and program metadata
+    // is kept and updated by the program. It is derived from Alice, Bob
+    // and the nonce.
+    const [pda] = await sol.PublicKey.findProgramAddress([
+            Buffer.from("stream"),
+            alice.publicKey.toBuffer(),
+            bob.publicKey.toBuffer(),
+            new spl.u64(now).toBuffer(),
+        ],
+        new sol.PublicKey(programAddr),
+    );
 
     console.log("ALICE: %s", alice.publicKey.toBase58());
     console.log("BOB:   %s", bob.publicKey.toBase58());
-    console.log("PDA:   %s", pda.publicKey.toBase58());
+    console.log("PDA:   %s", pda.toBase58());
     console.log("DATA:", data);
 
     const instruction = new sol.TransactionInstruction({
@@ -124,8 +173,8 @@ async function initStream(connection) {
         }, {
             // pda is the account that will be created.
             // It shall contain the locked funds and necessary metadata.
-            pubkey: pda.publicKey,
-            isSigner: true,
+            pubkey: pda,
+            isSigner: false,
             isWritable: true,
         }, {
             // This is the system program public key.
@@ -137,9 +186,9 @@ async function initStream(connection) {
         data: data,
     });
 
-    // Transaction signed by Alice and the new pda.
+    // Transaction signed by Alice.
     tx = new sol.Transaction().add(instruction);
-    return await sol.sendAndConfirmTransaction(connection, tx, [alice, pda]);
+    return await sol.sendAndConfirmTransaction(connection, tx, [alice]);
 }
 
 async function withdrawStream(connection, accountAddr) {
@@ -154,6 +203,13 @@ async function withdrawStream(connection, accountAddr) {
         },
         data,
     );
+    // The program's config, naming who collects the rent of streams
+    // without a rent destination of their own.
+    const [config] = await sol.PublicKey.findProgramAddress(
+        [Buffer.from("config")],
+        new sol.PublicKey(programAddr),
+    );
+
     console.log("ALICE: %s", alice.publicKey.toBase58());
     console.log("BOB:   %s", bob.publicKey.toBase58());
     console.log("PDA:   %s", accountAddr);
@@ -172,12 +228,16 @@ async function withdrawStream(connection, accountAddr) {
             isSigner: false,
             isWritable: true,
         }, {
-            // This address is hardcoded in the program, and is supposed
-            // to collect the remaining rent when everything is withdrawn
-            // from the stream successfully.
-            pubkey: new sol.PublicKey("DrFtxPb9F6SxpHHHFiEtSNXE3SZCUNLXMaHS6r8pkoz2"),
+            // The stream's rent destination, Alice unless chosen otherwise
+            // on initialization, collects the remaining rent when everything
+            // is withdrawn from the stream successfully.
+            pubkey: alice.publicKey,
             isSigner: false,
             isWritable: true,
+        }, {
+            pubkey: config,
+            isSigner: false,
+            isWritable: false,
         }, {
             // This is the system program public key.
             pubkey: sol.SystemProgram.programId,
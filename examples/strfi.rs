@@ -16,21 +16,17 @@
 use std::{env, process, str::FromStr, time::SystemTime};
 
 use solana_client::{blockhash_query::BlockhashQuery, rpc_client::RpcClient};
-use solana_program::{
-    instruction::{AccountMeta, Instruction},
-    pubkey::Pubkey,
-};
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
 
-use solana_sdk::{
-    signer::keypair::Keypair, signer::Signer, system_program, transaction::Transaction,
-};
+use solana_sdk::{signer::keypair::Keypair, signer::Signer, transaction::Transaction};
 
-use streamflow::utils::any_as_u8_slice;
+use streamflow::instruction::{cancel_native, initialize_native, withdraw_native, StreamParams};
+use streamflow::utils::{find_stream_address, PeriodUnit};
 
 /// The program address to use.
-const PROGRAM_ID: &'static str = "ETNwB99fC4HegvqBcHyvPhsiJ9x336NVTeqS11LBSmiP";
+const PROGRAM_ID: &str = "ETNwB99fC4HegvqBcHyvPhsiJ9x336NVTeqS11LBSmiP";
 /// The Cluster RPC URL
-const RPC_ADDR: &'static str = "http://localhost:8899";
+const RPC_ADDR: &str = "http://localhost:8899";
 
 // 71G4rRM4DugVRmAwEUtBNaw8xwGKZmSujwjFy37ErphW
 /// Alice is our sender, make sure there is funds in the account.
@@ -50,28 +46,6 @@ const BOB_KEY_BYTES: [u8; 64] = [
     99, 226,
 ];
 
-#[repr(packed(1))]
-/// Structure layout for the native init instruction
-struct InitLayout {
-    instruction: u8,
-    start_time: u64,
-    end_time: u64,
-    amount: u64,
-}
-
-#[repr(packed(1))]
-/// Structure layout for the native withdraw instruction
-struct WithdrawLayout {
-    instruction: u8,
-    amount: u64,
-}
-
-#[repr(packed(1))]
-/// Structure layout for the native cancel instruction
-struct CancelLayout {
-    instruction: u8,
-}
-
 fn usage() {
     println!("usage: strfi [nativeinit|nativewithdraw|nativecancel] [accountAddress]\n");
     println!("accountAddress is needed for withdraw/cancel");
@@ -83,11 +57,11 @@ fn keypair_from_some(envvar: &str, bytes: &[u8]) -> Result<Keypair, &'static str
         // TODO: Read key from file
         Ok(_v) => {
             println!("Loading {} key from file", envvar);
-            return Err("foo");
+            Err("foo")
         }
         Err(_) => match Keypair::from_bytes(bytes) {
-            Ok(v) => return Ok(v),
-            Err(_) => return Err("Could not parse key from bytes"),
+            Ok(v) => Ok(v),
+            Err(_) => Err("Could not parse key from bytes"),
         },
     }
 }
@@ -100,21 +74,7 @@ fn create_keypairs() -> Vec<Keypair> {
     res.push(alice);
     res.push(bob);
 
-    return res;
-}
-
-fn create_accountmeta(keys: Vec<(Pubkey, bool)>) -> Vec<AccountMeta> {
-    let mut ret: Vec<AccountMeta> = Vec::new();
-
-    for i in keys {
-        ret.push(AccountMeta::new(i.0, i.1));
-    }
-
-    return ret;
-}
-
-fn create_instruction(programid: Pubkey, ix: &[u8], keys: Vec<(Pubkey, bool)>) -> Instruction {
-    return Instruction::new_with_bytes(programid, ix, create_accountmeta(keys));
+    res
 }
 
 fn create_tx(
@@ -123,56 +83,58 @@ fn create_tx(
     payer: Option<&Pubkey>,
     signers: Vec<&Keypair>,
 ) -> Transaction {
-    let mut tx = Transaction::new_with_payer(&ix, payer);
+    let mut tx = Transaction::new_with_payer(ix, payer);
     let bhq = BlockhashQuery::default();
-    match bhq.get_blockhash_and_fee_calculator(&rpc, rpc.commitment()) {
+    match bhq.get_blockhash_and_fee_calculator(rpc, rpc.commitment()) {
         Err(_) => panic!("Couldn't connect to rpc"),
         Ok(v) => tx.sign(&signers, v.0),
     }
 
-    return tx;
+    tx
 }
 
 fn native_init(rpc: RpcClient) {
     let kps = create_keypairs();
-    let pda = Keypair::new();
-
-    println!("ALICE: {}", kps[0].pubkey());
-    println!("BOB:   {}", kps[1].pubkey());
-    println!("PDA:   {}", pda.pubkey());
+    let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
 
     let now = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap()
         .as_secs();
 
-    let layout = InitLayout {
-        instruction: 0,
-        start_time: now as u64 + 15,
-        end_time: now as u64 + 615,
+    // Use the current time as nonce, so every run creates a new stream.
+    let (pda, _) = find_stream_address(&program_id, &kps[0].pubkey(), &kps[1].pubkey(), now);
+
+    println!("ALICE: {}", kps[0].pubkey());
+    println!("BOB:   {}", kps[1].pubkey());
+    println!("PDA:   {}", pda);
+
+    let params = StreamParams {
+        start_time: now + 15,
+        end_time: now + 615,
         amount: 100000000,
+        cliff_time: 0,
+        cliff_amount: 0,
+        period: 0,
+        amount_per_period: 0,
+        period_unit: PeriodUnit::Seconds,
+        breakpoints: vec![],
+        rate: 0,
+        transfer_by_sender_only: false,
+        cancelable_by_sender: true,
+        cancelable_by_recipient: false,
+        rent_destination: None,
+        crank_fee_bps: 0,
     };
 
-    println!("instruction: {}", { layout.instruction });
-    println!("start_time: {}", { layout.start_time });
-    println!("end_time: {}", { layout.end_time });
-    println!("amount: {}", { layout.amount });
+    println!("start_time: {}", params.start_time);
+    println!("end_time: {}", params.end_time);
+    println!("amount: {}", params.amount);
+    println!("nonce: {}", now);
 
-    let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
-
-    let ix = create_instruction(
-        program_id,
-        unsafe { any_as_u8_slice(&layout) },
-        vec![
-            (kps[0].pubkey(), true),
-            (kps[1].pubkey(), false),
-            (pda.pubkey(), true),
-            (kps[1].pubkey(), false), // placeholder
-            (system_program::ID, false),
-        ],
-    );
+    let ix = initialize_native(&program_id, &kps[0].pubkey(), &kps[1].pubkey(), params, now);
 
-    let tx = create_tx(&rpc, &[ix], Some(&kps[0].pubkey()), vec![&kps[0], &pda]);
+    let tx = create_tx(&rpc, &[ix], Some(&kps[0].pubkey()), vec![&kps[0]]);
 
     println!(
         "{:#?}",
@@ -189,23 +151,13 @@ fn native_withdraw(rpc: RpcClient, accaddr: &str) {
     println!("BOB:   {}", kps[1].pubkey());
     println!("PDA:   {}", accaddr);
 
-    let layout = WithdrawLayout {
-        instruction: 1,
-        amount: 0, // 0 will withdraw everything that is unlocked
-    };
-
-    println!("instruction: {}", { layout.instruction });
-    println!("amount: {}", { layout.amount });
-
     let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
-    let lld_pub = Pubkey::from_str("DrFtxPb9F6SxpHHHFiEtSNXE3SZCUNLXMaHS6r8pkoz2").unwrap();
+    // The sender collects the rent, as no other destination was chosen.
+    let lld_pub = kps[0].pubkey();
     let pda_pub = Pubkey::from_str(accaddr).unwrap();
 
-    let ix = create_instruction(
-        program_id,
-        unsafe { any_as_u8_slice(&layout) },
-        vec![(kps[1].pubkey(), true), (pda_pub, false), (lld_pub, false)],
-    );
+    // 0 will withdraw everything that is unlocked
+    let ix = withdraw_native(&program_id, &kps[1].pubkey(), &pda_pub, &lld_pub, None, 0);
 
     let tx = create_tx(&rpc, &[ix], Some(&kps[1].pubkey()), vec![&kps[1]]);
 
@@ -216,6 +168,7 @@ fn native_withdraw(rpc: RpcClient, accaddr: &str) {
             .to_string()
     );
 }
+
 fn native_cancel(rpc: RpcClient, accaddr: &str) {
     let kps = create_keypairs();
 
@@ -223,24 +176,10 @@ fn native_cancel(rpc: RpcClient, accaddr: &str) {
     println!("BOB:   {}", kps[1].pubkey());
     println!("PDA:   {}", accaddr);
 
-    let layout = CancelLayout { instruction: 2 };
-
-    println!("instruction: {}", { layout.instruction });
-
     let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
-    let lld_pub = Pubkey::from_str("DrFtxPb9F6SxpHHHFiEtSNXE3SZCUNLXMaHS6r8pkoz2").unwrap();
     let pda_pub = Pubkey::from_str(accaddr).unwrap();
 
-    let ix = create_instruction(
-        program_id,
-        unsafe { any_as_u8_slice(&layout) },
-        vec![
-            (kps[0].pubkey(), true),
-            (kps[1].pubkey(), false),
-            (pda_pub, false),
-            (lld_pub, false),
-        ],
-    );
+    let ix = cancel_native(&program_id, &kps[0].pubkey(), &kps[1].pubkey(), &pda_pub);
 
     let tx = create_tx(&rpc, &[ix], Some(&kps[0].pubkey()), vec![&kps[0]]);
 
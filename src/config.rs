@@ -0,0 +1,158 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    entrypoint::ProgramResult,
+    msg,
+    program::invoke_signed,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_instruction, system_program,
+    sysvar::{rent::Rent, Sysvar},
+};
+
+use crate::{
+    error::StreamFlowError,
+    state::Config,
+    utils::{find_config_address, next_config_account, CONFIG_SEED_PREFIX},
+};
+
+/// Check `authority` is the upgrade authority of the program, according to
+/// its `program_data` account.
+fn check_upgrade_authority(
+    pid: &Pubkey,
+    program_data: &AccountInfo,
+    authority: &AccountInfo,
+) -> ProgramResult {
+    let (program_data_key, _) =
+        Pubkey::find_program_address(&[pid.as_ref()], &bpf_loader_upgradeable::id());
+    if program_data.key != &program_data_key || program_data.owner != &bpf_loader_upgradeable::id()
+    {
+        msg!("Mismatched program data account in [accounts]");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let upgrade_authority = match bincode::deserialize(&program_data.try_borrow_data()?) {
+        Ok(UpgradeableLoaderState::ProgramData {
+            upgrade_authority_address,
+            ..
+        }) => upgrade_authority_address,
+        _ => return Err(ProgramError::InvalidAccountData),
+    };
+
+    if upgrade_authority != Some(*authority.key) {
+        msg!("Only the program's upgrade authority can create the config");
+        return Err(StreamFlowError::UnauthorizedSigner.into());
+    }
+
+    Ok(())
+}
+
+/// Program function to create the program's config account.
+pub fn initialize_config(
+    pid: &Pubkey,
+    accounts: &[AccountInfo],
+    collector: Pubkey,
+) -> ProgramResult {
+    msg!("Requested initialize_config");
+    let account_info_iter = &mut accounts.iter();
+    let admin = next_account_info(account_info_iter)?;
+    let config_acc = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    let program_data = next_account_info(account_info_iter)?;
+
+    if system_program.key != &system_program::id() {
+        msg!("Mismatched System program address in [accounts]!");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    if !admin.is_signer || !admin.is_writable || !config_acc.is_writable {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    // Otherwise anyone could take over the config before the deployer.
+    check_upgrade_authority(pid, program_data, admin)?;
+
+    let (config_key, bump) = find_config_address(pid);
+    if config_acc.key != &config_key {
+        msg!("Mismatched config account in [accounts]");
+        return Err(StreamFlowError::InvalidConfigAccount.into());
+    }
+
+    if !config_acc.data_is_empty() {
+        msg!("Config is already initialized");
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    let cluster_rent = Rent::get()?;
+    invoke_signed(
+        &system_instruction::create_account(
+            admin.key,
+            config_acc.key,
+            cluster_rent.minimum_balance(Config::LEN),
+            Config::LEN as u64,
+            pid,
+        ),
+        &[admin.clone(), config_acc.clone(), system_program.clone()],
+        &[&[CONFIG_SEED_PREFIX, &[bump]]],
+    )?;
+
+    let config = Config::new(admin.key, &collector);
+    Config::pack(config, &mut config_acc.try_borrow_mut_data()?)?;
+
+    msg!("Admin:     {}", admin.key);
+    msg!("Collector: {}", collector);
+
+    Ok(())
+}
+
+/// Program function to update the program's config.
+pub fn set_config(
+    pid: &Pubkey,
+    accounts: &[AccountInfo],
+    new_admin: Pubkey,
+    collector: Pubkey,
+) -> ProgramResult {
+    msg!("Requested set_config");
+    let account_info_iter = &mut accounts.iter();
+    let admin = next_account_info(account_info_iter)?;
+    let (config_acc, mut config) = next_config_account(pid, account_info_iter)?;
+
+    if !admin.is_signer || !config_acc.is_writable {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    if admin.key.to_bytes() != config.admin {
+        return Err(StreamFlowError::UnauthorizedSigner.into());
+    }
+
+    if new_admin == Pubkey::default() {
+        msg!("The admin can't be removed");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    config.admin = new_admin.to_bytes();
+    config.collector = collector.to_bytes();
+    config.pack_into_slice(&mut config_acc.try_borrow_mut_data()?);
+
+    msg!("Admin:     {}", new_admin);
+    msg!("Collector: {}", collector);
+
+    Ok(())
+}
@@ -0,0 +1,133 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+use num_derive::FromPrimitive;
+use solana_program::{
+    decode_error::DecodeError, msg, program_error::PrintProgramError, program_error::ProgramError,
+};
+use thiserror::Error;
+
+/// Errors returned by the StreamFlow program.
+///
+/// The numeric values are returned to clients as `ProgramError::Custom`
+/// codes, so existing variants must never be renumbered.
+#[derive(Clone, Debug, Eq, Error, FromPrimitive, PartialEq)]
+pub enum StreamFlowError {
+    /// The signer is not allowed to perform this action on the stream
+    #[error("Signer is not authorized for this stream")]
+    UnauthorizedSigner = 0,
+    /// The given recipient does not match the stream's recipient
+    #[error("Recipient does not match the stream")]
+    WrongRecipient = 1,
+    /// The stream's timestamps are invalid
+    #[error("Invalid stream schedule")]
+    InvalidSchedule = 2,
+    /// The requested amount is larger than what is available
+    #[error("Amount exceeds available funds")]
+    AmountExceedsAvailable = 3,
+    /// The stream account has already been closed
+    #[error("Stream is already closed")]
+    StreamClosed = 4,
+    /// The stream account is not the expected program-derived address
+    #[error("Invalid stream account address")]
+    InvalidStreamAccount = 5,
+    /// The escrow account does not belong to the stream
+    #[error("Invalid escrow account")]
+    InvalidEscrow = 6,
+    /// The token account has the wrong owner or mint
+    #[error("Invalid token account")]
+    InvalidTokenAccount = 7,
+    /// The rent collection account is not the expected one
+    #[error("Invalid rent collector account")]
+    InvalidRentReaper = 8,
+    /// The schedule account doesn't belong to the stream
+    #[error("Invalid schedule account")]
+    InvalidScheduleAccount = 9,
+    /// The stream doesn't allow the signer to cancel it
+    #[error("Stream cancellation is not allowed")]
+    CancelNotAllowed = 10,
+    /// The stream is paused already
+    #[error("Stream is already paused")]
+    AlreadyPaused = 11,
+    /// The stream isn't paused
+    #[error("Stream is not paused")]
+    NotPaused = 12,
+    /// The stream still holds funds, so it can't be closed
+    #[error("Stream is not fully withdrawn")]
+    NotFullyWithdrawn = 13,
+    /// The config account is not the program's config
+    #[error("Invalid config account")]
+    InvalidConfigAccount = 14,
+    /// The stream's accounting doesn't add up
+    #[error("Stream accounting invariant violated")]
+    InvariantViolation = 15,
+    /// An amount or timestamp doesn't fit into its type
+    #[error("Arithmetic overflow")]
+    MathOverflow = 16,
+    /// A batch names the same recipient more than once
+    #[error("Duplicate recipient in batch")]
+    DuplicateRecipient = 17,
+}
+
+impl From<StreamFlowError> for ProgramError {
+    fn from(e: StreamFlowError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
+impl<T> DecodeError<T> for StreamFlowError {
+    fn type_of() -> &'static str {
+        "StreamFlowError"
+    }
+}
+
+impl PrintProgramError for StreamFlowError {
+    fn print<E>(&self)
+    where
+        E: 'static
+            + std::error::Error
+            + DecodeError<E>
+            + PrintProgramError
+            + num_traits::FromPrimitive,
+    {
+        match self {
+            StreamFlowError::UnauthorizedSigner => {
+                msg!("Error: Signer is not authorized for this stream")
+            }
+            StreamFlowError::WrongRecipient => msg!("Error: Recipient does not match the stream"),
+            StreamFlowError::InvalidSchedule => msg!("Error: Invalid stream schedule"),
+            StreamFlowError::AmountExceedsAvailable => {
+                msg!("Error: Amount exceeds available funds")
+            }
+            StreamFlowError::StreamClosed => msg!("Error: Stream is already closed"),
+            StreamFlowError::InvalidStreamAccount => msg!("Error: Invalid stream account address"),
+            StreamFlowError::InvalidEscrow => msg!("Error: Invalid escrow account"),
+            StreamFlowError::InvalidTokenAccount => msg!("Error: Invalid token account"),
+            StreamFlowError::InvalidRentReaper => msg!("Error: Invalid rent collector account"),
+            StreamFlowError::InvalidScheduleAccount => msg!("Error: Invalid schedule account"),
+            StreamFlowError::CancelNotAllowed => msg!("Error: Stream cancellation is not allowed"),
+            StreamFlowError::AlreadyPaused => msg!("Error: Stream is already paused"),
+            StreamFlowError::NotPaused => msg!("Error: Stream is not paused"),
+            StreamFlowError::NotFullyWithdrawn => msg!("Error: Stream is not fully withdrawn"),
+            StreamFlowError::InvalidConfigAccount => msg!("Error: Invalid config account"),
+            StreamFlowError::InvariantViolation => {
+                msg!("Error: Stream accounting invariant violated")
+            }
+            StreamFlowError::MathOverflow => msg!("Error: Arithmetic overflow"),
+            StreamFlowError::DuplicateRecipient => msg!("Error: Duplicate recipient in batch"),
+        }
+    }
+}
@@ -0,0 +1,1102 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+use std::convert::TryInto;
+
+use num_traits::FromPrimitive;
+use solana_program::{
+    bpf_loader_upgradeable,
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+
+use crate::utils::{
+    find_batch_metadata_address, find_config_address, find_escrow_address, find_schedule_address,
+    find_stream_address, Breakpoint, PeriodUnit, TopUpMode, MAX_BATCH_SIZE, MAX_BREAKPOINTS,
+};
+
+/// Parameters describing a new stream, common to all initialize instructions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StreamParams {
+    /// Timestamp when the funds start unlocking
+    pub start_time: u64,
+    /// Timestamp when all funds should be unlocked
+    pub end_time: u64,
+    /// Amount of funds to lock
+    pub amount: u64,
+    /// Timestamp before which nothing unlocks (0 for no cliff)
+    pub cliff_time: u64,
+    /// Amount of funds unlocked at once at `cliff_time`
+    pub cliff_amount: u64,
+    /// Seconds between releases (0 for continuous unlocking)
+    pub period: u64,
+    /// Amount of funds released every `period`
+    pub amount_per_period: u64,
+    /// Unit `period` is counted in
+    pub period_unit: PeriodUnit,
+    /// Breakpoints of a piecewise-linear unlock curve, which replaces
+    /// the schedule above if not empty
+    pub breakpoints: Vec<Breakpoint>,
+    /// Amount of funds unlocked every second by an open-ended stream,
+    /// which must have no end time or other schedule (0 for none)
+    pub rate: u64,
+    /// Whether only the sender, rather than the recipient, may transfer
+    /// the stream to a new recipient
+    pub transfer_by_sender_only: bool,
+    /// Whether the sender may cancel the stream
+    pub cancelable_by_sender: bool,
+    /// Whether the recipient may cancel the stream
+    pub cancelable_by_recipient: bool,
+    /// Account receiving the rent once the stream is closed (the sender
+    /// if `None`, which is sent as zeroes)
+    pub rent_destination: Option<Pubkey>,
+    /// Share of every crank withdrawal paid to whoever sends it, in basis
+    /// points of at most `MAX_CRANK_FEE_BPS`
+    pub crank_fee_bps: u16,
+}
+
+/// Instructions supported by the StreamFlow program.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StreamFlowInstruction {
+    /// Initialize a stream of native SOL.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Sender
+    /// 1. `[writable]` Recipient
+    /// 2. `[writable]` Stream account, derived with `find_stream_address`
+    /// 3. `[]` System program
+    /// 4. `[writable]` Schedule account, derived with `find_schedule_address`
+    ///    (only used with breakpoints)
+    InitializeNative {
+        /// Stream parameters
+        params: StreamParams,
+        /// Nonce used to derive the stream account
+        nonce: u64,
+    },
+    /// Withdraw unlocked native SOL to the recipient, or to a destination
+    /// of their choosing. An amount of 0 withdraws everything that is
+    /// currently unlocked.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Recipient (only writable without a destination)
+    /// 1. `[writable]` Stream account
+    /// 2. `[writable]` Rent destination, which gets the rent once the
+    ///    stream is fully withdrawn and closed
+    /// 3. `[]` Config account, derived with `find_config_address`
+    ///    (only read if this closes a stream without a rent destination)
+    /// 4. `[writable]` Schedule account (only used if the stream has one;
+    ///    otherwise its address, the program id or the system program has
+    ///    to be given before a destination)
+    /// 5. `[writable]` Destination (optional, the recipient if omitted)
+    WithdrawNative {
+        /// Amount of lamports to withdraw
+        amount: u64,
+    },
+    /// Cancel a stream of native SOL. Signed by the sender, or by the
+    /// recipient instead, as far as the stream allows either to cancel.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Sender
+    /// 1. `[writable]` Recipient (signer when cancelling as the recipient)
+    /// 2. `[writable]` Stream account
+    /// 3. `[writable]` Schedule account (only used if the stream has one)
+    CancelNative,
+    /// Initialize a stream of SPL tokens.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Sender
+    /// 1. `[writable]` Sender's token account
+    /// 2. `[writable]` Recipient
+    /// 3. `[writable]` Recipient's token account
+    /// 4. `[signer, writable]` Metadata account
+    /// 5. `[writable]` Escrow account, derived with `find_escrow_address`
+    /// 6. `[]` Token mint
+    /// 7. `[]` Rent sysvar
+    /// 8. `[]` StreamFlow program
+    /// 9. `[]` SPL token program
+    /// 10. `[]` System program
+    /// 11. `[writable]` Schedule account, derived with `find_schedule_address`
+    ///     (only used with breakpoints)
+    InitializeToken {
+        /// Stream parameters
+        params: StreamParams,
+    },
+    /// Withdraw unlocked SPL tokens to the recipient, or to a destination
+    /// of their choosing. An amount of 0 withdraws everything that is
+    /// currently unlocked.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Recipient
+    /// 1. `[writable]` Recipient's token account (only writable without a
+    ///    destination)
+    /// 2. `[writable]` Metadata account
+    /// 3. `[writable]` Escrow account
+    /// 4. `[]` SPL token program
+    /// 5. `[writable]` Rent destination, which gets the rent once the
+    ///    stream is fully withdrawn and closed
+    /// 6. `[]` Config account, derived with `find_config_address`
+    ///    (only read if this closes a stream without a rent destination)
+    /// 7. `[writable]` Schedule account (only used if the stream has one;
+    ///    otherwise its address, the program id or the system program has
+    ///    to be given before a destination)
+    /// 8. `[writable]` Destination token account of the stream's mint
+    ///    (optional, the recipient's token account if omitted)
+    WithdrawToken {
+        /// Amount of tokens to withdraw
+        amount: u64,
+    },
+    /// Cancel a stream of SPL tokens. Signed by the sender, or by the
+    /// recipient instead, as far as the stream allows either to cancel.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Sender
+    /// 1. `[writable]` Sender's token account
+    /// 2. `[]` Recipient (signer when cancelling as the recipient)
+    /// 3. `[writable]` Recipient's token account
+    /// 4. `[writable]` Metadata account
+    /// 5. `[writable]` Escrow account
+    /// 6. `[]` SPL token program
+    /// 7. `[writable]` Schedule account (only used if the stream has one)
+    CancelToken,
+    /// Add native SOL to a running stream. Anyone can fund a stream.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Funder
+    /// 1. `[writable]` Stream account
+    /// 2. `[]` System program
+    TopUpNative {
+        /// Amount of lamports to add
+        amount: u64,
+        /// How the stream's schedule takes the new funds in
+        mode: TopUpMode,
+    },
+    /// Add SPL tokens to a running stream. Anyone can fund a stream.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Funder
+    /// 1. `[writable]` Funder's token account
+    /// 2. `[writable]` Metadata account
+    /// 3. `[writable]` Escrow account
+    /// 4. `[]` SPL token program
+    TopUpToken {
+        /// Amount of tokens to add
+        amount: u64,
+        /// How the stream's schedule takes the new funds in
+        mode: TopUpMode,
+    },
+    /// Transfer a stream of native SOL or SPL tokens to a new recipient.
+    /// Signed by the current recipient, or by the sender if the stream
+    /// was created with `transfer_by_sender_only` and the sender can cancel
+    /// it.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Recipient, or sender
+    /// 1. `[writable]` Stream or metadata account
+    /// 2. `[]` New recipient
+    /// 3. `[]` New recipient's token account (only used for token streams)
+    TransferRecipient,
+    /// Pause a stream of native SOL or SPL tokens, so nothing more unlocks
+    /// until it's resumed.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Sender
+    /// 1. `[writable]` Stream or metadata account
+    Pause,
+    /// Resume a paused stream, shifting the rest of its schedule forward
+    /// by the time it spent paused.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Sender
+    /// 1. `[writable]` Stream or metadata account
+    Resume,
+    /// Close a fully withdrawn stream of native SOL. Anyone can close it,
+    /// as its rent goes to the rent destination chosen at initialization.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Stream account
+    /// 1. `[writable]` Rent destination
+    /// 2. `[]` Config account, derived with `find_config_address`
+    ///    (only read if the stream has no rent destination)
+    /// 3. `[writable]` Schedule account (only used if the stream has one)
+    CloseNative,
+    /// Close a fully withdrawn stream of SPL tokens. Anyone can close it,
+    /// as its rent goes to the rent destination chosen at initialization.
+    /// Tokens sent to the escrow on top of the stream go to the recipient.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Metadata account
+    /// 1. `[writable]` Escrow account
+    /// 2. `[writable]` Recipient's token account
+    /// 3. `[writable]` Rent destination
+    /// 4. `[]` SPL token program
+    /// 5. `[]` Config account, derived with `find_config_address`
+    ///    (only read if the stream has no rent destination)
+    /// 6. `[writable]` Schedule account (only used if the stream has one)
+    CloseToken,
+    /// Create the program's config account. This can only be done once,
+    /// by the program's upgrade authority, who becomes the admin.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Admin, paying for the account
+    /// 1. `[writable]` Config account, derived with `find_config_address`
+    /// 2. `[]` System program
+    /// 3. `[]` Program data account of this program, holding its upgrade
+    ///    authority
+    InitializeConfig {
+        /// Account collecting rent that no stream chose a destination for
+        collector: Pubkey,
+    },
+    /// Update the program's config.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Admin
+    /// 1. `[writable]` Config account
+    SetConfig {
+        /// New admin, which may be the current one
+        admin: Pubkey,
+        /// New collector
+        collector: Pubkey,
+    },
+    /// Withdraw everything unlocked from a stream of native SOL to its
+    /// recipient. Anyone can send it, and gets the stream's crank fee.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Caller
+    /// 1. `[writable]` Recipient
+    /// 2. `[writable]` Stream account
+    /// 3. `[writable]` Rent destination, which gets the rent once the
+    ///    stream is fully withdrawn and closed
+    /// 4. `[]` Config account, derived with `find_config_address`
+    ///    (only read if this closes a stream without a rent destination)
+    /// 5. `[writable]` Schedule account (only used if the stream has one)
+    CrankNative,
+    /// Withdraw everything unlocked from a stream of SPL tokens to the
+    /// recipient's token account. Anyone can send it, and gets the
+    /// stream's crank fee.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Caller
+    /// 1. `[writable]` Recipient's token account
+    /// 2. `[writable]` Metadata account
+    /// 3. `[writable]` Escrow account
+    /// 4. `[]` SPL token program
+    /// 5. `[writable]` Rent destination, which gets the rent once the
+    ///    stream is fully withdrawn and closed
+    /// 6. `[]` Config account, derived with `find_config_address`
+    ///    (only read if this closes a stream without a rent destination)
+    /// 7. `[writable]` Schedule account (only used if the stream has one;
+    ///    otherwise its address, the program id or the system program has
+    ///    to be given before the caller's token account)
+    /// 8. `[writable]` Caller's token account of the stream's mint (only
+    ///    used if the stream pays a crank fee)
+    CrankToken,
+    /// Initialize up to `MAX_BATCH_SIZE` streams of native SOL from the
+    /// same sender at once, checking the sender's funds for all of them
+    /// together. Streams in a batch can't have breakpoints, and every
+    /// recipient can only get one of them.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Sender
+    /// 1. `[]` System program
+    /// 2. `[writable]` Recipient of the first stream
+    /// 3. `[writable]` First stream account, derived with
+    ///    `find_stream_address`
+    ///
+    /// followed by the recipient and stream account of every other stream.
+    CreateBatchNative {
+        /// Stream parameters, one for each stream
+        entries: Vec<StreamParams>,
+        /// Nonce used to derive all of the stream accounts
+        nonce: u64,
+    },
+    /// Initialize up to `MAX_BATCH_SIZE` streams of SPL tokens from the
+    /// same sender at once, checking the sender's funds for all of them
+    /// together. Streams in a batch can't have breakpoints, and their
+    /// metadata accounts are derived from the batch rather than signing
+    /// themselves, so one recipient can get several streams of a batch.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Sender
+    /// 1. `[writable]` Sender's token account
+    /// 2. `[]` Token mint
+    /// 3. `[]` Rent sysvar
+    /// 4. `[]` SPL token program
+    /// 5. `[]` System program
+    /// 6. `[writable]` Recipient of the first stream
+    /// 7. `[writable]` Recipient's token account of the first stream
+    /// 8. `[writable]` First metadata account, derived with
+    ///    `find_batch_metadata_address` from its index in the batch
+    /// 9. `[writable]` First escrow account, derived with
+    ///    `find_escrow_address`
+    ///
+    /// followed by the same four accounts of every other stream.
+    CreateBatchToken {
+        /// Stream parameters, one for each stream
+        entries: Vec<StreamParams>,
+        /// Nonce used to derive all of the metadata accounts
+        nonce: u64,
+    },
+}
+
+impl StreamFlowInstruction {
+    /// Unpack a byte buffer into a StreamFlowInstruction.
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        let (&tag, rest) = input
+            .split_first()
+            .ok_or(ProgramError::InvalidInstructionData)?;
+
+        let (instruction, rest) = match tag {
+            0 => {
+                let (params, rest) = Self::unpack_stream_params(rest)?;
+                let (nonce, rest) = Self::unpack_u64(rest)?;
+                (Self::InitializeNative { params, nonce }, rest)
+            }
+            1 => {
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                (Self::WithdrawNative { amount }, rest)
+            }
+            2 => (Self::CancelNative, rest),
+            3 => {
+                let (params, rest) = Self::unpack_stream_params(rest)?;
+                (Self::InitializeToken { params }, rest)
+            }
+            4 => {
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                (Self::WithdrawToken { amount }, rest)
+            }
+            5 => (Self::CancelToken, rest),
+            6 => {
+                let (amount, mode, rest) = Self::unpack_top_up(rest)?;
+                (Self::TopUpNative { amount, mode }, rest)
+            }
+            7 => {
+                let (amount, mode, rest) = Self::unpack_top_up(rest)?;
+                (Self::TopUpToken { amount, mode }, rest)
+            }
+            8 => (Self::TransferRecipient, rest),
+            9 => (Self::Pause, rest),
+            10 => (Self::Resume, rest),
+            11 => (Self::CloseNative, rest),
+            12 => (Self::CloseToken, rest),
+            13 => {
+                let (collector, rest) = Self::unpack_pubkey(rest)?;
+                (Self::InitializeConfig { collector }, rest)
+            }
+            14 => {
+                let (admin, rest) = Self::unpack_pubkey(rest)?;
+                let (collector, rest) = Self::unpack_pubkey(rest)?;
+                (Self::SetConfig { admin, collector }, rest)
+            }
+            15 => (Self::CrankNative, rest),
+            16 => (Self::CrankToken, rest),
+            17 => {
+                let (entries, nonce, rest) = Self::unpack_batch(rest)?;
+                (Self::CreateBatchNative { entries, nonce }, rest)
+            }
+            18 => {
+                let (entries, nonce, rest) = Self::unpack_batch(rest)?;
+                (Self::CreateBatchToken { entries, nonce }, rest)
+            }
+            _ => return Err(ProgramError::InvalidInstructionData),
+        };
+
+        // Trailing bytes mean the client built the instruction wrong.
+        if !rest.is_empty() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(instruction)
+    }
+
+    /// Pack a StreamFlowInstruction into a byte buffer.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(std::mem::size_of::<Self>());
+        match self {
+            Self::InitializeNative { params, nonce } => {
+                buf.push(0);
+                Self::pack_stream_params(params, &mut buf);
+                buf.extend_from_slice(&nonce.to_le_bytes());
+            }
+            Self::WithdrawNative { amount } => {
+                buf.push(1);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::CancelNative => buf.push(2),
+            Self::InitializeToken { params } => {
+                buf.push(3);
+                Self::pack_stream_params(params, &mut buf);
+            }
+            Self::WithdrawToken { amount } => {
+                buf.push(4);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::CancelToken => buf.push(5),
+            Self::TopUpNative { amount, mode } => {
+                buf.push(6);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.push(*mode as u8);
+            }
+            Self::TopUpToken { amount, mode } => {
+                buf.push(7);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.push(*mode as u8);
+            }
+            Self::TransferRecipient => buf.push(8),
+            Self::Pause => buf.push(9),
+            Self::Resume => buf.push(10),
+            Self::CloseNative => buf.push(11),
+            Self::CloseToken => buf.push(12),
+            Self::InitializeConfig { collector } => {
+                buf.push(13);
+                buf.extend_from_slice(collector.as_ref());
+            }
+            Self::SetConfig { admin, collector } => {
+                buf.push(14);
+                buf.extend_from_slice(admin.as_ref());
+                buf.extend_from_slice(collector.as_ref());
+            }
+            Self::CrankNative => buf.push(15),
+            Self::CrankToken => buf.push(16),
+            Self::CreateBatchNative { entries, nonce } => {
+                buf.push(17);
+                Self::pack_batch(entries, *nonce, &mut buf);
+            }
+            Self::CreateBatchToken { entries, nonce } => {
+                buf.push(18);
+                Self::pack_batch(entries, *nonce, &mut buf);
+            }
+        }
+        buf
+    }
+
+    fn unpack_u64(input: &[u8]) -> Result<(u64, &[u8]), ProgramError> {
+        if input.len() < 8 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let (value, rest) = input.split_at(8);
+        Ok((u64::from_le_bytes(value.try_into().unwrap()), rest))
+    }
+
+    fn unpack_u16(input: &[u8]) -> Result<(u16, &[u8]), ProgramError> {
+        if input.len() < 2 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let (value, rest) = input.split_at(2);
+        Ok((u16::from_le_bytes(value.try_into().unwrap()), rest))
+    }
+
+    fn unpack_pubkey(input: &[u8]) -> Result<(Pubkey, &[u8]), ProgramError> {
+        if input.len() < 32 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let (key, rest) = input.split_at(32);
+        Ok((Pubkey::new(key), rest))
+    }
+
+    fn unpack_bool(input: &[u8]) -> Result<(bool, &[u8]), ProgramError> {
+        match input.split_first() {
+            Some((0, rest)) => Ok((false, rest)),
+            Some((1, rest)) => Ok((true, rest)),
+            _ => Err(ProgramError::InvalidInstructionData),
+        }
+    }
+
+    fn unpack_top_up(input: &[u8]) -> Result<(u64, TopUpMode, &[u8]), ProgramError> {
+        let (amount, rest) = Self::unpack_u64(input)?;
+        let (&mode, rest) = rest
+            .split_first()
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        let mode = TopUpMode::from_u8(mode).ok_or(ProgramError::InvalidInstructionData)?;
+        Ok((amount, mode, rest))
+    }
+
+    fn unpack_batch(input: &[u8]) -> Result<(Vec<StreamParams>, u64, &[u8]), ProgramError> {
+        let (nonce, rest) = Self::unpack_u64(input)?;
+        let (&len, mut rest) = rest
+            .split_first()
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        if len == 0 || len as usize > MAX_BATCH_SIZE {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let mut entries = Vec::with_capacity(len as usize);
+        for _ in 0..len {
+            let (params, r) = Self::unpack_stream_params(rest)?;
+            entries.push(params);
+            rest = r;
+        }
+        Ok((entries, nonce, rest))
+    }
+
+    fn unpack_stream_params(input: &[u8]) -> Result<(StreamParams, &[u8]), ProgramError> {
+        let (start_time, rest) = Self::unpack_u64(input)?;
+        let (end_time, rest) = Self::unpack_u64(rest)?;
+        let (amount, rest) = Self::unpack_u64(rest)?;
+        let (cliff_time, rest) = Self::unpack_u64(rest)?;
+        let (cliff_amount, rest) = Self::unpack_u64(rest)?;
+        let (period, rest) = Self::unpack_u64(rest)?;
+        let (amount_per_period, rest) = Self::unpack_u64(rest)?;
+        let (&period_unit, rest) = rest
+            .split_first()
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        let period_unit =
+            PeriodUnit::from_u8(period_unit).ok_or(ProgramError::InvalidInstructionData)?;
+        let (&len, mut rest) = rest
+            .split_first()
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        if len as usize > MAX_BREAKPOINTS {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let mut breakpoints = Vec::with_capacity(len as usize);
+        for _ in 0..len {
+            let (timestamp, r) = Self::unpack_u64(rest)?;
+            let (cumulative, r) = Self::unpack_u64(r)?;
+            breakpoints.push(Breakpoint {
+                timestamp,
+                cumulative,
+            });
+            rest = r;
+        }
+        let (rate, rest) = Self::unpack_u64(rest)?;
+        let (transfer_by_sender_only, rest) = Self::unpack_bool(rest)?;
+        let (cancelable_by_sender, rest) = Self::unpack_bool(rest)?;
+        let (cancelable_by_recipient, rest) = Self::unpack_bool(rest)?;
+        let (rent_destination, rest) = Self::unpack_pubkey(rest)?;
+        let rent_destination = Some(rent_destination).filter(|k| k != &Pubkey::default());
+        let (crank_fee_bps, rest) = Self::unpack_u16(rest)?;
+        Ok((
+            StreamParams {
+                start_time,
+                end_time,
+                amount,
+                cliff_time,
+                cliff_amount,
+                period,
+                amount_per_period,
+                period_unit,
+                breakpoints,
+                rate,
+                transfer_by_sender_only,
+                cancelable_by_sender,
+                cancelable_by_recipient,
+                rent_destination,
+                crank_fee_bps,
+            },
+            rest,
+        ))
+    }
+
+    fn pack_batch(entries: &[StreamParams], nonce: u64, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&nonce.to_le_bytes());
+        buf.push(entries.len() as u8);
+        for params in entries {
+            Self::pack_stream_params(params, buf);
+        }
+    }
+
+    fn pack_stream_params(params: &StreamParams, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&params.start_time.to_le_bytes());
+        buf.extend_from_slice(&params.end_time.to_le_bytes());
+        buf.extend_from_slice(&params.amount.to_le_bytes());
+        buf.extend_from_slice(&params.cliff_time.to_le_bytes());
+        buf.extend_from_slice(&params.cliff_amount.to_le_bytes());
+        buf.extend_from_slice(&params.period.to_le_bytes());
+        buf.extend_from_slice(&params.amount_per_period.to_le_bytes());
+        buf.push(params.period_unit as u8);
+        buf.push(params.breakpoints.len() as u8);
+        for b in &params.breakpoints {
+            buf.extend_from_slice(&b.timestamp.to_le_bytes());
+            buf.extend_from_slice(&b.cumulative.to_le_bytes());
+        }
+        buf.extend_from_slice(&params.rate.to_le_bytes());
+        buf.push(params.transfer_by_sender_only as u8);
+        buf.push(params.cancelable_by_sender as u8);
+        buf.push(params.cancelable_by_recipient as u8);
+        buf.extend_from_slice(params.rent_destination.unwrap_or_default().as_ref());
+        buf.extend_from_slice(&params.crank_fee_bps.to_le_bytes());
+    }
+}
+
+/// Creates an `InitializeNative` instruction. The stream account is
+/// derived from the sender, recipient and nonce.
+pub fn initialize_native(
+    program_id: &Pubkey,
+    sender: &Pubkey,
+    recipient: &Pubkey,
+    params: StreamParams,
+    nonce: u64,
+) -> Instruction {
+    let (stream, _) = find_stream_address(program_id, sender, recipient, nonce);
+    let (schedule, _) = find_schedule_address(program_id, &stream);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*sender, true),
+            AccountMeta::new(*recipient, false),
+            AccountMeta::new(stream, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(schedule, false),
+        ],
+        data: StreamFlowInstruction::InitializeNative { params, nonce }.pack(),
+    }
+}
+
+/// Creates a `WithdrawNative` instruction, paying out to `destination`
+/// if given, or to the recipient otherwise.
+pub fn withdraw_native(
+    program_id: &Pubkey,
+    recipient: &Pubkey,
+    stream: &Pubkey,
+    rent_destination: &Pubkey,
+    destination: Option<&Pubkey>,
+    amount: u64,
+) -> Instruction {
+    let (schedule, _) = find_schedule_address(program_id, stream);
+    let (config, _) = find_config_address(program_id);
+    let mut accounts = vec![
+        AccountMeta::new(*recipient, true),
+        AccountMeta::new(*stream, false),
+        AccountMeta::new(*rent_destination, false),
+        AccountMeta::new_readonly(config, false),
+        AccountMeta::new(schedule, false),
+    ];
+    if let Some(destination) = destination {
+        accounts[0].is_writable = false;
+        accounts.push(AccountMeta::new(*destination, false));
+    }
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StreamFlowInstruction::WithdrawNative { amount }.pack(),
+    }
+}
+
+/// Creates a `CancelNative` instruction.
+pub fn cancel_native(
+    program_id: &Pubkey,
+    sender: &Pubkey,
+    recipient: &Pubkey,
+    stream: &Pubkey,
+) -> Instruction {
+    let (schedule, _) = find_schedule_address(program_id, stream);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*sender, true),
+            AccountMeta::new(*recipient, false),
+            AccountMeta::new(*stream, false),
+            AccountMeta::new(schedule, false),
+        ],
+        data: StreamFlowInstruction::CancelNative.pack(),
+    }
+}
+
+/// Creates an `InitializeToken` instruction. The escrow account is
+/// derived from the metadata account, which has to sign the transaction.
+#[allow(clippy::too_many_arguments)]
+pub fn initialize_token(
+    program_id: &Pubkey,
+    sender: &Pubkey,
+    sender_tokens: &Pubkey,
+    recipient: &Pubkey,
+    recipient_tokens: &Pubkey,
+    metadata: &Pubkey,
+    mint: &Pubkey,
+    params: StreamParams,
+) -> Instruction {
+    let (escrow, _) = find_escrow_address(program_id, metadata);
+    let (schedule, _) = find_schedule_address(program_id, metadata);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*sender, true),
+            AccountMeta::new(*sender_tokens, false),
+            AccountMeta::new(*recipient, false),
+            AccountMeta::new(*recipient_tokens, false),
+            AccountMeta::new(*metadata, true),
+            AccountMeta::new(escrow, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(*program_id, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(schedule, false),
+        ],
+        data: StreamFlowInstruction::InitializeToken { params }.pack(),
+    }
+}
+
+/// Creates a `WithdrawToken` instruction, paying out to the token account
+/// `destination` if given, or to the recipient's token account otherwise.
+pub fn withdraw_token(
+    program_id: &Pubkey,
+    recipient: &Pubkey,
+    recipient_tokens: &Pubkey,
+    metadata: &Pubkey,
+    rent_destination: &Pubkey,
+    destination: Option<&Pubkey>,
+    amount: u64,
+) -> Instruction {
+    let (escrow, _) = find_escrow_address(program_id, metadata);
+    let (schedule, _) = find_schedule_address(program_id, metadata);
+    let (config, _) = find_config_address(program_id);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*recipient, true),
+        AccountMeta::new(*recipient_tokens, false),
+        AccountMeta::new(*metadata, false),
+        AccountMeta::new(escrow, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(*rent_destination, false),
+        AccountMeta::new_readonly(config, false),
+        AccountMeta::new(schedule, false),
+    ];
+    if let Some(destination) = destination {
+        accounts[1].is_writable = false;
+        accounts.push(AccountMeta::new(*destination, false));
+    }
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StreamFlowInstruction::WithdrawToken { amount }.pack(),
+    }
+}
+
+/// Creates a `CancelToken` instruction.
+pub fn cancel_token(
+    program_id: &Pubkey,
+    sender: &Pubkey,
+    sender_tokens: &Pubkey,
+    recipient: &Pubkey,
+    recipient_tokens: &Pubkey,
+    metadata: &Pubkey,
+) -> Instruction {
+    let (escrow, _) = find_escrow_address(program_id, metadata);
+    let (schedule, _) = find_schedule_address(program_id, metadata);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*sender, true),
+            AccountMeta::new(*sender_tokens, false),
+            AccountMeta::new_readonly(*recipient, false),
+            AccountMeta::new(*recipient_tokens, false),
+            AccountMeta::new(*metadata, false),
+            AccountMeta::new(escrow, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(schedule, false),
+        ],
+        data: StreamFlowInstruction::CancelToken.pack(),
+    }
+}
+
+/// Creates a `TopUpNative` instruction.
+pub fn top_up_native(
+    program_id: &Pubkey,
+    funder: &Pubkey,
+    stream: &Pubkey,
+    amount: u64,
+    mode: TopUpMode,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*funder, true),
+            AccountMeta::new(*stream, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: StreamFlowInstruction::TopUpNative { amount, mode }.pack(),
+    }
+}
+
+/// Creates a `TopUpToken` instruction.
+pub fn top_up_token(
+    program_id: &Pubkey,
+    funder: &Pubkey,
+    funder_tokens: &Pubkey,
+    metadata: &Pubkey,
+    amount: u64,
+    mode: TopUpMode,
+) -> Instruction {
+    let (escrow, _) = find_escrow_address(program_id, metadata);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*funder, true),
+            AccountMeta::new(*funder_tokens, false),
+            AccountMeta::new(*metadata, false),
+            AccountMeta::new(escrow, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: StreamFlowInstruction::TopUpToken { amount, mode }.pack(),
+    }
+}
+
+/// Creates a `TransferRecipient` instruction. `new_recipient_tokens` is
+/// only needed for token streams.
+pub fn transfer_recipient(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    stream: &Pubkey,
+    new_recipient: &Pubkey,
+    new_recipient_tokens: Option<&Pubkey>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*authority, true),
+        AccountMeta::new(*stream, false),
+        AccountMeta::new_readonly(*new_recipient, false),
+    ];
+    if let Some(tokens) = new_recipient_tokens {
+        accounts.push(AccountMeta::new_readonly(*tokens, false));
+    }
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StreamFlowInstruction::TransferRecipient.pack(),
+    }
+}
+
+/// Creates a `Pause` instruction.
+pub fn pause(program_id: &Pubkey, sender: &Pubkey, stream: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*sender, true),
+            AccountMeta::new(*stream, false),
+        ],
+        data: StreamFlowInstruction::Pause.pack(),
+    }
+}
+
+/// Creates a `Resume` instruction.
+pub fn resume(program_id: &Pubkey, sender: &Pubkey, stream: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*sender, true),
+            AccountMeta::new(*stream, false),
+        ],
+        data: StreamFlowInstruction::Resume.pack(),
+    }
+}
+
+/// Creates a `CloseNative` instruction.
+pub fn close_native(
+    program_id: &Pubkey,
+    stream: &Pubkey,
+    rent_destination: &Pubkey,
+) -> Instruction {
+    let (schedule, _) = find_schedule_address(program_id, stream);
+    let (config, _) = find_config_address(program_id);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*stream, false),
+            AccountMeta::new(*rent_destination, false),
+            AccountMeta::new_readonly(config, false),
+            AccountMeta::new(schedule, false),
+        ],
+        data: StreamFlowInstruction::CloseNative.pack(),
+    }
+}
+
+/// Creates a `CloseToken` instruction.
+pub fn close_token(
+    program_id: &Pubkey,
+    metadata: &Pubkey,
+    recipient_tokens: &Pubkey,
+    rent_destination: &Pubkey,
+) -> Instruction {
+    let (escrow, _) = find_escrow_address(program_id, metadata);
+    let (schedule, _) = find_schedule_address(program_id, metadata);
+    let (config, _) = find_config_address(program_id);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*metadata, false),
+            AccountMeta::new(escrow, false),
+            AccountMeta::new(*recipient_tokens, false),
+            AccountMeta::new(*rent_destination, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(config, false),
+            AccountMeta::new(schedule, false),
+        ],
+        data: StreamFlowInstruction::CloseToken.pack(),
+    }
+}
+
+/// Creates a `CrankNative` instruction, which `caller` can send for any
+/// stream.
+pub fn crank_native(
+    program_id: &Pubkey,
+    caller: &Pubkey,
+    recipient: &Pubkey,
+    stream: &Pubkey,
+    rent_destination: &Pubkey,
+) -> Instruction {
+    let (schedule, _) = find_schedule_address(program_id, stream);
+    let (config, _) = find_config_address(program_id);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*caller, true),
+            AccountMeta::new(*recipient, false),
+            AccountMeta::new(*stream, false),
+            AccountMeta::new(*rent_destination, false),
+            AccountMeta::new_readonly(config, false),
+            AccountMeta::new(schedule, false),
+        ],
+        data: StreamFlowInstruction::CrankNative.pack(),
+    }
+}
+
+/// Creates a `CrankToken` instruction, which `caller` can send for any
+/// stream. `caller_tokens` is only needed for streams paying a crank fee.
+pub fn crank_token(
+    program_id: &Pubkey,
+    caller: &Pubkey,
+    recipient_tokens: &Pubkey,
+    metadata: &Pubkey,
+    rent_destination: &Pubkey,
+    caller_tokens: Option<&Pubkey>,
+) -> Instruction {
+    let (escrow, _) = find_escrow_address(program_id, metadata);
+    let (schedule, _) = find_schedule_address(program_id, metadata);
+    let (config, _) = find_config_address(program_id);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*caller, true),
+        AccountMeta::new(*recipient_tokens, false),
+        AccountMeta::new(*metadata, false),
+        AccountMeta::new(escrow, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(*rent_destination, false),
+        AccountMeta::new_readonly(config, false),
+        AccountMeta::new(schedule, false),
+    ];
+    if let Some(tokens) = caller_tokens {
+        accounts.push(AccountMeta::new(*tokens, false));
+    }
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StreamFlowInstruction::CrankToken.pack(),
+    }
+}
+
+/// Creates a `CreateBatchNative` instruction from the recipient and the
+/// parameters of every stream. The stream accounts are derived from the
+/// sender, each recipient and the nonce.
+pub fn create_batch_native(
+    program_id: &Pubkey,
+    sender: &Pubkey,
+    entries: Vec<(Pubkey, StreamParams)>,
+    nonce: u64,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*sender, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    for (recipient, _) in &entries {
+        let (stream, _) = find_stream_address(program_id, sender, recipient, nonce);
+        accounts.push(AccountMeta::new(*recipient, false));
+        accounts.push(AccountMeta::new(stream, false));
+    }
+    let entries = entries.into_iter().map(|(_, params)| params).collect();
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StreamFlowInstruction::CreateBatchNative { entries, nonce }.pack(),
+    }
+}
+
+/// Creates a `CreateBatchToken` instruction from the recipient, their
+/// token account and the parameters of every stream. The metadata
+/// accounts are derived from the sender, the mint, the nonce and the
+/// position of each stream in the batch.
+pub fn create_batch_token(
+    program_id: &Pubkey,
+    sender: &Pubkey,
+    sender_tokens: &Pubkey,
+    mint: &Pubkey,
+    entries: Vec<(Pubkey, Pubkey, StreamParams)>,
+    nonce: u64,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*sender, true),
+        AccountMeta::new(*sender_tokens, false),
+        AccountMeta::new_readonly(*mint, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    for (index, (recipient, recipient_tokens, _)) in entries.iter().enumerate() {
+        let (metadata, _) =
+            find_batch_metadata_address(program_id, sender, mint, nonce, index as u8);
+        let (escrow, _) = find_escrow_address(program_id, &metadata);
+        accounts.push(AccountMeta::new(*recipient, false));
+        accounts.push(AccountMeta::new(*recipient_tokens, false));
+        accounts.push(AccountMeta::new(metadata, false));
+        accounts.push(AccountMeta::new(escrow, false));
+    }
+    let entries = entries.into_iter().map(|(_, _, params)| params).collect();
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StreamFlowInstruction::CreateBatchToken { entries, nonce }.pack(),
+    }
+}
+
+/// Creates an `InitializeConfig` instruction, which `admin` has to send as
+/// the program's upgrade authority.
+pub fn initialize_config(program_id: &Pubkey, admin: &Pubkey, collector: &Pubkey) -> Instruction {
+    let (config, _) = find_config_address(program_id);
+    let (program_data, _) =
+        Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*admin, true),
+            AccountMeta::new(config, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(program_data, false),
+        ],
+        data: StreamFlowInstruction::InitializeConfig {
+            collector: *collector,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a `SetConfig` instruction.
+pub fn set_config(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    new_admin: &Pubkey,
+    collector: &Pubkey,
+) -> Instruction {
+    let (config, _) = find_config_address(program_id);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(config, false),
+        ],
+        data: StreamFlowInstruction::SetConfig {
+            admin: *new_admin,
+            collector: *collector,
+        }
+        .pack(),
+    }
+}
@@ -14,21 +14,53 @@
 //
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
+pub mod config;
+pub mod error;
+pub mod instruction;
+pub mod pause;
+pub mod sol_batch;
 pub mod sol_cancel;
+pub mod sol_close;
+pub mod sol_crank;
 pub mod sol_initialize;
+pub mod sol_top_up;
 pub mod sol_withdraw;
+pub mod state;
+pub mod tok_batch;
+pub mod tok_cancel;
+pub mod tok_close;
+pub mod tok_crank;
 pub mod tok_initialize;
+pub mod tok_top_up;
+pub mod tok_withdraw;
+pub mod transfer;
 pub mod utils;
 
 use solana_program::{
     account_info::AccountInfo, entrypoint, entrypoint::ProgramResult, msg,
-    program_error::ProgramError, pubkey::Pubkey,
+    program_error::PrintProgramError, pubkey::Pubkey,
 };
 
+use error::StreamFlowError;
+use instruction::StreamFlowInstruction;
+
+use config::{initialize_config, set_config};
+use pause::{pause_stream, resume_stream};
+use sol_batch::sol_create_batch;
 use sol_cancel::sol_cancel_stream;
+use sol_close::sol_close_stream;
+use sol_crank::sol_crank_withdraw;
 use sol_initialize::sol_initialize_stream;
+use sol_top_up::sol_top_up_stream;
 use sol_withdraw::sol_withdraw_unlocked;
+use tok_batch::tok_create_batch;
+use tok_cancel::tok_cancel_stream;
+use tok_close::tok_close_stream;
+use tok_crank::tok_crank_withdraw;
 use tok_initialize::tok_initialize_stream;
+use tok_top_up::tok_top_up_stream;
+use tok_withdraw::tok_withdraw_unlocked;
+use transfer::transfer_recipient;
 
 entrypoint!(process_instruction);
 /// The program entrypoint
@@ -44,16 +76,55 @@ pub fn process_instruction(
         env!("CARGO_PKG_VERSION_PATCH")
     );
 
-    match instruction_data[0] {
+    let result = match StreamFlowInstruction::unpack(instruction_data)? {
         // These are for native SOL
-        0 => sol_initialize_stream(program_id, accounts, instruction_data),
-        1 => sol_withdraw_unlocked(program_id, accounts, instruction_data),
-        2 => sol_cancel_stream(program_id, accounts, instruction_data),
+        StreamFlowInstruction::InitializeNative { params, nonce } => {
+            sol_initialize_stream(program_id, accounts, params, nonce)
+        }
+        StreamFlowInstruction::WithdrawNative { amount } => {
+            sol_withdraw_unlocked(program_id, accounts, amount)
+        }
+        StreamFlowInstruction::CancelNative => sol_cancel_stream(program_id, accounts),
+        StreamFlowInstruction::TopUpNative { amount, mode } => {
+            sol_top_up_stream(program_id, accounts, amount, mode)
+        }
+        StreamFlowInstruction::CloseNative => sol_close_stream(program_id, accounts),
+        StreamFlowInstruction::CrankNative => sol_crank_withdraw(program_id, accounts),
+        StreamFlowInstruction::CreateBatchNative { entries, nonce } => {
+            sol_create_batch(program_id, accounts, entries, nonce)
+        }
         // These are for SPL tokens
-        3 => tok_initialize_stream(program_id, accounts, instruction_data),
-        // 4 => tok_withdraw_unlocked(program_id, accounts, instruction_data),
-        // 5 => tok_cancel_stream(program_id, accounts, instruction_data),
-        // Invalid
-        _ => Err(ProgramError::InvalidArgument),
+        StreamFlowInstruction::InitializeToken { params } => {
+            tok_initialize_stream(program_id, accounts, params)
+        }
+        StreamFlowInstruction::WithdrawToken { amount } => {
+            tok_withdraw_unlocked(program_id, accounts, amount)
+        }
+        StreamFlowInstruction::CancelToken => tok_cancel_stream(program_id, accounts),
+        StreamFlowInstruction::TopUpToken { amount, mode } => {
+            tok_top_up_stream(program_id, accounts, amount, mode)
+        }
+        StreamFlowInstruction::CloseToken => tok_close_stream(program_id, accounts),
+        StreamFlowInstruction::CrankToken => tok_crank_withdraw(program_id, accounts),
+        StreamFlowInstruction::CreateBatchToken { entries, nonce } => {
+            tok_create_batch(program_id, accounts, entries, nonce)
+        }
+        // These are for both
+        StreamFlowInstruction::TransferRecipient => transfer_recipient(program_id, accounts),
+        StreamFlowInstruction::Pause => pause_stream(program_id, accounts),
+        StreamFlowInstruction::Resume => resume_stream(program_id, accounts),
+        // These are for the program itself
+        StreamFlowInstruction::InitializeConfig { collector } => {
+            initialize_config(program_id, accounts, collector)
+        }
+        StreamFlowInstruction::SetConfig { admin, collector } => {
+            set_config(program_id, accounts, admin, collector)
+        }
+    };
+
+    if let Err(error) = &result {
+        error.print::<StreamFlowError>();
     }
+
+    result
 }
@@ -0,0 +1,86 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    sysvar::{clock::Clock, Sysvar},
+};
+
+use crate::{error::StreamFlowError, state::StreamFlow};
+
+/// Program function to pause a stream.
+pub fn pause_stream(pid: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    msg!("Requested stream pause");
+    update_stream(pid, accounts, |sf, now| sf.pause(now))?;
+    msg!("Stream paused");
+    Ok(())
+}
+
+/// Program function to resume a paused stream.
+pub fn resume_stream(pid: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    msg!("Requested stream resume");
+    update_stream(pid, accounts, |sf, now| sf.resume(now))?;
+    msg!("Stream resumed");
+    Ok(())
+}
+
+/// Apply `f` to the stream in the given accounts, on behalf of its sender.
+fn update_stream<F>(pid: &Pubkey, accounts: &[AccountInfo], f: F) -> ProgramResult
+where
+    F: FnOnce(&mut StreamFlow, u64) -> ProgramResult,
+{
+    let account_info_iter = &mut accounts.iter();
+    let alice = next_account_info(account_info_iter)?;
+    let data_acc = next_account_info(account_info_iter)?;
+
+    if !alice.is_signer || !data_acc.is_writable {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    if data_acc.data_is_empty() {
+        return Err(StreamFlowError::StreamClosed.into());
+    }
+
+    if data_acc.owner != pid {
+        return Err(ProgramError::IllegalOwner);
+    }
+
+    let mut data = data_acc.try_borrow_mut_data()?;
+    let mut sf = StreamFlow::unpack_from_slice(&data)?;
+
+    if alice.key.to_bytes() != sf.sender {
+        msg!("Unauthorized to pause or resume for {}", alice.key);
+        return Err(StreamFlowError::UnauthorizedSigner.into());
+    }
+
+    // Nothing moves here, but a stream that lost track of its funds
+    // shouldn't be touched either.
+    sf.remaining()?;
+
+    let now = Clock::get()?.unix_timestamp as u64;
+    f(&mut sf, now)?;
+    sf.pack_into_slice(&mut data);
+
+    msg!("Stream account: {}", data_acc.key);
+    msg!("Paused for {} seconds so far", sf.paused_time);
+
+    Ok(())
+}
@@ -0,0 +1,167 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    native_token::lamports_to_sol,
+    program::invoke_signed,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_instruction, system_program,
+    sysvar::{clock::Clock, fees::Fees, rent::Rent, Sysvar},
+};
+
+use crate::{
+    error::StreamFlowError,
+    instruction::StreamParams,
+    state::StreamFlow,
+    utils::{
+        check_native_funds, find_stream_address, stream_sanity, transfer_lamports,
+        STREAM_SEED_PREFIX,
+    },
+};
+
+/// Program function to initialize several streams of native SOL at once.
+pub fn sol_create_batch(
+    pid: &Pubkey,
+    accounts: &[AccountInfo],
+    entries: Vec<StreamParams>,
+    nonce: u64,
+) -> ProgramResult {
+    msg!(
+        "Requested native SOL create_batch of {} streams",
+        entries.len()
+    );
+    let account_info_iter = &mut accounts.iter();
+    let alice = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    if system_program.key != &system_program::id() {
+        msg!("Mismatched System program address in [accounts]");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    if !alice.is_signer || !alice.is_writable {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let now = Clock::get()?.unix_timestamp as u64;
+    let rent = Rent::get()?.minimum_balance(StreamFlow::LEN);
+    let fee = Fees::get()?
+        .fee_calculator
+        .lamports_per_signature
+        .checked_mul(2)
+        .ok_or(StreamFlowError::MathOverflow)?;
+
+    // Check every stream, and the funds for all of them, before creating
+    // any, so the whole batch either succeeds or fails.
+    let mut streams: Vec<(&AccountInfo, &AccountInfo, StreamFlow)> =
+        Vec::with_capacity(entries.len());
+    let mut funds: u64 = 0;
+    for params in &entries {
+        let bob = next_account_info(account_info_iter)?;
+        let pda = next_account_info(account_info_iter)?;
+
+        // Streams of a batch are derived from their recipient, so each
+        // recipient can only get one of them.
+        if streams.iter().any(|(other, _, _)| other.key == bob.key) {
+            msg!("{} appears more than once in the batch", bob.key);
+            return Err(StreamFlowError::DuplicateRecipient.into());
+        }
+
+        let (pda_key, bump) = find_stream_address(pid, alice.key, bob.key, nonce);
+        if pda.key != &pda_key {
+            msg!("Mismatched stream account address for {}", bob.key);
+            return Err(StreamFlowError::InvalidStreamAccount.into());
+        }
+
+        if !pda.data_is_empty() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+
+        if !bob.is_writable || !pda.is_writable {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        if !params.breakpoints.is_empty() {
+            msg!("Streams in a batch can't have breakpoints");
+            return Err(StreamFlowError::InvalidSchedule.into());
+        }
+
+        let mut sf = StreamFlow::new(params, alice.key, bob.key, bob.key);
+        sf.nonce = nonce;
+        sf.bump = bump;
+        if !stream_sanity(now, &sf, &[]) {
+            return Err(StreamFlowError::InvalidSchedule.into());
+        }
+
+        funds = sf
+            .amount
+            .checked_add(rent)
+            .and_then(|f| f.checked_add(funds))
+            .ok_or(StreamFlowError::MathOverflow)?;
+        streams.push((bob, pda, sf));
+    }
+
+    if alice.lamports() < funds {
+        msg!("Not enough funds in sender's account to initialize the streams");
+        return Err(ProgramError::InsufficientFunds);
+    }
+
+    for (bob, pda, mut sf) in streams {
+        let lamports = sf
+            .amount
+            .checked_add(rent)
+            .ok_or(StreamFlowError::MathOverflow)?;
+        invoke_signed(
+            &system_instruction::create_account(
+                alice.key,
+                pda.key,
+                lamports,
+                StreamFlow::LEN as u64,
+                pid,
+            ),
+            &[alice.clone(), pda.clone(), system_program.clone()],
+            &[&[
+                STREAM_SEED_PREFIX,
+                alice.key.as_ref(),
+                bob.key.as_ref(),
+                &nonce.to_le_bytes(),
+                &[sf.bump],
+            ]],
+        )?;
+
+        // Send enough for one transaction to Bob, as for a single stream.
+        transfer_lamports(pda, bob, fee)?;
+        sf.withdrawn = fee;
+        check_native_funds(pda, &sf)?;
+        sf.pack_into_slice(&mut pda.try_borrow_mut_data()?);
+
+        msg!(
+            "Initialized {} SOL ({} lamports) stream for {}",
+            lamports_to_sol(sf.amount),
+            sf.amount,
+            bob.key
+        );
+        msg!("Funds locked in account: {}", pda.key);
+    }
+
+    msg!("Called by account: {}", alice.key);
+    Ok(())
+}
@@ -20,55 +20,71 @@ use solana_program::{
     msg,
     native_token::lamports_to_sol,
     program_error::ProgramError,
+    program_pack::Pack,
     pubkey::Pubkey,
     sysvar::{clock::Clock, Sysvar},
 };
 
-use crate::utils::{calculate_streamed, unpack_account_data};
+use crate::{
+    error::StreamFlowError,
+    state::StreamFlow,
+    utils::{check_native_funds, close_program_account, next_schedule_account, transfer_lamports},
+};
 
 /// Program function to cancel an initialized stream of funds.
-pub fn sol_cancel_stream(pid: &Pubkey, accounts: &[AccountInfo], _ix: &[u8]) -> ProgramResult {
+pub fn sol_cancel_stream(pid: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
     msg!("Requested stream cancellation");
     let account_info_iter = &mut accounts.iter();
     let alice = next_account_info(account_info_iter)?;
     let bob = next_account_info(account_info_iter)?;
     let pda = next_account_info(account_info_iter)?;
 
-    if !alice.is_signer || !alice.is_writable || !bob.is_writable || !pda.is_writable {
+    if !alice.is_writable || !bob.is_writable || !pda.is_writable {
         return Err(ProgramError::MissingRequiredSignature);
     }
 
-    if pda.data_is_empty() || pda.owner != pid {
-        return Err(ProgramError::UninitializedAccount);
+    if pda.data_is_empty() {
+        return Err(StreamFlowError::StreamClosed.into());
+    }
+
+    if pda.owner != pid {
+        return Err(ProgramError::IllegalOwner);
     }
 
     let data = pda.try_borrow_data()?;
-    let sf = unpack_account_data(&data);
+    let sf = StreamFlow::unpack_from_slice(&data)?;
 
     if alice.key.to_bytes() != sf.sender {
         msg!("Unauthorized to withdraw for {}", alice.key);
-        return Err(ProgramError::MissingRequiredSignature);
+        return Err(StreamFlowError::UnauthorizedSigner.into());
     }
 
     if bob.key.to_bytes() != sf.recipient {
         msg!("This stream isn't intended for {}", bob.key);
-        return Err(ProgramError::MissingRequiredSignature);
+        return Err(StreamFlowError::WrongRecipient.into());
     }
 
+    sf.check_cancel(alice.is_signer, bob.is_signer)?;
+    check_native_funds(pda, &sf)?;
+
     // Current cluster time used to calculate unlocked amount.
     let now = Clock::get()?.unix_timestamp as u64;
 
+    let schedule = next_schedule_account(pid, pda, &sf, account_info_iter)?;
+
     // Transfer what was unlocked but not withdrawn to Bob.
-    let amount_unlocked = calculate_streamed(now, sf.start_time, sf.end_time, sf.amount);
-    let available = amount_unlocked - sf.withdrawn;
-    **pda.try_borrow_mut_lamports()? -= available;
-    **bob.try_borrow_mut_lamports()? += available;
+    let available = sf.available(now, schedule.as_ref().map(|(_, s)| s))?;
+    transfer_lamports(pda, bob, available)?;
 
     // Alice decides to cancel, and withdraws from the derived account,
     // resulting in its purge.
-    let remains = pda.lamports();
-    **pda.try_borrow_mut_lamports()? -= remains;
-    **alice.try_borrow_mut_lamports()? += remains;
+    drop(data);
+    let remains = close_program_account(pda, alice)?;
+
+    // The schedule goes away with the stream.
+    if let Some((schedule_acc, _)) = schedule {
+        close_program_account(schedule_acc, alice)?;
+    }
 
     msg!("Successfully cancelled stream on {} ", pda.key);
     msg!(
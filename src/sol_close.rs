@@ -0,0 +1,79 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    native_token::lamports_to_sol,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+
+use crate::{
+    error::StreamFlowError,
+    state::StreamFlow,
+    utils::{check_rent_destination, close_program_account, next_schedule_account},
+};
+
+/// Program function to close a fully withdrawn stream of native SOL.
+pub fn sol_close_stream(pid: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    msg!("Requested native SOL stream close");
+    let account_info_iter = &mut accounts.iter();
+    let pda = next_account_info(account_info_iter)?;
+    let lld = next_account_info(account_info_iter)?;
+
+    if !pda.is_writable || !lld.is_writable {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    if pda.data_is_empty() {
+        return Err(StreamFlowError::StreamClosed.into());
+    }
+
+    if pda.owner != pid {
+        return Err(ProgramError::IllegalOwner);
+    }
+
+    let sf = StreamFlow::unpack_from_slice(&pda.try_borrow_data()?)?;
+
+    let config_acc = next_account_info(account_info_iter)?;
+    check_rent_destination(pid, &sf, lld, config_acc)?;
+
+    let remaining = sf.remaining()?;
+    if remaining > 0 {
+        msg!("Remaining: {} lamports", remaining);
+        return Err(StreamFlowError::NotFullyWithdrawn.into());
+    }
+
+    let schedule = next_schedule_account(pid, pda, &sf, account_info_iter)?;
+
+    let rent = close_program_account(pda, lld)?;
+    if let Some((schedule_acc, _)) = schedule {
+        close_program_account(schedule_acc, lld)?;
+    }
+
+    msg!("Successfully closed stream on {}", pda.key);
+    msg!(
+        "Returned {} SOL ({} lamports) of rent to {}",
+        lamports_to_sol(rent),
+        rent,
+        lld.key
+    );
+
+    Ok(())
+}
@@ -0,0 +1,129 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    native_token::lamports_to_sol,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    sysvar::{clock::Clock, Sysvar},
+};
+
+use crate::{
+    error::StreamFlowError,
+    state::StreamFlow,
+    utils::{
+        check_native_funds, check_rent_destination, close_program_account, next_schedule_account,
+        transfer_lamports,
+    },
+};
+
+/// Program function to withdraw all unlocked funds to the recipient on
+/// their behalf.
+pub fn sol_crank_withdraw(pid: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    msg!("Requested crank withdraw of unlocked funds");
+    let account_info_iter = &mut accounts.iter();
+    let caller = next_account_info(account_info_iter)?;
+    let bob = next_account_info(account_info_iter)?;
+    let pda = next_account_info(account_info_iter)?;
+    let lld = next_account_info(account_info_iter)?;
+
+    if !caller.is_signer || !bob.is_writable || !pda.is_writable || !lld.is_writable {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    if pda.data_is_empty() {
+        return Err(StreamFlowError::StreamClosed.into());
+    }
+
+    if pda.owner != pid {
+        return Err(ProgramError::IllegalOwner);
+    }
+
+    let mut sf = StreamFlow::unpack_from_slice(&pda.try_borrow_data()?)?;
+
+    // Whoever sends this, the funds only ever go to the recipient.
+    if bob.key.to_bytes() != sf.recipient {
+        msg!("This stream isn't intended for {}", bob.key);
+        return Err(StreamFlowError::WrongRecipient.into());
+    }
+
+    // Only read once the stream is closed.
+    let config_acc = next_account_info(account_info_iter)?;
+    check_native_funds(pda, &sf)?;
+
+    // Current cluster time used to calculate unlocked amount.
+    let now = Clock::get()?.unix_timestamp as u64;
+
+    let schedule = next_schedule_account(pid, pda, &sf, account_info_iter)?;
+    let available = sf.available(now, schedule.as_ref().map(|(_, s)| s))?;
+    if available == 0 {
+        msg!("Nothing is unlocked to be withdrawn");
+        return Err(StreamFlowError::AmountExceedsAvailable.into());
+    }
+
+    let fee = sf.crank_fee(available);
+    let payout = available
+        .checked_sub(fee)
+        .ok_or(StreamFlowError::InvariantViolation)?;
+    if fee > 0 && !caller.is_writable {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    transfer_lamports(pda, bob, payout)?;
+    transfer_lamports(pda, caller, fee)?;
+
+    // Update account data, accounting for exactly what was transferred.
+    sf.withdrawn = sf
+        .withdrawn
+        .checked_add(available)
+        .ok_or(StreamFlowError::MathOverflow)?;
+    check_native_funds(pda, &sf)?;
+    sf.pack_into_slice(&mut pda.try_borrow_mut_data()?);
+
+    msg!(
+        "Successfully withdrawn: {} SOL ({} lamports) to {}",
+        lamports_to_sol(payout),
+        payout,
+        bob.key
+    );
+    msg!(
+        "Crank fee: {} SOL ({} lamports) to {}",
+        lamports_to_sol(fee),
+        fee,
+        caller.key
+    );
+
+    // Collect rent after stream is finished.
+    if sf.is_withdrawn() {
+        check_rent_destination(pid, &sf, lld, config_acc)?;
+        let rent = close_program_account(pda, lld)?;
+        if let Some((schedule_acc, _)) = schedule {
+            close_program_account(schedule_acc, lld)?;
+        }
+        msg!(
+            "Closed stream, returned {} SOL ({} lamports) of rent to {}",
+            lamports_to_sol(rent),
+            rent,
+            lld.key
+        );
+    }
+
+    Ok(())
+}
@@ -19,17 +19,31 @@ use solana_program::{
     entrypoint::ProgramResult,
     msg,
     native_token::lamports_to_sol,
-    program::invoke,
+    program::invoke_signed,
     program_error::ProgramError,
+    program_pack::Pack,
     pubkey::Pubkey,
     system_instruction,
     sysvar::{clock::Clock, fees::Fees, rent::Rent, Sysvar},
 };
 
-use crate::utils::{any_as_u8_slice, duration_sanity, unpack_init_instruction, StreamFlow};
+use crate::{
+    error::StreamFlowError,
+    instruction::StreamParams,
+    state::StreamFlow,
+    utils::{
+        check_native_funds, create_schedule_account, find_stream_address, stream_sanity,
+        transfer_lamports, STREAM_SEED_PREFIX,
+    },
+};
 
 /// Program function to initialize a stream of native SOL.
-pub fn sol_initialize_stream(pid: &Pubkey, accounts: &[AccountInfo], ix: &[u8]) -> ProgramResult {
+pub fn sol_initialize_stream(
+    pid: &Pubkey,
+    accounts: &[AccountInfo],
+    params: StreamParams,
+    nonce: u64,
+) -> ProgramResult {
     msg!("Requested native SOL initialize_stream");
     let account_info_iter = &mut accounts.iter();
     let alice = next_account_info(account_info_iter)?;
@@ -39,63 +53,84 @@ pub fn sol_initialize_stream(pid: &Pubkey, accounts: &[AccountInfo], ix: &[u8])
 
     // TODO: Organize so all sanity checks are before doing something.
 
-    if ix.len() != 25 {
-        return Err(ProgramError::InvalidInstructionData);
+    // The stream account is derived from both parties and a nonce, so it
+    // can always be found again without storing any extra keypair.
+    let (pda_key, bump) = find_stream_address(pid, alice.key, bob.key, nonce);
+    if pda.key != &pda_key {
+        msg!("Mismatched stream account address in [accounts]");
+        return Err(StreamFlowError::InvalidStreamAccount.into());
     }
 
     if !pda.data_is_empty() {
         return Err(ProgramError::AccountAlreadyInitialized);
     }
 
-    if !alice.is_writable
-        || !bob.is_writable
-        || !pda.is_writable
-        || !alice.is_signer
-        || !pda.is_signer
-    {
+    if !alice.is_writable || !bob.is_writable || !pda.is_writable || !alice.is_signer {
         return Err(ProgramError::MissingRequiredSignature);
     }
 
-    let mut sf = unpack_init_instruction(ix, alice.key, bob.key, bob.key);
-    let struct_size = std::mem::size_of::<StreamFlow>();
+    let mut sf = StreamFlow::new(&params, alice.key, bob.key, bob.key);
+    sf.nonce = nonce;
+    sf.bump = bump;
+    let struct_size = StreamFlow::LEN;
 
     // We also transfer enough to be rent-exempt (about 0.00156 SOL) to the
     // new account. After all funds are withdrawn and unlocked, this might
     // be returned to the initializer or put in another pool for future reuse.
     let cluster_rent = Rent::get()?;
-    if alice.lamports() < sf.amount + cluster_rent.minimum_balance(struct_size) {
+    let funds = sf
+        .amount
+        .checked_add(cluster_rent.minimum_balance(struct_size))
+        .ok_or(StreamFlowError::MathOverflow)?;
+    if alice.lamports() < funds {
         msg!("Not enough funds in sender's account to initialize stream");
         return Err(ProgramError::InsufficientFunds);
     }
 
     let now = Clock::get()?.unix_timestamp as u64;
-    if !duration_sanity(now, sf.start_time, sf.end_time) {
-        return Err(ProgramError::InvalidArgument);
+    let breakpoints = params.breakpoints;
+    if !stream_sanity(now, &sf, &breakpoints) {
+        return Err(StreamFlowError::InvalidSchedule.into());
     }
 
     // Create the account holding locked funds and data
-    invoke(
-        &system_instruction::create_account(
-            &alice.key,
-            &pda.key,
-            sf.amount + cluster_rent.minimum_balance(struct_size),
-            struct_size as u64,
-            &pid,
-        ),
+    invoke_signed(
+        &system_instruction::create_account(alice.key, pda.key, funds, struct_size as u64, pid),
         &[alice.clone(), pda.clone(), system_program.clone()],
+        &[&[
+            STREAM_SEED_PREFIX,
+            alice.key.as_ref(),
+            bob.key.as_ref(),
+            &nonce.to_le_bytes(),
+            &[bump],
+        ]],
     )?;
 
     // Send enough for one transaction to Bob, so Bob can do an initial
     // withdraw without having previous funds on their account.
     let fees = Fees::get()?;
-    **pda.try_borrow_mut_lamports()? -= fees.fee_calculator.lamports_per_signature * 2;
-    **bob.try_borrow_mut_lamports()? += fees.fee_calculator.lamports_per_signature * 2;
-    sf.withdrawn += fees.fee_calculator.lamports_per_signature * 2;
+    let fee = fees
+        .fee_calculator
+        .lamports_per_signature
+        .checked_mul(2)
+        .ok_or(StreamFlowError::MathOverflow)?;
+    transfer_lamports(pda, bob, fee)?;
+    sf.withdrawn = sf
+        .withdrawn
+        .checked_add(fee)
+        .ok_or(StreamFlowError::MathOverflow)?;
+    check_native_funds(pda, &sf)?;
+
+    // Custom unlock curves live in their own account next to the stream.
+    if !breakpoints.is_empty() {
+        let schedule_acc = next_account_info(account_info_iter)?;
+        create_schedule_account(pid, alice, pda, schedule_acc, system_program, breakpoints)?;
+        sf.schedule = schedule_acc.key.to_bytes();
+    }
 
     // Write our metadata to pda's data.
     let mut data = pda.try_borrow_mut_data()?;
-    let bytes: &[u8] = unsafe { any_as_u8_slice(&sf) };
-    data[0..bytes.len()].clone_from_slice(bytes);
+    sf.pack_into_slice(&mut data);
 
     msg!(
         "Successfully initialized {} SOL ({} lamports) stream for: {}",
@@ -105,7 +140,14 @@ pub fn sol_initialize_stream(pid: &Pubkey, accounts: &[AccountInfo], ix: &[u8])
     );
     msg!("Called by account: {}", alice.key);
     msg!("Funds locked in account: {}", pda.key);
-    msg!("Stream duration: {} seconds", sf.end_time - sf.start_time);
+    if sf.rate > 0 {
+        msg!("Stream rate:     {} per second", sf.rate);
+    } else {
+        msg!(
+            "Stream duration: {} seconds",
+            sf.end_time.saturating_sub(sf.start_time)
+        );
+    }
 
     Ok(())
 }
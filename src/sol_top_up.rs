@@ -0,0 +1,93 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    native_token::lamports_to_sol,
+    program::invoke,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_instruction, system_program,
+    sysvar::{clock::Clock, Sysvar},
+};
+
+use crate::{
+    error::StreamFlowError,
+    state::StreamFlow,
+    utils::{check_native_funds, TopUpMode},
+};
+
+/// Program function to add funds to a stream of native SOL.
+pub fn sol_top_up_stream(
+    pid: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+    mode: TopUpMode,
+) -> ProgramResult {
+    msg!("Requested native SOL top_up_stream");
+    let account_info_iter = &mut accounts.iter();
+    let funder = next_account_info(account_info_iter)?;
+    let pda = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    if system_program.key != &system_program::id() {
+        msg!("Mismatched System program address in [accounts]!");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    if !funder.is_signer || !funder.is_writable || !pda.is_writable {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    if pda.data_is_empty() {
+        return Err(StreamFlowError::StreamClosed.into());
+    }
+
+    if pda.owner != pid {
+        return Err(ProgramError::IllegalOwner);
+    }
+
+    if amount == 0 {
+        msg!("Nothing to top up");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let mut sf = StreamFlow::unpack_from_slice(&pda.try_borrow_data()?)?;
+
+    let now = Clock::get()?.unix_timestamp as u64;
+    sf.top_up(now, amount, mode)?;
+
+    invoke(
+        &system_instruction::transfer(funder.key, pda.key, amount),
+        &[funder.clone(), pda.clone(), system_program.clone()],
+    )?;
+
+    check_native_funds(pda, &sf)?;
+    sf.pack_into_slice(&mut pda.try_borrow_mut_data()?);
+
+    msg!(
+        "Successfully topped up: {} SOL ({} lamports)",
+        lamports_to_sol(amount),
+        amount
+    );
+    msg!("Stream amount: {} lamports", sf.amount);
+    msg!("Stream end:    {}", sf.end_time);
+
+    Ok(())
+}
@@ -14,68 +14,65 @@
 //
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
-use std::convert::TryInto;
-use std::str::FromStr;
-
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
     msg,
     native_token::lamports_to_sol,
     program_error::ProgramError,
+    program_pack::Pack,
     pubkey::Pubkey,
     sysvar::{clock::Clock, Sysvar},
 };
 
-use crate::utils::{any_as_u8_slice, calculate_streamed, unpack_account_data};
+use crate::{
+    error::StreamFlowError,
+    state::StreamFlow,
+    utils::{
+        check_native_funds, check_rent_destination, close_program_account,
+        next_destination_account, next_schedule_account, transfer_lamports,
+    },
+};
 
 /// Program function to withdraw unlocked funds.
-pub fn sol_withdraw_unlocked(pid: &Pubkey, accounts: &[AccountInfo], ix: &[u8]) -> ProgramResult {
+pub fn sol_withdraw_unlocked(pid: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
     msg!("Requested withdraw of unlocked funds");
     let account_info_iter = &mut accounts.iter();
     let bob = next_account_info(account_info_iter)?;
     let pda = next_account_info(account_info_iter)?;
     let lld = next_account_info(account_info_iter)?;
 
-    if ix.len() != 9 {
-        return Err(ProgramError::InvalidInstructionData);
-    }
-
-    // Hardcoded rent collector
-    let rent_reaper = Pubkey::from_str("DrFtxPb9F6SxpHHHFiEtSNXE3SZCUNLXMaHS6r8pkoz2").unwrap();
-    if lld.key != &rent_reaper {
-        msg!("Got unexpected rent collection account");
-        return Err(ProgramError::InvalidAccountData);
+    if !bob.is_signer || !pda.is_writable || !lld.is_writable {
+        return Err(ProgramError::MissingRequiredSignature);
     }
 
-    if !bob.is_signer || !bob.is_writable || !pda.is_writable || !lld.is_writable {
-        return Err(ProgramError::MissingRequiredSignature);
+    if pda.data_is_empty() {
+        return Err(StreamFlowError::StreamClosed.into());
     }
 
-    if pda.data_is_empty() || pda.owner != pid {
-        return Err(ProgramError::UninitializedAccount);
+    if pda.owner != pid {
+        return Err(ProgramError::IllegalOwner);
     }
 
-    let mut data = pda.try_borrow_mut_data()?;
-    let mut sf = unpack_account_data(&data);
+    let mut sf = StreamFlow::unpack_from_slice(&pda.try_borrow_data()?)?;
 
     if bob.key.to_bytes() != sf.recipient {
-        msg!("This stream isn't indented for {}", bob.key);
-        return Err(ProgramError::MissingRequiredSignature);
+        msg!("This stream isn't intended for {}", bob.key);
+        return Err(StreamFlowError::WrongRecipient.into());
     }
 
+    // Only read once the stream is closed.
+    let config_acc = next_account_info(account_info_iter)?;
+    check_native_funds(pda, &sf)?;
+
     // Current cluster time used to calculate unlocked amount.
     let now = Clock::get()?.unix_timestamp as u64;
 
-    let amount_unlocked = calculate_streamed(now, sf.start_time, sf.end_time, sf.amount);
-    let mut available = amount_unlocked - sf.withdrawn;
-
-    // In case we're past the set time, everything is available.
-    if now >= sf.end_time {
-        available = sf.amount - sf.withdrawn;
-    }
+    let schedule = next_schedule_account(pid, pda, &sf, account_info_iter)?;
+    let destination = next_destination_account(pid, pda, &sf, bob, account_info_iter)?;
+    let available = sf.available(now, schedule.as_ref().map(|(_, s)| s))?;
 
-    let mut requested = u64::from_le_bytes(ix[1..9].try_into().unwrap());
+    let mut requested = amount;
     if requested == 0 {
         requested = available;
     }
@@ -92,36 +89,46 @@ pub fn sol_withdraw_unlocked(pid: &Pubkey, accounts: &[AccountInfo], ix: &[u8])
             lamports_to_sol(available),
             available
         );
-        return Err(ProgramError::InvalidArgument);
+        return Err(StreamFlowError::AmountExceedsAvailable.into());
     }
 
-    **pda.try_borrow_mut_lamports()? -= requested;
-    **bob.try_borrow_mut_lamports()? += requested;
+    transfer_lamports(pda, destination, requested)?;
 
-    // Update account data
-    sf.withdrawn += available as u64;
-    let bytes: &[u8] = unsafe { any_as_u8_slice(&sf) };
-    data[0..bytes.len()].clone_from_slice(bytes);
+    // Update account data, accounting for exactly what was transferred.
+    sf.withdrawn = sf
+        .withdrawn
+        .checked_add(requested)
+        .ok_or(StreamFlowError::MathOverflow)?;
+    check_native_funds(pda, &sf)?;
+    sf.pack_into_slice(&mut pda.try_borrow_mut_data()?);
 
+    let remaining = sf.remaining()?;
     msg!(
-        "Successfully withdrawn: {} SOL ({} lamports)",
-        lamports_to_sol(available),
-        available
+        "Successfully withdrawn: {} SOL ({} lamports) to {}",
+        lamports_to_sol(requested),
+        requested,
+        destination.key
     );
     msg!(
         "Remaining: {} SOL ({} lamports)",
-        lamports_to_sol(sf.amount - sf.withdrawn),
-        sf.amount - sf.withdrawn
+        lamports_to_sol(remaining),
+        remaining
     );
 
-    /*
-    if sf.withdrawn == sf.amount {
-        // Collect rent after stream is finished.
-        let rent = pda.lamports();
-        **pda.try_borrow_mut_lamports()? -= rent;
-        **lld.try_borrow_mut_lamports()? += rent;
+    // Collect rent after stream is finished.
+    if sf.is_withdrawn() {
+        check_rent_destination(pid, &sf, lld, config_acc)?;
+        let rent = close_program_account(pda, lld)?;
+        if let Some((schedule_acc, _)) = schedule {
+            close_program_account(schedule_acc, lld)?;
+        }
+        msg!(
+            "Closed stream, returned {} SOL ({} lamports) of rent to {}",
+            lamports_to_sol(rent),
+            rent,
+            lld.key
+        );
     }
-    */
 
     Ok(())
 }
@@ -0,0 +1,736 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+use std::convert::TryInto;
+
+use num_traits::FromPrimitive;
+use solana_program::{
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+    pubkey::Pubkey,
+};
+
+use crate::{
+    error::StreamFlowError,
+    instruction::StreamParams,
+    utils::{
+        calculate_piecewise, calculate_rate_duration, calculate_rate_streamed, calculate_streamed,
+        Breakpoint, LinearSchedule, PeriodUnit, TopUpMode, MAX_BREAKPOINTS,
+    },
+};
+
+/// Discriminator every versioned stream account starts with.
+pub const STREAM_MAGIC: [u8; 8] = *b"STRMFLOW";
+
+/// Layout version written to newly created stream accounts.
+///
+/// Version 0 is the original layout without a header, holding only the
+/// times, amounts and the four pubkeys. Every later version starts with
+/// `STREAM_MAGIC` and the version byte, followed by the fields of the
+/// versions before it, and finally the fields it adds. New fields are only
+/// ever appended, so an account keeps its version (and size) for its whole
+/// life and is read and written in that same layout.
+pub const STREAM_VERSION: u8 = 1;
+
+/// Size of a version 0 stream account.
+pub const LEGACY_STREAM_LEN: usize = 4 * 8 + 4 * 32;
+
+/// StreamFlow is the struct containing all our necessary metadata.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StreamFlow {
+    /// Layout version of the account this was read from
+    pub version: u8,
+    /// Timestamp when the funds start unlocking
+    pub start_time: u64,
+    /// Timestamp when all funds should be unlocked
+    pub end_time: u64,
+    /// Amount of funds locked
+    pub amount: u64,
+    /// Amount of funds withdrawn
+    pub withdrawn: u64,
+    /// Pubkey of the program initializer
+    pub sender: [u8; 32],
+    /// Pubkey of the funds' recipient
+    pub recipient: [u8; 32],
+    /// Pubkey of the token mint (can be zeroes for native SOL)
+    pub mint: [u8; 32],
+    /// Pubkey of the account holding the locked tokens
+    /// (should be zeroes for native SOL)
+    pub escrow: [u8; 32],
+    /// Nonce used to derive the stream account's address
+    pub nonce: u64,
+    /// Bump seed of the stream account's program-derived address
+    pub bump: u8,
+    /// Bump seed of the escrow's program-derived address
+    pub escrow_bump: u8,
+    /// Timestamp before which nothing unlocks (0 for no cliff)
+    pub cliff_time: u64,
+    /// Amount of funds unlocked at once at `cliff_time`
+    pub cliff_amount: u64,
+    /// Seconds between releases (0 for continuous unlocking)
+    pub period: u64,
+    /// Amount of funds released every `period`
+    pub amount_per_period: u64,
+    /// Unit `period` is counted in
+    pub period_unit: PeriodUnit,
+    /// Pubkey of the account holding a piecewise-linear unlock schedule,
+    /// which replaces all of the above (zeroes if there's none)
+    pub schedule: [u8; 32],
+    /// Amount of funds unlocked every second by an open-ended stream, which
+    /// has no end time and runs until `amount` is used up (0 if it has a
+    /// fixed schedule)
+    pub rate: u64,
+    /// Pubkey of the recipient's token account the stream pays out to
+    /// (zeroes for native SOL, and for streams created before it was kept)
+    pub recipient_tokens: [u8; 32],
+    /// Whether only the sender, rather than the recipient, may transfer
+    /// the stream to a new recipient
+    pub transfer_by_sender_only: bool,
+    /// Whether the sender may cancel the stream (always true for streams
+    /// created before this was configurable)
+    pub cancelable_by_sender: bool,
+    /// Whether the recipient may cancel the stream
+    pub cancelable_by_recipient: bool,
+    /// Timestamp the stream was paused at (0 if it's running)
+    pub paused_at: u64,
+    /// Seconds the stream spent paused before its last resume, by which
+    /// the whole schedule is shifted forward
+    pub paused_time: u64,
+    /// Pubkey of the account receiving the rent once the stream is closed
+    /// (zeroes for streams created before this was configurable, whose
+    /// rent goes to the collector in the program's config)
+    pub rent_destination: [u8; 32],
+    /// Share of every crank withdrawal paid to whoever sends it, in basis
+    /// points (0 for no fee)
+    pub crank_fee_bps: u16,
+}
+
+impl StreamFlow {
+    /// Create the metadata of a new stream from the given parameters.
+    pub fn new(params: &StreamParams, alice: &Pubkey, bob: &Pubkey, mint: &Pubkey) -> Self {
+        StreamFlow {
+            version: STREAM_VERSION,
+            start_time: params.start_time,
+            end_time: params.end_time,
+            amount: params.amount,
+            withdrawn: 0,
+            sender: alice.to_bytes(),
+            recipient: bob.to_bytes(),
+            mint: mint.to_bytes(),
+            escrow: mint.to_bytes(),
+            nonce: 0,
+            bump: 0,
+            escrow_bump: 0,
+            cliff_time: params.cliff_time,
+            cliff_amount: params.cliff_amount,
+            period: params.period,
+            amount_per_period: params.amount_per_period,
+            period_unit: params.period_unit,
+            schedule: [0; 32],
+            rate: params.rate,
+            recipient_tokens: [0; 32],
+            transfer_by_sender_only: params.transfer_by_sender_only,
+            cancelable_by_sender: params.cancelable_by_sender,
+            cancelable_by_recipient: params.cancelable_by_recipient,
+            paused_at: 0,
+            paused_time: 0,
+            rent_destination: params.rent_destination.unwrap_or(*alice).to_bytes(),
+            crank_fee_bps: params.crank_fee_bps,
+        }
+    }
+
+    /// Whether the stream unlocks along a separate schedule account.
+    pub fn has_schedule(&self) -> bool {
+        self.schedule != [0; 32]
+    }
+
+    /// Whether all of the stream's funds have been withdrawn, so it can
+    /// be closed.
+    pub fn is_withdrawn(&self) -> bool {
+        self.withdrawn >= self.amount
+    }
+
+    /// Whether the stream is currently paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused_at != 0
+    }
+
+    /// Point of the stream's own schedule reached at the given time, which
+    /// stands still while paused and lags behind by the time spent paused.
+    pub fn schedule_time(&self, now: u64) -> u64 {
+        let now = if self.is_paused() {
+            now.min(self.paused_at)
+        } else {
+            now
+        };
+        now.saturating_sub(self.paused_time)
+    }
+
+    /// Amount of funds unlocked at the given time. `schedule` has to be
+    /// the stream's schedule account, if it has one.
+    pub fn unlocked(&self, now: u64, schedule: Option<&StreamSchedule>) -> u64 {
+        self.unlocked_at(self.schedule_time(now), schedule)
+    }
+
+    /// Amount of funds unlocked at the given time, but not withdrawn yet.
+    /// Native streams count the lamports sent to the recipient at creation
+    /// as withdrawn, so nothing is available until more has unlocked.
+    pub fn available(
+        &self,
+        now: u64,
+        schedule: Option<&StreamSchedule>,
+    ) -> Result<u64, ProgramError> {
+        self.remaining()?;
+        Ok(self.unlocked(now, schedule).saturating_sub(self.withdrawn))
+    }
+
+    /// Amount of funds the stream still holds, failing if more than its
+    /// amount was withdrawn.
+    pub fn remaining(&self) -> Result<u64, ProgramError> {
+        self.amount.checked_sub(self.withdrawn).ok_or_else(|| {
+            msg!("Withdrawn {} out of {}", self.withdrawn, self.amount);
+            StreamFlowError::InvariantViolation.into()
+        })
+    }
+
+    /// Part of a crank withdrawal of `amount` paid to the caller.
+    pub fn crank_fee(&self, amount: u64) -> u64 {
+        (amount as u128 * self.crank_fee_bps as u128 / 10_000) as u64
+    }
+
+    fn unlocked_at(&self, now: u64, schedule: Option<&StreamSchedule>) -> u64 {
+        if let Some(schedule) = schedule {
+            return calculate_piecewise(now, &schedule.breakpoints).min(self.amount);
+        }
+
+        if self.rate > 0 {
+            return calculate_rate_streamed(now, self.start_time, self.rate, self.amount);
+        }
+
+        calculate_streamed(
+            now,
+            &LinearSchedule {
+                start: self.start_time,
+                end: self.end_time,
+                amount: self.amount,
+                cliff: self.cliff_time,
+                cliff_amount: self.cliff_amount,
+                period: self.period,
+                amount_per_period: self.amount_per_period,
+                unit: self.period_unit,
+            },
+        )
+    }
+
+    /// Check whether whoever signed a cancellation may cancel the stream.
+    pub fn check_cancel(&self, sender_signed: bool, recipient_signed: bool) -> ProgramResult {
+        if !sender_signed && !recipient_signed {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        if (sender_signed && self.cancelable_by_sender)
+            || (recipient_signed && self.cancelable_by_recipient)
+        {
+            return Ok(());
+        }
+
+        msg!("Stream can't be cancelled by the signer");
+        Err(StreamFlowError::CancelNotAllowed.into())
+    }
+
+    /// Stop the stream from unlocking any more funds. Only streams the
+    /// sender could cancel can be paused, as a pause that is never resumed
+    /// withholds the rest of the funds just like a cancellation would.
+    pub fn pause(&mut self, now: u64) -> ProgramResult {
+        if self.is_paused() {
+            return Err(StreamFlowError::AlreadyPaused.into());
+        }
+
+        if !self.cancelable_by_sender {
+            msg!("Stream can't be cancelled by the sender, so it can't be paused");
+            return Err(StreamFlowError::CancelNotAllowed.into());
+        }
+
+        if self.version == 0 {
+            msg!("Legacy stream accounts can't be paused");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        self.paused_at = now;
+        Ok(())
+    }
+
+    /// Let a paused stream continue where it stopped.
+    pub fn resume(&mut self, now: u64) -> ProgramResult {
+        if !self.is_paused() {
+            return Err(StreamFlowError::NotPaused.into());
+        }
+
+        self.paused_time = (now.saturating_sub(self.paused_at))
+            .checked_add(self.paused_time)
+            .ok_or(StreamFlowError::MathOverflow)?;
+        self.paused_at = 0;
+        Ok(())
+    }
+
+    /// Add `amount` to a running stream, without changing what has been
+    /// unlocked so far. Funds unlocking from now on are either spread at
+    /// the current rate over a longer stream, or over the remaining time.
+    ///
+    /// Streams that have already unlocked everything can only be extended,
+    /// and pick up again from now at the rate they used to unlock at.
+    ///
+    /// Open-ended streams can only be extended, and streams with a release
+    /// period or a schedule account can't be topped up at all, as their
+    /// releases can't be rescaled exactly.
+    pub fn top_up(&mut self, now: u64, amount: u64, mode: TopUpMode) -> ProgramResult {
+        if self.has_schedule() || self.period > 0 {
+            msg!("Only linear and open-ended streams can be topped up");
+            return Err(StreamFlowError::InvalidSchedule.into());
+        }
+
+        let total = self
+            .amount
+            .checked_add(amount)
+            .ok_or(StreamFlowError::MathOverflow)?;
+
+        let now = self.schedule_time(now);
+        let unlocked = self.unlocked_at(now, None);
+        let exhausted = unlocked == self.amount;
+        if exhausted && mode != TopUpMode::Extend {
+            msg!("Stream has already unlocked all of its funds");
+            return Err(StreamFlowError::StreamClosed.into());
+        }
+
+        if self.rate > 0 {
+            if mode != TopUpMode::Extend {
+                msg!("Open-ended streams can only be extended");
+                return Err(StreamFlowError::InvalidSchedule.into());
+            }
+            // The stream simply runs for longer at the same rate. One that
+            // ran dry is moved to have run dry just now, so the new funds
+            // don't unlock at once for the time it sat empty. Only whole
+            // seconds can be moved, so up to a second's worth still does.
+            if exhausted {
+                self.start_time = now - calculate_rate_duration(self.amount, self.rate);
+            }
+            self.amount = total;
+            return Ok(());
+        }
+
+        if exhausted {
+            if self.version == 0 {
+                msg!("Legacy stream accounts can't be topped up once started");
+                return Err(ProgramError::InvalidAccountData);
+            }
+            let from = self.start_time.max(self.cliff_time);
+            let extra = self.extension(amount, from, self.cliff_amount)?;
+            self.cliff_time = now;
+            self.cliff_amount = self.amount;
+            self.end_time = (now as u128 + extra)
+                .try_into()
+                .map_err(|_| StreamFlowError::MathOverflow)?;
+            self.amount = total;
+            return Ok(());
+        }
+
+        // Once unlocking has begun, restart the remaining linear part from
+        // now with what is unlocked so far as a cliff, so that raising the
+        // amount or moving the end doesn't change the past.
+        let from = self.start_time.max(self.cliff_time);
+        let (from, base) = if now > from {
+            if self.version == 0 {
+                msg!("Legacy stream accounts can't be topped up once started");
+                return Err(ProgramError::InvalidAccountData);
+            }
+            self.cliff_time = now;
+            self.cliff_amount = unlocked;
+            (now, unlocked)
+        } else {
+            (from, self.cliff_amount)
+        };
+
+        if mode == TopUpMode::Extend {
+            let extra = self.extension(amount, from, base)?;
+            self.end_time = (self.end_time as u128 + extra)
+                .try_into()
+                .map_err(|_| StreamFlowError::MathOverflow)?;
+        }
+
+        self.amount = total;
+        Ok(())
+    }
+
+    /// Seconds it takes to unlock `amount` more at the rate of the linear
+    /// part of the schedule, which unlocks from `base` at `from` to the
+    /// whole amount at the end.
+    fn extension(&self, amount: u64, from: u64, base: u64) -> Result<u128, ProgramError> {
+        // Everything unlocking at once at the cliff has no rate.
+        if self.amount == base {
+            msg!("Stream has no rate to extend it at");
+            return Err(StreamFlowError::InvalidSchedule.into());
+        }
+        let duration = self
+            .end_time
+            .checked_sub(from)
+            .ok_or(StreamFlowError::MathOverflow)?;
+        Ok(amount as u128 * duration as u128 / (self.amount - base) as u128)
+    }
+}
+
+impl Sealed for StreamFlow {}
+
+impl IsInitialized for StreamFlow {
+    fn is_initialized(&self) -> bool {
+        // A valid schedule always ends after it starts, or is open-ended
+        // with a rate, so a zeroed account never passes for a stream.
+        self.end_time != 0 || self.rate != 0
+    }
+}
+
+impl Pack for StreamFlow {
+    /// Size of an account holding the current layout version.
+    const LEN: usize = 337;
+
+    /// Serialize StreamFlow struct into account data, using the layout
+    /// version it was created or read with. Accounts of older versions
+    /// are shorter than `LEN`, so they should be written back with this
+    /// function directly rather than through `Pack::pack`.
+    ///
+    /// Panics if `dst` is too small for that layout version.
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let off = &mut 0;
+        if self.version > 0 {
+            write(dst, off, &STREAM_MAGIC);
+            write(dst, off, &[self.version]);
+        }
+
+        write(dst, off, &self.start_time.to_le_bytes());
+        write(dst, off, &self.end_time.to_le_bytes());
+        write(dst, off, &self.amount.to_le_bytes());
+        write(dst, off, &self.withdrawn.to_le_bytes());
+        write(dst, off, &self.sender);
+        write(dst, off, &self.recipient);
+        write(dst, off, &self.mint);
+        write(dst, off, &self.escrow);
+
+        if self.version == 0 {
+            return;
+        }
+
+        write(dst, off, &self.nonce.to_le_bytes());
+        write(dst, off, &[self.bump]);
+        write(dst, off, &[self.escrow_bump]);
+        write(dst, off, &self.cliff_time.to_le_bytes());
+        write(dst, off, &self.cliff_amount.to_le_bytes());
+        write(dst, off, &self.period.to_le_bytes());
+        write(dst, off, &self.amount_per_period.to_le_bytes());
+        write(dst, off, &[self.period_unit as u8]);
+        write(dst, off, &self.schedule);
+        write(dst, off, &self.rate.to_le_bytes());
+        write(dst, off, &self.recipient_tokens);
+        write(dst, off, &[self.transfer_by_sender_only as u8]);
+        write(dst, off, &[self.cancelable_by_sender as u8]);
+        write(dst, off, &[self.cancelable_by_recipient as u8]);
+        write(dst, off, &self.paused_at.to_le_bytes());
+        write(dst, off, &self.paused_time.to_le_bytes());
+        write(dst, off, &self.rent_destination);
+        write(dst, off, &self.crank_fee_bps.to_le_bytes());
+    }
+
+    /// Deserialize account data into StreamFlow struct, in whichever
+    /// layout version the account was written with.
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let (version, mut off) =
+            if src.len() > STREAM_MAGIC.len() && src[..STREAM_MAGIC.len()] == STREAM_MAGIC {
+                (src[STREAM_MAGIC.len()], STREAM_MAGIC.len() + 1)
+            } else {
+                (0, 0)
+            };
+
+        if version > STREAM_VERSION || (version == 0 && off > 0) {
+            msg!("Unsupported stream layout version {}", version);
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let off = &mut off;
+        let mut sf = StreamFlow {
+            version,
+            start_time: read_u64(src, off)?,
+            end_time: read_u64(src, off)?,
+            amount: read_u64(src, off)?,
+            withdrawn: read_u64(src, off)?,
+            sender: read_pubkey(src, off)?,
+            recipient: read_pubkey(src, off)?,
+            mint: read_pubkey(src, off)?,
+            escrow: read_pubkey(src, off)?,
+            nonce: 0,
+            bump: 0,
+            escrow_bump: 0,
+            cliff_time: 0,
+            cliff_amount: 0,
+            period: 0,
+            amount_per_period: 0,
+            period_unit: PeriodUnit::Seconds,
+            schedule: [0; 32],
+            rate: 0,
+            recipient_tokens: [0; 32],
+            transfer_by_sender_only: false,
+            cancelable_by_sender: true,
+            cancelable_by_recipient: false,
+            paused_at: 0,
+            paused_time: 0,
+            rent_destination: [0; 32],
+            crank_fee_bps: 0,
+        };
+
+        // Legacy accounts end here. Their stream account and escrow were
+        // keypairs of the client rather than derived addresses, so they
+        // have no nonce or bump seeds, and everything else is left at what
+        // those streams always did.
+        if version == 0 {
+            return Ok(sf);
+        }
+
+        sf.nonce = read_u64(src, off)?;
+        sf.bump = read_u8(src, off)?;
+        sf.escrow_bump = read_u8(src, off)?;
+        sf.cliff_time = read_u64(src, off)?;
+        sf.cliff_amount = read_u64(src, off)?;
+        sf.period = read_u64(src, off)?;
+        sf.amount_per_period = read_u64(src, off)?;
+        sf.period_unit =
+            PeriodUnit::from_u8(read_u8(src, off)?).ok_or(ProgramError::InvalidAccountData)?;
+        sf.schedule = read_pubkey(src, off)?;
+        sf.rate = read_u64(src, off)?;
+        sf.recipient_tokens = read_pubkey(src, off)?;
+        sf.transfer_by_sender_only = read_bool(src, off)?;
+        sf.cancelable_by_sender = read_bool(src, off)?;
+        sf.cancelable_by_recipient = read_bool(src, off)?;
+        sf.paused_at = read_u64(src, off)?;
+        sf.paused_time = read_u64(src, off)?;
+        sf.rent_destination = read_pubkey(src, off)?;
+        sf.crank_fee_bps = read_u16(src, off)?;
+
+        Ok(sf)
+    }
+}
+
+/// Discriminator every schedule account starts with.
+pub const SCHEDULE_MAGIC: [u8; 8] = *b"STRMSCHD";
+
+/// Layout version written to newly created schedule accounts.
+pub const SCHEDULE_VERSION: u8 = 1;
+
+/// StreamSchedule holds the breakpoints of a piecewise-linear unlock
+/// curve, kept in its own account next to the stream it belongs to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StreamSchedule {
+    /// Layout version of the account this was read from
+    pub version: u8,
+    /// Pubkey of the stream (or metadata) account this schedule belongs to
+    pub stream: [u8; 32],
+    /// Breakpoints of the curve, at most `MAX_BREAKPOINTS`
+    pub breakpoints: Vec<Breakpoint>,
+}
+
+impl StreamSchedule {
+    /// Create a schedule for the given stream account.
+    pub fn new(stream: &Pubkey, breakpoints: Vec<Breakpoint>) -> Self {
+        StreamSchedule {
+            version: SCHEDULE_VERSION,
+            stream: stream.to_bytes(),
+            breakpoints,
+        }
+    }
+}
+
+impl Sealed for StreamSchedule {}
+
+impl IsInitialized for StreamSchedule {
+    fn is_initialized(&self) -> bool {
+        !self.breakpoints.is_empty()
+    }
+}
+
+impl Pack for StreamSchedule {
+    /// Size of a schedule account, which always has room for
+    /// `MAX_BREAKPOINTS` breakpoints.
+    const LEN: usize = 8 + 1 + 32 + 1 + MAX_BREAKPOINTS * 16;
+
+    /// Serialize StreamSchedule struct into account data.
+    ///
+    /// Panics if `dst` is too small or there are too many breakpoints.
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        assert!(self.breakpoints.len() <= MAX_BREAKPOINTS);
+        let off = &mut 0;
+        write(dst, off, &SCHEDULE_MAGIC);
+        write(dst, off, &[self.version]);
+        write(dst, off, &self.stream);
+        write(dst, off, &[self.breakpoints.len() as u8]);
+        for b in &self.breakpoints {
+            write(dst, off, &b.timestamp.to_le_bytes());
+            write(dst, off, &b.cumulative.to_le_bytes());
+        }
+    }
+
+    /// Deserialize account data into StreamSchedule struct.
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let off = &mut 0;
+        if read(src, off, SCHEDULE_MAGIC.len())? != SCHEDULE_MAGIC {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let version = read_u8(src, off)?;
+        if version > SCHEDULE_VERSION {
+            msg!("Unsupported schedule layout version {}", version);
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let stream = read_pubkey(src, off)?;
+        let len = read_u8(src, off)? as usize;
+        if len > MAX_BREAKPOINTS {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let mut breakpoints = Vec::with_capacity(len);
+        for _ in 0..len {
+            breakpoints.push(Breakpoint {
+                timestamp: read_u64(src, off)?,
+                cumulative: read_u64(src, off)?,
+            });
+        }
+
+        Ok(StreamSchedule {
+            version,
+            stream,
+            breakpoints,
+        })
+    }
+}
+
+/// Discriminator every config account starts with.
+pub const CONFIG_MAGIC: [u8; 8] = *b"STRMCNFG";
+
+/// Layout version written to newly created config accounts.
+pub const CONFIG_VERSION: u8 = 1;
+
+/// Config holds the program's global settings, kept in a single account
+/// derived with `find_config_address`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    /// Layout version of the account this was read from
+    pub version: u8,
+    /// Pubkey allowed to change the config
+    pub admin: [u8; 32],
+    /// Pubkey of the account collecting rent and fees that don't belong
+    /// to any party of a stream
+    pub collector: [u8; 32],
+}
+
+impl Config {
+    /// Create a config with the given admin and collector.
+    pub fn new(admin: &Pubkey, collector: &Pubkey) -> Self {
+        Config {
+            version: CONFIG_VERSION,
+            admin: admin.to_bytes(),
+            collector: collector.to_bytes(),
+        }
+    }
+}
+
+impl Sealed for Config {}
+
+impl IsInitialized for Config {
+    fn is_initialized(&self) -> bool {
+        self.admin != [0; 32]
+    }
+}
+
+impl Pack for Config {
+    /// Size of a config account.
+    const LEN: usize = 8 + 1 + 32 + 32;
+
+    /// Serialize Config struct into account data.
+    ///
+    /// Panics if `dst` is too small.
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let off = &mut 0;
+        write(dst, off, &CONFIG_MAGIC);
+        write(dst, off, &[self.version]);
+        write(dst, off, &self.admin);
+        write(dst, off, &self.collector);
+    }
+
+    /// Deserialize account data into Config struct.
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let off = &mut 0;
+        if read(src, off, CONFIG_MAGIC.len())? != CONFIG_MAGIC {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let version = read_u8(src, off)?;
+        if version > CONFIG_VERSION {
+            msg!("Unsupported config layout version {}", version);
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Ok(Config {
+            version,
+            admin: read_pubkey(src, off)?,
+            collector: read_pubkey(src, off)?,
+        })
+    }
+}
+
+fn read<'a>(src: &'a [u8], off: &mut usize, len: usize) -> Result<&'a [u8], ProgramError> {
+    let bytes = src
+        .get(*off..*off + len)
+        .ok_or(ProgramError::InvalidAccountData)?;
+    *off += len;
+    Ok(bytes)
+}
+
+fn read_u8(src: &[u8], off: &mut usize) -> Result<u8, ProgramError> {
+    Ok(read(src, off, 1)?[0])
+}
+
+fn read_u16(src: &[u8], off: &mut usize) -> Result<u16, ProgramError> {
+    Ok(u16::from_le_bytes(read(src, off, 2)?.try_into().unwrap()))
+}
+
+fn read_bool(src: &[u8], off: &mut usize) -> Result<bool, ProgramError> {
+    match read_u8(src, off)? {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+fn read_u64(src: &[u8], off: &mut usize) -> Result<u64, ProgramError> {
+    Ok(u64::from_le_bytes(read(src, off, 8)?.try_into().unwrap()))
+}
+
+fn read_pubkey(src: &[u8], off: &mut usize) -> Result<[u8; 32], ProgramError> {
+    Ok(read(src, off, 32)?.try_into().unwrap())
+}
+
+fn write(dst: &mut [u8], off: &mut usize, bytes: &[u8]) {
+    dst[*off..*off + bytes.len()].copy_from_slice(bytes);
+    *off += bytes.len();
+}
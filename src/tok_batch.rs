@@ -0,0 +1,251 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program::invoke_signed,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_instruction, system_program,
+    sysvar::{clock::Clock, fees::Fees, rent::Rent, Sysvar},
+};
+use spl_token::state::Account;
+
+use crate::{
+    error::StreamFlowError,
+    instruction::StreamParams,
+    state::StreamFlow,
+    utils::{
+        check_token_funds, find_batch_metadata_address, find_escrow_address,
+        spl_token_init_account, spl_token_transfer, stream_sanity, transfer_lamports,
+        TokenInitializeAccountParams, TokenTransferParams, BATCH_SEED_PREFIX, ESCROW_SEED_PREFIX,
+    },
+};
+
+/// Program function to initialize several streams of tokens at once.
+pub fn tok_create_batch(
+    pid: &Pubkey,
+    accounts: &[AccountInfo],
+    entries: Vec<StreamParams>,
+    nonce: u64,
+) -> ProgramResult {
+    msg!(
+        "Requested SPL token create_batch of {} streams",
+        entries.len()
+    );
+    let account_info_iter = &mut accounts.iter();
+    let alice_authority = next_account_info(account_info_iter)?;
+    let alice_tokens = next_account_info(account_info_iter)?;
+    let token_mint = next_account_info(account_info_iter)?;
+    let rent_acc = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    if token_program.key != &spl_token::id() {
+        msg!("Mismatched Token program address in [accounts]!");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    if system_program.key != &system_program::id() {
+        msg!("Mismatched System program address in [accounts]");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    if !alice_authority.is_signer || !alice_authority.is_writable || !alice_tokens.is_writable {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let alice_tokens_acc = Account::unpack(&alice_tokens.try_borrow_data()?)?;
+    if alice_tokens_acc.mint != *token_mint.key {
+        msg!("Token account {} can't fund this batch", alice_tokens.key);
+        return Err(StreamFlowError::InvalidTokenAccount.into());
+    }
+
+    let now = Clock::get()?.unix_timestamp as u64;
+    let cluster_rent = Rent::get()?;
+    let data_rent = cluster_rent.minimum_balance(StreamFlow::LEN);
+    let toks_rent = cluster_rent.minimum_balance(Account::LEN);
+
+    // Every recipient gets enough for one instruction, as for a single
+    // stream, which passes through the metadata account.
+    let fee = Fees::get()?
+        .fee_calculator
+        .lamports_per_signature
+        .checked_mul(3)
+        .ok_or(StreamFlowError::MathOverflow)?;
+    let data_funds = data_rent
+        .checked_add(fee)
+        .ok_or(StreamFlowError::MathOverflow)?;
+    let stream_funds = data_funds
+        .checked_add(toks_rent)
+        .ok_or(StreamFlowError::MathOverflow)?;
+
+    // Check every stream, and the funds for all of them, before creating
+    // any, so the whole batch either succeeds or fails.
+    let mut streams = Vec::with_capacity(entries.len());
+    let mut tokens: u64 = 0;
+    let mut funds: u64 = 0;
+    for (index, params) in entries.iter().enumerate() {
+        let bob = next_account_info(account_info_iter)?;
+        let bob_tokens = next_account_info(account_info_iter)?;
+        let data_acc = next_account_info(account_info_iter)?;
+        let escrow_acc = next_account_info(account_info_iter)?;
+
+        if bob_tokens.owner != &spl_token::id() {
+            return Err(StreamFlowError::InvalidTokenAccount.into());
+        }
+        let bob_tokens_acc = Account::unpack(&bob_tokens.try_borrow_data()?)?;
+        if bob_tokens_acc.owner != *bob.key || bob_tokens_acc.mint != *token_mint.key {
+            msg!("Token account {} can't receive this stream", bob_tokens.key);
+            return Err(StreamFlowError::InvalidTokenAccount.into());
+        }
+
+        let index = index as u8;
+        let (data_key, bump) =
+            find_batch_metadata_address(pid, alice_authority.key, token_mint.key, nonce, index);
+        if data_acc.key != &data_key {
+            msg!("Mismatched metadata account address for {}", bob.key);
+            return Err(StreamFlowError::InvalidStreamAccount.into());
+        }
+
+        let (escrow_key, escrow_bump) = find_escrow_address(pid, data_acc.key);
+        if escrow_acc.key != &escrow_key {
+            msg!("Mismatched escrow address for {}", bob.key);
+            return Err(StreamFlowError::InvalidEscrow.into());
+        }
+
+        if !data_acc.data_is_empty() || !escrow_acc.data_is_empty() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+
+        if !bob.is_writable
+            || !bob_tokens.is_writable
+            || !data_acc.is_writable
+            || !escrow_acc.is_writable
+        {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        if !params.breakpoints.is_empty() {
+            msg!("Streams in a batch can't have breakpoints");
+            return Err(StreamFlowError::InvalidSchedule.into());
+        }
+
+        let mut sf = StreamFlow::new(params, alice_authority.key, bob.key, token_mint.key);
+        sf.escrow = escrow_acc.key.to_bytes();
+        sf.escrow_bump = escrow_bump;
+        sf.recipient_tokens = bob_tokens.key.to_bytes();
+        sf.nonce = nonce;
+        sf.bump = bump;
+        if !stream_sanity(now, &sf, &[]) {
+            return Err(StreamFlowError::InvalidSchedule.into());
+        }
+
+        tokens = tokens
+            .checked_add(sf.amount)
+            .ok_or(StreamFlowError::MathOverflow)?;
+        funds = funds
+            .checked_add(stream_funds)
+            .ok_or(StreamFlowError::MathOverflow)?;
+        streams.push((index, bob, data_acc, escrow_acc, sf));
+    }
+
+    if alice_tokens_acc.amount < tokens {
+        msg!("Not enough tokens in sender's account to initialize the streams");
+        return Err(ProgramError::InsufficientFunds);
+    }
+
+    if alice_authority.lamports() < funds {
+        msg!("Not enough funds in sender's account to initialize the streams");
+        return Err(ProgramError::InsufficientFunds);
+    }
+
+    for (index, bob, data_acc, escrow_acc, sf) in streams {
+        // Create the account holding this stream's metadata
+        invoke_signed(
+            &system_instruction::create_account(
+                alice_authority.key,
+                data_acc.key,
+                data_funds,
+                StreamFlow::LEN as u64,
+                pid,
+            ),
+            &[
+                alice_authority.clone(),
+                data_acc.clone(),
+                system_program.clone(),
+            ],
+            &[&[
+                BATCH_SEED_PREFIX,
+                alice_authority.key.as_ref(),
+                token_mint.key.as_ref(),
+                &nonce.to_le_bytes(),
+                &[index],
+                &[sf.bump],
+            ]],
+        )?;
+        sf.pack_into_slice(&mut data_acc.try_borrow_mut_data()?);
+        transfer_lamports(data_acc, bob, fee)?;
+
+        // Create escrow account so we can transfer tokens to it.
+        invoke_signed(
+            &system_instruction::create_account(
+                alice_authority.key,
+                escrow_acc.key,
+                toks_rent,
+                Account::LEN as u64,
+                &spl_token::id(),
+            ),
+            &[
+                alice_authority.clone(),
+                escrow_acc.clone(),
+                system_program.clone(),
+            ],
+            &[&[ESCROW_SEED_PREFIX, data_acc.key.as_ref(), &[sf.escrow_bump]]],
+        )?;
+
+        spl_token_init_account(TokenInitializeAccountParams {
+            account: escrow_acc.clone(),
+            mint: token_mint.clone(),
+            owner: escrow_acc.clone(),
+            rent: rent_acc.clone(),
+            token_program: token_program.clone(),
+        })?;
+
+        spl_token_transfer(TokenTransferParams {
+            source: alice_tokens.clone(),
+            destination: escrow_acc.clone(),
+            amount: sf.amount,
+            authority: alice_authority.clone(),
+            authority_signer_seeds: &[],
+            token_program: token_program.clone(),
+        })?;
+        check_token_funds(Account::unpack(&escrow_acc.try_borrow_data()?)?.amount, &sf)?;
+
+        msg!(
+            "Initialized {} token stream for {} in {}",
+            sf.amount,
+            bob.key,
+            data_acc.key
+        );
+    }
+
+    msg!("Called by account: {}", alice_authority.key);
+    Ok(())
+}
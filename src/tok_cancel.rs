@@ -0,0 +1,182 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    native_token::lamports_to_sol,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    sysvar::{clock::Clock, Sysvar},
+};
+use spl_token::state::Account;
+
+use crate::{
+    error::StreamFlowError,
+    state::StreamFlow,
+    utils::{
+        check_token_funds, close_program_account, next_schedule_account, spl_token_close_account,
+        spl_token_transfer, TokenCloseAccountParams, TokenTransferParams, ESCROW_SEED_PREFIX,
+    },
+};
+
+/// Program function to cancel an initialized stream of SPL tokens.
+pub fn tok_cancel_stream(pid: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    msg!("Requested SPL token stream cancellation");
+    let account_info_iter = &mut accounts.iter();
+    let alice_authority = next_account_info(account_info_iter)?;
+    let alice_tokens = next_account_info(account_info_iter)?;
+    let bob_authority = next_account_info(account_info_iter)?;
+    let bob_tokens = next_account_info(account_info_iter)?;
+    let data_acc = next_account_info(account_info_iter)?;
+    let escrow_acc = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+
+    if token_program.key != &spl_token::id() {
+        msg!("Mismatched Token program address in [accounts]!");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    if !alice_authority.is_writable
+        || !alice_tokens.is_writable
+        || !bob_tokens.is_writable
+        || !data_acc.is_writable
+        || !escrow_acc.is_writable
+    {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    if data_acc.data_is_empty() {
+        return Err(StreamFlowError::StreamClosed.into());
+    }
+
+    if data_acc.owner != pid {
+        return Err(ProgramError::IllegalOwner);
+    }
+
+    let sf = StreamFlow::unpack_from_slice(&data_acc.try_borrow_data()?)?;
+
+    if alice_authority.key.to_bytes() != sf.sender {
+        msg!("Unauthorized to cancel for {}", alice_authority.key);
+        return Err(StreamFlowError::UnauthorizedSigner.into());
+    }
+
+    if bob_authority.key.to_bytes() != sf.recipient {
+        msg!("This stream isn't intended for {}", bob_authority.key);
+        return Err(StreamFlowError::WrongRecipient.into());
+    }
+
+    sf.check_cancel(alice_authority.is_signer, bob_authority.is_signer)?;
+
+    if escrow_acc.key.to_bytes() != sf.escrow {
+        msg!("Got unexpected escrow account {}", escrow_acc.key);
+        return Err(StreamFlowError::InvalidEscrow.into());
+    }
+
+    let alice_tokens_acc = Account::unpack(&alice_tokens.try_borrow_data()?)?;
+    if alice_tokens_acc.owner != *alice_authority.key || alice_tokens_acc.mint.to_bytes() != sf.mint
+    {
+        msg!(
+            "Token account {} can't receive this stream",
+            alice_tokens.key
+        );
+        return Err(StreamFlowError::InvalidTokenAccount.into());
+    }
+
+    // Legacy streams didn't keep the recipient's token account.
+    let bob_tokens_acc = Account::unpack(&bob_tokens.try_borrow_data()?)?;
+    if bob_tokens_acc.owner != *bob_authority.key
+        || bob_tokens_acc.mint.to_bytes() != sf.mint
+        || (sf.recipient_tokens != [0; 32] && bob_tokens.key.to_bytes() != sf.recipient_tokens)
+    {
+        msg!("Token account {} can't receive this stream", bob_tokens.key);
+        return Err(StreamFlowError::InvalidTokenAccount.into());
+    }
+
+    let escrow_tokens = Account::unpack(&escrow_acc.try_borrow_data()?)?.amount;
+    check_token_funds(escrow_tokens, &sf)?;
+
+    // Current cluster time used to calculate unlocked amount.
+    let now = Clock::get()?.unix_timestamp as u64;
+
+    let schedule = next_schedule_account(pid, data_acc, &sf, account_info_iter)?;
+    let available = sf.available(now, schedule.as_ref().map(|(_, s)| s))?;
+    let remains = escrow_tokens
+        .checked_sub(available)
+        .ok_or(StreamFlowError::MathOverflow)?;
+
+    let escrow_signer_seeds: &[&[u8]] =
+        &[ESCROW_SEED_PREFIX, data_acc.key.as_ref(), &[sf.escrow_bump]];
+
+    // Transfer what was unlocked but not withdrawn to Bob.
+    if available > 0 {
+        spl_token_transfer(TokenTransferParams {
+            source: escrow_acc.clone(),
+            destination: bob_tokens.clone(),
+            amount: available,
+            authority: escrow_acc.clone(),
+            authority_signer_seeds: escrow_signer_seeds,
+            token_program: token_program.clone(),
+        })?;
+    }
+
+    // Return the rest of the locked tokens to Alice.
+    if remains > 0 {
+        spl_token_transfer(TokenTransferParams {
+            source: escrow_acc.clone(),
+            destination: alice_tokens.clone(),
+            amount: remains,
+            authority: escrow_acc.clone(),
+            authority_signer_seeds: escrow_signer_seeds,
+            token_program: token_program.clone(),
+        })?;
+    }
+
+    // Close the now empty escrow, and send its rent to Alice.
+    spl_token_close_account(TokenCloseAccountParams {
+        account: escrow_acc.clone(),
+        destination: alice_authority.clone(),
+        authority: escrow_acc.clone(),
+        authority_signer_seeds: escrow_signer_seeds,
+        token_program: token_program.clone(),
+    })?;
+
+    // Purge the metadata account and return its rent to Alice as well.
+    let rent = close_program_account(data_acc, alice_authority)?;
+
+    // The schedule goes away with the stream.
+    if let Some((schedule_acc, _)) = schedule {
+        close_program_account(schedule_acc, alice_authority)?;
+    }
+
+    msg!("Successfully cancelled stream on {}", data_acc.key);
+    msg!(
+        "Transferred unlocked {} tokens to {}",
+        available,
+        bob_tokens.key
+    );
+    msg!("Returned {} tokens to {}", remains, alice_tokens.key);
+    msg!(
+        "Returned {} SOL ({} lamports) of rent to {}",
+        lamports_to_sol(rent),
+        rent,
+        alice_authority.key
+    );
+
+    Ok(())
+}
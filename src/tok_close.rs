@@ -0,0 +1,131 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    native_token::lamports_to_sol,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use spl_token::state::Account;
+
+use crate::{
+    error::StreamFlowError,
+    state::StreamFlow,
+    utils::{
+        check_rent_destination, close_token_stream, next_schedule_account, spl_token_transfer,
+        TokenTransferParams, ESCROW_SEED_PREFIX,
+    },
+};
+
+/// Program function to close a fully withdrawn stream of SPL tokens.
+pub fn tok_close_stream(pid: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    msg!("Requested SPL token stream close");
+    let account_info_iter = &mut accounts.iter();
+    let data_acc = next_account_info(account_info_iter)?;
+    let escrow_acc = next_account_info(account_info_iter)?;
+    let bob_tokens = next_account_info(account_info_iter)?;
+    let rent_acc = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+
+    if token_program.key != &spl_token::id() {
+        msg!("Mismatched Token program address in [accounts]!");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    if !data_acc.is_writable
+        || !escrow_acc.is_writable
+        || !bob_tokens.is_writable
+        || !rent_acc.is_writable
+    {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    if data_acc.data_is_empty() {
+        return Err(StreamFlowError::StreamClosed.into());
+    }
+
+    if data_acc.owner != pid {
+        return Err(ProgramError::IllegalOwner);
+    }
+
+    let sf = StreamFlow::unpack_from_slice(&data_acc.try_borrow_data()?)?;
+
+    if escrow_acc.key.to_bytes() != sf.escrow {
+        msg!("Got unexpected escrow account {}", escrow_acc.key);
+        return Err(StreamFlowError::InvalidEscrow.into());
+    }
+
+    let config_acc = next_account_info(account_info_iter)?;
+    check_rent_destination(pid, &sf, rent_acc, config_acc)?;
+
+    let remaining = sf.remaining()?;
+    if remaining > 0 {
+        msg!("Remaining: {} tokens", remaining);
+        return Err(StreamFlowError::NotFullyWithdrawn.into());
+    }
+
+    let schedule = next_schedule_account(pid, data_acc, &sf, account_info_iter)?;
+
+    // Tokens sent to the escrow on top of the stream go to the recipient,
+    // as the escrow can only be closed once it is empty.
+    let escrow_tokens = Account::unpack(&escrow_acc.try_borrow_data()?)?.amount;
+    if escrow_tokens > 0 {
+        if bob_tokens.owner != &spl_token::id() {
+            return Err(StreamFlowError::InvalidTokenAccount.into());
+        }
+        let bob_tokens_acc = Account::unpack(&bob_tokens.try_borrow_data()?)?;
+        if bob_tokens_acc.owner.to_bytes() != sf.recipient
+            || bob_tokens_acc.mint.to_bytes() != sf.mint
+            || (sf.recipient_tokens != [0; 32] && bob_tokens.key.to_bytes() != sf.recipient_tokens)
+        {
+            msg!("Token account {} can't receive this stream", bob_tokens.key);
+            return Err(StreamFlowError::InvalidTokenAccount.into());
+        }
+
+        spl_token_transfer(TokenTransferParams {
+            source: escrow_acc.clone(),
+            destination: bob_tokens.clone(),
+            amount: escrow_tokens,
+            authority: escrow_acc.clone(),
+            authority_signer_seeds: &[ESCROW_SEED_PREFIX, data_acc.key.as_ref(), &[sf.escrow_bump]],
+            token_program: token_program.clone(),
+        })?;
+        msg!("Swept {} tokens to {}", escrow_tokens, bob_tokens.key);
+    }
+
+    let rent = close_token_stream(
+        data_acc,
+        escrow_acc,
+        rent_acc,
+        token_program,
+        sf.escrow_bump,
+        schedule.map(|(acc, _)| acc),
+    )?;
+
+    msg!("Successfully closed stream on {}", data_acc.key);
+    msg!(
+        "Returned {} SOL ({} lamports) of rent to {}",
+        lamports_to_sol(rent),
+        rent,
+        rent_acc.key
+    );
+
+    Ok(())
+}
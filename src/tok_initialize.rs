@@ -18,7 +18,7 @@ use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
     msg,
-    program::invoke,
+    program::{invoke, invoke_signed},
     program_error::ProgramError,
     program_pack::Pack,
     pubkey::Pubkey,
@@ -27,13 +27,23 @@ use solana_program::{
 };
 use spl_token::state::Account;
 
-use crate::utils::{
-    any_as_u8_slice, duration_sanity, spl_token_init_account, spl_token_transfer,
-    unpack_init_instruction, StreamFlow, TokenInitializeAccountParams, TokenTransferParams,
+use crate::{
+    error::StreamFlowError,
+    instruction::StreamParams,
+    state::StreamFlow,
+    utils::{
+        check_token_funds, create_schedule_account, find_escrow_address, spl_token_init_account,
+        spl_token_transfer, stream_sanity, transfer_lamports, TokenInitializeAccountParams,
+        TokenTransferParams, ESCROW_SEED_PREFIX,
+    },
 };
 
 /// Program function to initialize a stream of tokens.
-pub fn tok_initialize_stream(pid: &Pubkey, accounts: &[AccountInfo], ix: &[u8]) -> ProgramResult {
+pub fn tok_initialize_stream(
+    pid: &Pubkey,
+    accounts: &[AccountInfo],
+    params: StreamParams,
+) -> ProgramResult {
     msg!("Requested SPL token initialize_stream");
     let account_info_iter = &mut accounts.iter();
 
@@ -49,18 +59,22 @@ pub fn tok_initialize_stream(pid: &Pubkey, accounts: &[AccountInfo], ix: &[u8])
     let token_program = next_account_info(account_info_iter)?;
     let system_program = next_account_info(account_info_iter)?;
 
-    if ix.len() != 25 {
-        return Err(ProgramError::InvalidInstructionData);
-    }
-
     if token_program.key != &spl_token::id() {
         msg!("Mismatched Token program address in [accounts]!");
-        return Err(ProgramError::InvalidInstructionData);
+        return Err(ProgramError::IncorrectProgramId);
     }
 
     if self_program.key != pid {
         msg!("Mismatched program address in [accounts]");
-        return Err(ProgramError::InvalidInstructionData);
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    // The escrow is derived from the metadata account and owns itself,
+    // so the program can later sign for withdrawals.
+    let (escrow_key, escrow_bump) = find_escrow_address(pid, data_acc.key);
+    if escrow_acc.key != &escrow_key {
+        msg!("Mismatched escrow address in [accounts]");
+        return Err(StreamFlowError::InvalidEscrow.into());
     }
 
     if !data_acc.data_is_empty() || !escrow_acc.data_is_empty() {
@@ -74,25 +88,41 @@ pub fn tok_initialize_stream(pid: &Pubkey, accounts: &[AccountInfo], ix: &[u8])
         || !bob_tokens.is_writable
         || !data_acc.is_signer
         || !data_acc.is_writable
-        || !escrow_acc.is_signer
         || !escrow_acc.is_writable
     {
         return Err(ProgramError::MissingRequiredSignature);
     }
 
+    // The recipient's token account is kept with the stream, so it has to
+    // be able to receive it.
+    let bob_tokens_acc = Account::unpack(&bob_tokens.try_borrow_data()?)?;
+    if bob_tokens_acc.owner != *bob_authority.key || bob_tokens_acc.mint != *token_mint.key {
+        msg!("Token account {} can't receive this stream", bob_tokens.key);
+        return Err(StreamFlowError::InvalidTokenAccount.into());
+    }
+
     // Rent calculation
     let cluster_rent = Rent::get()?;
-    let data_struct_size = std::mem::size_of::<StreamFlow>();
+    let data_struct_size = StreamFlow::LEN;
     let toks_struct_size = Account::LEN;
     let data_rent = cluster_rent.minimum_balance(data_struct_size);
     let toks_rent = cluster_rent.minimum_balance(toks_struct_size);
 
-    // Unpack instruction into struct
-    let sf = unpack_init_instruction(ix, alice_authority.key, bob_authority.key, token_mint.key);
+    // Build stream metadata from instruction parameters
+    let mut sf = StreamFlow::new(
+        &params,
+        alice_authority.key,
+        bob_authority.key,
+        token_mint.key,
+    );
+    sf.escrow = escrow_acc.key.to_bytes();
+    sf.escrow_bump = escrow_bump;
+    sf.recipient_tokens = bob_tokens.key.to_bytes();
 
     let now = Clock::get()?.unix_timestamp as u64;
-    if !duration_sanity(now, sf.start_time, sf.end_time) {
-        return Err(ProgramError::InvalidArgument);
+    let breakpoints = params.breakpoints;
+    if !stream_sanity(now, &sf, &breakpoints) {
+        return Err(StreamFlowError::InvalidSchedule.into());
     }
 
     // Fee calculator
@@ -102,7 +132,14 @@ pub fn tok_initialize_stream(pid: &Pubkey, accounts: &[AccountInfo], ix: &[u8])
     // We also transfer enough to be rent-exempt (about 0.0016 SOL) to the
     // new accounts. After all funds are unlocked and withdrawn, this shall
     // be transferred to a rent-reaping address.
-    if alice_authority.lamports() < data_rent + toks_rent + (4 * lps) {
+    let data_funds = lps
+        .checked_mul(4)
+        .and_then(|fee| fee.checked_add(data_rent))
+        .ok_or(StreamFlowError::MathOverflow)?;
+    let funds = data_funds
+        .checked_add(toks_rent)
+        .ok_or(StreamFlowError::MathOverflow)?;
+    if alice_authority.lamports() < funds {
         msg!("Not enough funds in sender's account to initialize SPL token stream");
         return Err(ProgramError::InsufficientFunds);
     }
@@ -110,11 +147,11 @@ pub fn tok_initialize_stream(pid: &Pubkey, accounts: &[AccountInfo], ix: &[u8])
     // Create the account holding this stream's metadata
     invoke(
         &system_instruction::create_account(
-            &alice_authority.key,
-            &data_acc.key,
-            cluster_rent.minimum_balance(data_struct_size) + (lps * 4),
+            alice_authority.key,
+            data_acc.key,
+            data_funds,
             data_struct_size as u64,
-            &pid,
+            pid,
         ),
         &[
             alice_authority.clone(),
@@ -125,16 +162,29 @@ pub fn tok_initialize_stream(pid: &Pubkey, accounts: &[AccountInfo], ix: &[u8])
 
     // Send enough for one instruction to Bob, so Bob can do an initial
     // withdraw without having previous funds on their account.
-    **data_acc.try_borrow_mut_lamports()? -= lps * 3;
-    **bob_authority.try_borrow_mut_lamports()? += lps * 3;
+    let fee = lps.checked_mul(3).ok_or(StreamFlowError::MathOverflow)?;
+    transfer_lamports(data_acc, bob_authority, fee)?;
+
+    // Custom unlock curves live in their own account next to the stream.
+    if !breakpoints.is_empty() {
+        let schedule_acc = next_account_info(account_info_iter)?;
+        create_schedule_account(
+            pid,
+            alice_authority,
+            data_acc,
+            schedule_acc,
+            system_program,
+            breakpoints,
+        )?;
+        sf.schedule = schedule_acc.key.to_bytes();
+    }
 
     // Write our metadata to data_acc's data.
     let mut data_acc_data = data_acc.try_borrow_mut_data()?;
-    let bytes: &[u8] = unsafe { any_as_u8_slice(&sf) };
-    data_acc_data[0..bytes.len()].clone_from_slice(bytes);
+    sf.pack_into_slice(&mut data_acc_data);
 
     // Create escrow account so we can transfer tokens to it.
-    invoke(
+    invoke_signed(
         &system_instruction::create_account(
             alice_authority.key,
             escrow_acc.key,
@@ -147,13 +197,14 @@ pub fn tok_initialize_stream(pid: &Pubkey, accounts: &[AccountInfo], ix: &[u8])
             escrow_acc.clone(),
             system_program.clone(),
         ],
+        &[&[ESCROW_SEED_PREFIX, data_acc.key.as_ref(), &[escrow_bump]]],
     )?;
 
     // Initialize the created escrow account with SPL token data
     spl_token_init_account(TokenInitializeAccountParams {
         account: escrow_acc.clone(),
         mint: token_mint.clone(),
-        owner: self_program.clone(),
+        owner: escrow_acc.clone(),
         rent: rent_acc.clone(),
         token_program: token_program.clone(),
     })?;
@@ -167,6 +218,7 @@ pub fn tok_initialize_stream(pid: &Pubkey, accounts: &[AccountInfo], ix: &[u8])
         authority_signer_seeds: &[],
         token_program: token_program.clone(),
     })?;
+    check_token_funds(Account::unpack(&escrow_acc.try_borrow_data()?)?.amount, &sf)?;
 
     // TODO: Better output
     msg!("Successfully initialized stream for: {}", bob_authority.key);
@@ -174,7 +226,14 @@ pub fn tok_initialize_stream(pid: &Pubkey, accounts: &[AccountInfo], ix: &[u8])
     msg!("Funds locked in account: {}", escrow_acc.key);
     msg!("Stream start:    {}", sf.start_time);
     msg!("Stream end:      {}", sf.end_time);
-    msg!("Stream duration: {} seconds", sf.end_time - sf.start_time);
+    if sf.rate > 0 {
+        msg!("Stream rate:     {} per second", sf.rate);
+    } else {
+        msg!(
+            "Stream duration: {} seconds",
+            sf.end_time.saturating_sub(sf.start_time)
+        );
+    }
 
     Ok(())
 }
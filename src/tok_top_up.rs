@@ -0,0 +1,108 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    sysvar::{clock::Clock, Sysvar},
+};
+use spl_token::state::Account;
+
+use crate::{
+    error::StreamFlowError,
+    state::StreamFlow,
+    utils::{check_token_funds, spl_token_transfer, TokenTransferParams, TopUpMode},
+};
+
+/// Program function to add SPL tokens to a stream.
+pub fn tok_top_up_stream(
+    pid: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+    mode: TopUpMode,
+) -> ProgramResult {
+    msg!("Requested SPL token top_up_stream");
+    let account_info_iter = &mut accounts.iter();
+    let funder_authority = next_account_info(account_info_iter)?;
+    let funder_tokens = next_account_info(account_info_iter)?;
+    let data_acc = next_account_info(account_info_iter)?;
+    let escrow_acc = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+
+    if token_program.key != &spl_token::id() {
+        msg!("Mismatched Token program address in [accounts]!");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    if !funder_authority.is_signer
+        || !funder_tokens.is_writable
+        || !data_acc.is_writable
+        || !escrow_acc.is_writable
+    {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    if data_acc.data_is_empty() {
+        return Err(StreamFlowError::StreamClosed.into());
+    }
+
+    if data_acc.owner != pid {
+        return Err(ProgramError::IllegalOwner);
+    }
+
+    if amount == 0 {
+        msg!("Nothing to top up");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let mut sf = StreamFlow::unpack_from_slice(&data_acc.try_borrow_data()?)?;
+
+    if escrow_acc.key.to_bytes() != sf.escrow {
+        msg!("Got unexpected escrow account {}", escrow_acc.key);
+        return Err(StreamFlowError::InvalidEscrow.into());
+    }
+
+    let funder_tokens_acc = Account::unpack(&funder_tokens.try_borrow_data()?)?;
+    if funder_tokens_acc.mint.to_bytes() != sf.mint {
+        msg!("Token account {} can't fund this stream", funder_tokens.key);
+        return Err(StreamFlowError::InvalidTokenAccount.into());
+    }
+
+    let now = Clock::get()?.unix_timestamp as u64;
+    sf.top_up(now, amount, mode)?;
+
+    spl_token_transfer(TokenTransferParams {
+        source: funder_tokens.clone(),
+        destination: escrow_acc.clone(),
+        amount,
+        authority: funder_authority.clone(),
+        authority_signer_seeds: &[],
+        token_program: token_program.clone(),
+    })?;
+    check_token_funds(Account::unpack(&escrow_acc.try_borrow_data()?)?.amount, &sf)?;
+
+    sf.pack_into_slice(&mut data_acc.try_borrow_mut_data()?);
+
+    msg!("Successfully topped up: {} tokens", amount);
+    msg!("Stream amount: {} tokens", sf.amount);
+    msg!("Stream end:    {}", sf.end_time);
+
+    Ok(())
+}
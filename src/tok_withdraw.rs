@@ -0,0 +1,186 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    native_token::lamports_to_sol,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    sysvar::{clock::Clock, Sysvar},
+};
+use spl_token::state::Account;
+
+use crate::{
+    error::StreamFlowError,
+    state::StreamFlow,
+    utils::{
+        check_rent_destination, check_token_funds, close_token_stream, next_destination_account,
+        next_schedule_account, spl_token_transfer, TokenTransferParams, ESCROW_SEED_PREFIX,
+    },
+};
+
+/// Program function to withdraw unlocked SPL tokens.
+pub fn tok_withdraw_unlocked(pid: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+    msg!("Requested withdraw of unlocked SPL tokens");
+    let account_info_iter = &mut accounts.iter();
+    let bob_authority = next_account_info(account_info_iter)?;
+    let bob_tokens = next_account_info(account_info_iter)?;
+    let data_acc = next_account_info(account_info_iter)?;
+    let escrow_acc = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+    let rent_acc = next_account_info(account_info_iter)?;
+
+    if token_program.key != &spl_token::id() {
+        msg!("Mismatched Token program address in [accounts]!");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    if !bob_authority.is_signer
+        || !data_acc.is_writable
+        || !escrow_acc.is_writable
+        || !rent_acc.is_writable
+    {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    if data_acc.data_is_empty() {
+        return Err(StreamFlowError::StreamClosed.into());
+    }
+
+    if data_acc.owner != pid {
+        return Err(ProgramError::IllegalOwner);
+    }
+
+    let mut data = data_acc.try_borrow_mut_data()?;
+    let mut sf = StreamFlow::unpack_from_slice(&data)?;
+
+    if bob_authority.key.to_bytes() != sf.recipient {
+        msg!("This stream isn't intended for {}", bob_authority.key);
+        return Err(StreamFlowError::WrongRecipient.into());
+    }
+
+    if escrow_acc.key.to_bytes() != sf.escrow {
+        msg!("Got unexpected escrow account {}", escrow_acc.key);
+        return Err(StreamFlowError::InvalidEscrow.into());
+    }
+
+    // Only read once the stream is closed.
+    let config_acc = next_account_info(account_info_iter)?;
+
+    // Legacy streams didn't keep the recipient's token account.
+    let bob_tokens_acc = Account::unpack(&bob_tokens.try_borrow_data()?)?;
+    if bob_tokens_acc.owner != *bob_authority.key
+        || bob_tokens_acc.mint.to_bytes() != sf.mint
+        || (sf.recipient_tokens != [0; 32] && bob_tokens.key.to_bytes() != sf.recipient_tokens)
+    {
+        msg!("Token account {} can't receive this stream", bob_tokens.key);
+        return Err(StreamFlowError::InvalidTokenAccount.into());
+    }
+
+    // Current cluster time used to calculate unlocked amount.
+    let now = Clock::get()?.unix_timestamp as u64;
+
+    let schedule = next_schedule_account(pid, data_acc, &sf, account_info_iter)?;
+
+    // Bob may have the tokens sent to any account of the stream's mint.
+    let destination = next_destination_account(pid, data_acc, &sf, bob_tokens, account_info_iter)?;
+    if destination.key != bob_tokens.key {
+        if destination.owner != &spl_token::id() {
+            return Err(StreamFlowError::InvalidTokenAccount.into());
+        }
+        if Account::unpack(&destination.try_borrow_data()?)?
+            .mint
+            .to_bytes()
+            != sf.mint
+        {
+            msg!(
+                "Token account {} can't receive this stream",
+                destination.key
+            );
+            return Err(StreamFlowError::InvalidTokenAccount.into());
+        }
+    }
+
+    let available = sf.available(now, schedule.as_ref().map(|(_, s)| s))?;
+
+    let mut requested = amount;
+    if requested == 0 {
+        requested = available;
+    }
+
+    if requested > available {
+        msg!("Amount requested for withdraw is larger than what is available.");
+        msg!("Requested: {} tokens", requested);
+        msg!("Available: {} tokens", available);
+        return Err(StreamFlowError::AmountExceedsAvailable.into());
+    }
+
+    let escrow_tokens = Account::unpack(&escrow_acc.try_borrow_data()?)?.amount;
+    check_token_funds(escrow_tokens, &sf)?;
+
+    // The escrow is a program-derived address owning itself, so only
+    // this program is able to move the tokens out of it.
+    spl_token_transfer(TokenTransferParams {
+        source: escrow_acc.clone(),
+        destination: destination.clone(),
+        amount: requested,
+        authority: escrow_acc.clone(),
+        authority_signer_seeds: &[ESCROW_SEED_PREFIX, data_acc.key.as_ref(), &[sf.escrow_bump]],
+        token_program: token_program.clone(),
+    })?;
+
+    // Update account data
+    sf.withdrawn = sf
+        .withdrawn
+        .checked_add(requested)
+        .ok_or(StreamFlowError::MathOverflow)?;
+    check_token_funds(Account::unpack(&escrow_acc.try_borrow_data()?)?.amount, &sf)?;
+    sf.pack_into_slice(&mut data);
+
+    msg!(
+        "Successfully withdrawn: {} tokens to {}",
+        requested,
+        destination.key
+    );
+    msg!("Remaining: {} tokens", sf.remaining()?);
+
+    // Collect rent after stream is finished, unless someone sent more
+    // tokens to the escrow than the stream holds.
+    if sf.is_withdrawn() && escrow_tokens == requested {
+        check_rent_destination(pid, &sf, rent_acc, config_acc)?;
+        drop(data);
+        let rent = close_token_stream(
+            data_acc,
+            escrow_acc,
+            rent_acc,
+            token_program,
+            sf.escrow_bump,
+            schedule.map(|(acc, _)| acc),
+        )?;
+        msg!(
+            "Closed stream, returned {} SOL ({} lamports) of rent to {}",
+            lamports_to_sol(rent),
+            rent,
+            rent_acc.key
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,102 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use spl_token::state::Account;
+
+use crate::{error::StreamFlowError, state::StreamFlow};
+
+/// Program function to transfer a stream to a new recipient.
+pub fn transfer_recipient(pid: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    msg!("Requested stream recipient transfer");
+    let account_info_iter = &mut accounts.iter();
+    let authority = next_account_info(account_info_iter)?;
+    let data_acc = next_account_info(account_info_iter)?;
+    let new_bob = next_account_info(account_info_iter)?;
+
+    if !authority.is_signer || !data_acc.is_writable {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    if data_acc.data_is_empty() {
+        return Err(StreamFlowError::StreamClosed.into());
+    }
+
+    if data_acc.owner != pid {
+        return Err(ProgramError::IllegalOwner);
+    }
+
+    let mut data = data_acc.try_borrow_mut_data()?;
+    let mut sf = StreamFlow::unpack_from_slice(&data)?;
+
+    let allowed = if sf.transfer_by_sender_only {
+        sf.sender
+    } else {
+        sf.recipient
+    };
+    if authority.key.to_bytes() != allowed {
+        msg!("Unauthorized to transfer stream for {}", authority.key);
+        return Err(StreamFlowError::UnauthorizedSigner.into());
+    }
+
+    // Moving a stream the sender can't cancel to themselves would take
+    // back what was meant to be irrevocable.
+    if sf.transfer_by_sender_only && !sf.cancelable_by_sender {
+        msg!("Stream can't be cancelled by the sender, so they can't transfer it");
+        return Err(StreamFlowError::CancelNotAllowed.into());
+    }
+
+    // The new recipient takes over whatever the stream holds, so that
+    // has to add up.
+    sf.remaining()?;
+
+    // Token streams also pay out to the new recipient's token account.
+    if sf.recipient_tokens != [0; 32] {
+        let new_bob_tokens = next_account_info(account_info_iter)?;
+        if new_bob_tokens.owner != &spl_token::id() {
+            return Err(StreamFlowError::InvalidTokenAccount.into());
+        }
+
+        let new_bob_tokens_acc = Account::unpack(&new_bob_tokens.try_borrow_data()?)?;
+        if new_bob_tokens_acc.owner != *new_bob.key || new_bob_tokens_acc.mint.to_bytes() != sf.mint
+        {
+            msg!(
+                "Token account {} can't receive this stream",
+                new_bob_tokens.key
+            );
+            return Err(StreamFlowError::InvalidTokenAccount.into());
+        }
+
+        sf.recipient_tokens = new_bob_tokens.key.to_bytes();
+    }
+
+    let old_bob = Pubkey::new_from_array(sf.recipient);
+    sf.recipient = new_bob.key.to_bytes();
+    sf.pack_into_slice(&mut data);
+
+    msg!("Successfully transferred stream on {}", data_acc.key);
+    msg!("From recipient: {}", old_bob);
+    msg!("To recipient:   {}", new_bob.key);
+
+    Ok(())
+}
@@ -13,87 +13,586 @@
 //
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
-use std::convert::TryInto;
-
+use num_derive::FromPrimitive;
 use solana_program::{
-    account_info::AccountInfo,
+    account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
     msg,
     program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    program_pack::Pack,
     pubkey::Pubkey,
+    system_instruction, system_program,
+    sysvar::{rent::Rent, Sysvar},
+};
+
+use crate::{
+    error::StreamFlowError,
+    state::{Config, StreamFlow, StreamSchedule},
 };
 
-/// StreamFlow is the struct containing all our necessary metadata.
-#[repr(C)]
-pub struct StreamFlow {
+/// Seed prefix used to derive the address of a native SOL stream.
+pub const STREAM_SEED_PREFIX: &[u8] = b"stream";
+
+/// Maximum number of streams created by a single batch instruction. A
+/// token batch of this size, with four accounts and about a hundred
+/// bytes of parameters per stream, still fits into one 1232 byte
+/// transaction, and well within its compute budget. Native batches would
+/// fit twice as many streams, but deliberately share the same bound, so
+/// both kinds of batch take the same number of entries.
+pub const MAX_BATCH_SIZE: usize = 3;
+
+/// Find the address of the account holding a native SOL stream from
+/// `sender` to `recipient`. The nonce allows the same parties to have
+/// more than one stream between them.
+pub fn find_stream_address(
+    pid: &Pubkey,
+    sender: &Pubkey,
+    recipient: &Pubkey,
+    nonce: u64,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            STREAM_SEED_PREFIX,
+            sender.as_ref(),
+            recipient.as_ref(),
+            &nonce.to_le_bytes(),
+        ],
+        pid,
+    )
+}
+
+/// Seed prefix used to derive the address of the metadata of a token
+/// stream created in a batch.
+pub const BATCH_SEED_PREFIX: &[u8] = b"batch";
+
+/// Find the address of the metadata account of the token stream at
+/// `index` in a batch `sender` created of `mint`. Every entry gets its own
+/// address, so a batch may hold several streams to the same recipient.
+pub fn find_batch_metadata_address(
+    pid: &Pubkey,
+    sender: &Pubkey,
+    mint: &Pubkey,
+    nonce: u64,
+    index: u8,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            BATCH_SEED_PREFIX,
+            sender.as_ref(),
+            mint.as_ref(),
+            &nonce.to_le_bytes(),
+            &[index],
+        ],
+        pid,
+    )
+}
+
+/// Seed prefix used to derive the address of a stream's unlock schedule.
+pub const SCHEDULE_SEED_PREFIX: &[u8] = b"schedule";
+
+/// Find the address of the account holding the unlock schedule of the
+/// stream kept in the given stream (or metadata) account.
+pub fn find_schedule_address(pid: &Pubkey, stream: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[SCHEDULE_SEED_PREFIX, stream.as_ref()], pid)
+}
+
+/// Take the schedule account of the stream kept in `stream_acc` from the
+/// accounts iterator and read it, if the stream has one.
+pub fn next_schedule_account<'a, 'b, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    pid: &Pubkey,
+    stream_acc: &AccountInfo,
+    sf: &StreamFlow,
+    iter: &mut I,
+) -> Result<Option<(&'a AccountInfo<'b>, StreamSchedule)>, ProgramError> {
+    if !sf.has_schedule() {
+        return Ok(None);
+    }
+
+    let schedule_acc = next_account_info(iter)?;
+    if schedule_acc.key.to_bytes() != sf.schedule || schedule_acc.owner != pid {
+        msg!("Mismatched schedule account in [accounts]");
+        return Err(StreamFlowError::InvalidScheduleAccount.into());
+    }
+
+    let schedule = StreamSchedule::unpack(&schedule_acc.try_borrow_data()?)?;
+    if schedule.stream != stream_acc.key.to_bytes() {
+        msg!("Schedule {} belongs to another stream", schedule_acc.key);
+        return Err(StreamFlowError::InvalidScheduleAccount.into());
+    }
+
+    Ok(Some((schedule_acc, schedule)))
+}
+
+/// Take the account a withdrawal pays out to from the accounts iterator,
+/// or fall back to `recipient` if none was given. It follows the schedule
+/// account's slot, see `skip_schedule_placeholder`.
+pub fn next_destination_account<'a, 'b, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    pid: &Pubkey,
+    stream_acc: &AccountInfo,
+    sf: &StreamFlow,
+    recipient: &'a AccountInfo<'b>,
+    iter: &mut I,
+) -> Result<&'a AccountInfo<'b>, ProgramError> {
+    skip_schedule_placeholder(pid, stream_acc, sf, iter)?;
+
+    let destination = iter.next().unwrap_or(recipient);
+    if !destination.is_writable {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    Ok(destination)
+}
+
+/// Skip the schedule account's slot of a stream without a schedule, which
+/// has to be filled by the stream's schedule address, the program id or
+/// the system program if more accounts follow. Anything else there is
+/// refused rather than taken for the placeholder, so an account given
+/// without one is never skipped silently.
+pub fn skip_schedule_placeholder<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    pid: &Pubkey,
+    stream_acc: &AccountInfo,
+    sf: &StreamFlow,
+    iter: &mut I,
+) -> ProgramResult {
+    if sf.has_schedule() {
+        return Ok(());
+    }
+
+    if let Some(placeholder) = iter.next() {
+        if placeholder.key != pid
+            && placeholder.key != &system_program::id()
+            && placeholder.key != &find_schedule_address(pid, stream_acc.key).0
+        {
+            msg!(
+                "Expected a placeholder for the schedule, got {}",
+                placeholder.key
+            );
+            return Err(StreamFlowError::InvalidScheduleAccount.into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Create the schedule account of the stream kept in `stream_acc`, and
+/// write the given breakpoints to it. `payer` funds its rent.
+pub fn create_schedule_account<'a>(
+    pid: &Pubkey,
+    payer: &AccountInfo<'a>,
+    stream_acc: &AccountInfo<'a>,
+    schedule_acc: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    breakpoints: Vec<Breakpoint>,
+) -> ProgramResult {
+    let (schedule_key, bump) = find_schedule_address(pid, stream_acc.key);
+    if schedule_acc.key != &schedule_key {
+        msg!("Mismatched schedule account address in [accounts]");
+        return Err(StreamFlowError::InvalidScheduleAccount.into());
+    }
+
+    invoke_signed(
+        &system_instruction::create_account(
+            payer.key,
+            schedule_acc.key,
+            Rent::get()?.minimum_balance(StreamSchedule::LEN),
+            StreamSchedule::LEN as u64,
+            pid,
+        ),
+        &[payer.clone(), schedule_acc.clone(), system_program.clone()],
+        &[&[SCHEDULE_SEED_PREFIX, stream_acc.key.as_ref(), &[bump]]],
+    )?;
+
+    let schedule = StreamSchedule::new(stream_acc.key, breakpoints);
+    schedule.pack_into_slice(&mut schedule_acc.try_borrow_mut_data()?);
+    Ok(())
+}
+
+/// Seed used to derive the address of the program's config account.
+pub const CONFIG_SEED_PREFIX: &[u8] = b"config";
+
+/// Find the address of the program's config account.
+pub fn find_config_address(pid: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_SEED_PREFIX], pid)
+}
+
+/// Take the program's config account from the accounts iterator and
+/// read it.
+pub fn next_config_account<'a, 'b, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    pid: &Pubkey,
+    iter: &mut I,
+) -> Result<(&'a AccountInfo<'b>, Config), ProgramError> {
+    let config_acc = next_account_info(iter)?;
+    let config = read_config(pid, config_acc)?;
+    Ok((config_acc, config))
+}
+
+/// Read the program's config from `config_acc`, checking it is the
+/// program's config account.
+pub fn read_config(pid: &Pubkey, config_acc: &AccountInfo) -> Result<Config, ProgramError> {
+    if config_acc.key != &find_config_address(pid).0 || config_acc.owner != pid {
+        msg!("Mismatched config account in [accounts]");
+        return Err(StreamFlowError::InvalidConfigAccount.into());
+    }
+
+    Config::unpack(&config_acc.try_borrow_data()?)
+}
+
+/// Check `destination` is where the stream's rent should go, once the
+/// stream is closed. The config in `config_acc` is only read for streams
+/// that didn't choose a destination, so for all others any account can be
+/// passed in its place, and legacy streams can be withdrawn from before
+/// the config exists as long as they aren't closed.
+pub fn check_rent_destination(
+    pid: &Pubkey,
+    sf: &StreamFlow,
+    destination: &AccountInfo,
+    config_acc: &AccountInfo,
+) -> ProgramResult {
+    // Streams that didn't choose a destination leave it to the program.
+    let expected = if sf.rent_destination == [0; 32] {
+        read_config(pid, config_acc)?.collector
+    } else {
+        sf.rent_destination
+    };
+
+    if destination.key.to_bytes() != expected {
+        msg!("Got unexpected rent collection account");
+        return Err(StreamFlowError::InvalidRentReaper.into());
+    }
+
+    Ok(())
+}
+
+/// Purge an account owned by this program, and send all of its lamports
+/// to `destination`. Returns the amount of lamports reclaimed.
+pub fn close_program_account(
+    account: &AccountInfo,
+    destination: &AccountInfo,
+) -> Result<u64, ProgramError> {
+    let lamports = account.lamports();
+    transfer_lamports(account, destination, lamports)?;
+    account.try_borrow_mut_data()?.fill(0);
+    Ok(lamports)
+}
+
+/// Move lamports out of an account owned by this program, failing
+/// rather than wrapping around either balance.
+pub fn transfer_lamports(from: &AccountInfo, to: &AccountInfo, amount: u64) -> ProgramResult {
+    let balance = from.lamports().checked_sub(amount).ok_or_else(|| {
+        msg!("Can't move {} lamports out of {}", amount, from.key);
+        StreamFlowError::InvariantViolation
+    })?;
+    **from.try_borrow_mut_lamports()? = balance;
+
+    let balance = to
+        .lamports()
+        .checked_add(amount)
+        .ok_or(StreamFlowError::MathOverflow)?;
+    **to.try_borrow_mut_lamports()? = balance;
+
+    Ok(())
+}
+
+/// Check a stream account of native SOL holds the funds left in the
+/// stream on top of its rent.
+pub fn check_native_funds(pda: &AccountInfo, sf: &StreamFlow) -> ProgramResult {
+    let rent = Rent::get()?.minimum_balance(pda.data_len());
+    let needed = sf
+        .remaining()?
+        .checked_add(rent)
+        .ok_or(StreamFlowError::MathOverflow)?;
+
+    if pda.lamports() < needed {
+        msg!("Stream holds {} lamports, needs {}", pda.lamports(), needed);
+        return Err(StreamFlowError::InvariantViolation.into());
+    }
+
+    Ok(())
+}
+
+/// Check an escrow holding `escrow_tokens` covers the tokens left in the
+/// stream.
+pub fn check_token_funds(escrow_tokens: u64, sf: &StreamFlow) -> ProgramResult {
+    let needed = sf.remaining()?;
+    if escrow_tokens < needed {
+        msg!("Escrow holds {} tokens, needs {}", escrow_tokens, needed);
+        return Err(StreamFlowError::InvariantViolation.into());
+    }
+
+    Ok(())
+}
+
+/// Seed prefix used to derive the escrow address of a token stream.
+pub const ESCROW_SEED_PREFIX: &[u8] = b"escrow";
+
+/// Find the address of the escrow account holding the tokens of the
+/// stream described by the given metadata account.
+pub fn find_escrow_address(pid: &Pubkey, metadata: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[ESCROW_SEED_PREFIX, metadata.as_ref()], pid)
+}
+
+/// Unit a stream's release `period` is counted in.
+#[derive(Clone, Copy, Debug, Eq, FromPrimitive, PartialEq)]
+pub enum PeriodUnit {
+    /// Fixed number of seconds
+    Seconds = 0,
+    /// UTC calendar months, released on the same day of the month as the
+    /// stream started (or the last day of shorter months)
+    Months = 1,
+    /// UTC calendar years, released on the anniversary of the start
+    Years = 2,
+}
+
+/// How a top-up changes the schedule of a stream.
+#[derive(Clone, Copy, Debug, Eq, FromPrimitive, PartialEq)]
+pub enum TopUpMode {
+    /// Keep unlocking at the current rate, and move the end time back
+    Extend = 0,
+    /// Keep the end time, and unlock faster for the rest of the stream
+    Increase = 1,
+}
+
+/// Parameters of a linear unlock schedule, see `calculate_streamed`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LinearSchedule {
     /// Timestamp when the funds start unlocking
-    pub start_time: u64,
-    /// Timestamp when all funds should be unlocked
-    pub end_time: u64,
-    /// Amount of funds locked
+    pub start: u64,
+    /// Timestamp when all funds are unlocked
+    pub end: u64,
+    /// Amount of funds unlocking
     pub amount: u64,
-    /// Amount of funds withdrawn
-    pub withdrawn: u64,
-    /// Pubkey of the program initializer
-    pub sender: [u8; 32],
-    /// Pubkey of the funds' recipient
-    pub recipient: [u8; 32],
-    /// Pubkey of the token mint (can be zeroes for native SOL)
-    pub mint: [u8; 32],
-    /// Pubkey of the account holding the locked tokens
-    /// (should be zeroes for native SOL)
-    pub escrow: [u8; 32],
-}
-
-/// Serialize anything to u8 slice.
-/// # Safety
-///
-/// :)
-pub unsafe fn any_as_u8_slice<T: Sized>(p: &T) -> &[u8] {
-    ::std::slice::from_raw_parts((p as *const T) as *const u8, ::std::mem::size_of::<T>())
-}
-
-/// Deserialize instruction_data into StreamFlow struct.
-/// This is used to read instructions given to us by the program's initializer.
-pub fn unpack_init_instruction(
-    ix: &[u8],
-    alice: &Pubkey,
-    bob: &Pubkey,
-    mint: &Pubkey,
-) -> StreamFlow {
-    StreamFlow {
-        start_time: u64::from_le_bytes(ix[1..9].try_into().unwrap()),
-        end_time: u64::from_le_bytes(ix[9..17].try_into().unwrap()),
-        amount: u64::from_le_bytes(ix[17..25].try_into().unwrap()),
-        withdrawn: 0,
-        sender: alice.to_bytes(),
-        recipient: bob.to_bytes(),
-        mint: mint.to_bytes(),
-        escrow: mint.to_bytes(),
-    }
-}
-
-/// Deserialize account data into StreamFlow struct.
-/// This is used for reading the metadata from the account holding the locked funds.
-pub fn unpack_account_data(ix: &[u8]) -> StreamFlow {
-    StreamFlow {
-        start_time: u64::from_le_bytes(ix[0..8].try_into().unwrap()),
-        end_time: u64::from_le_bytes(ix[8..16].try_into().unwrap()),
-        amount: u64::from_le_bytes(ix[16..24].try_into().unwrap()),
-        withdrawn: u64::from_le_bytes(ix[24..32].try_into().unwrap()),
-        sender: ix[32..64].try_into().unwrap(),
-        recipient: ix[64..96].try_into().unwrap(),
-        mint: ix[96..128].try_into().unwrap(),
-        escrow: ix[96..128].try_into().unwrap(),
-    }
-}
-
-/// Calculate unlocked funds from start to end.
-pub fn calculate_streamed(now: u64, start: u64, end: u64, amount: u64) -> u64 {
-    // This is valid float division, but we lose precision when going u64.
-    // The loss however should not matter, as in the end we will simply
-    // send everything that is remaining.
-    (((now - start) as f64) / ((end - start) as f64) * amount as f64) as u64
+    /// Timestamp before which nothing unlocks, or `0` for none
+    pub cliff: u64,
+    /// Amount unlocked at once at the cliff
+    pub cliff_amount: u64,
+    /// Length of a release period, or `0` for continuous unlocking
+    pub period: u64,
+    /// Amount released at the end of every period
+    pub amount_per_period: u64,
+    /// Unit `period` is counted in
+    pub unit: PeriodUnit,
+}
+
+/// Calculate unlocked funds from start to end. Nothing is unlocked
+/// before `start` (or `cliff`, if set), `cliff_amount` is unlocked at
+/// once at the cliff, and the rest unlocks until `end`, from where on
+/// everything is unlocked. With a `period` set, the rest is released
+/// in steps of `amount_per_period` for every whole period (counted in
+/// `unit`) elapsed since the cliff, and linearly every second otherwise.
+pub fn calculate_streamed(now: u64, schedule: &LinearSchedule) -> u64 {
+    let LinearSchedule {
+        start,
+        end,
+        amount,
+        cliff,
+        cliff_amount,
+        period,
+        amount_per_period,
+        unit,
+    } = *schedule;
+
+    let from = start.max(cliff);
+    if now < from {
+        return 0;
+    }
+
+    if now >= end {
+        return amount;
+    }
+
+    let elapsed = match unit {
+        PeriodUnit::Seconds => now - from,
+        PeriodUnit::Months => elapsed_months(from, now),
+        PeriodUnit::Years => elapsed_months(from, now) / 12,
+    };
+
+    // from <= now < end, so the quotient is below the streamed amount
+    // and fits into u64. Integer division rounds down, which means we
+    // never release more than what has actually been unlocked.
+    // A period of 0 means continuous unlocking.
+    let streamed = match elapsed.checked_div(period) {
+        Some(periods) => periods as u128 * amount_per_period as u128,
+        None => {
+            let streamed = amount.saturating_sub(cliff_amount);
+            (now - from) as u128 * streamed as u128 / (end - from) as u128
+        }
+    };
+
+    (cliff_amount as u128 + streamed).min(amount as u128) as u64
+}
+
+/// Maximum number of breakpoints in a piecewise-linear schedule.
+pub const MAX_BREAKPOINTS: usize = 32;
+
+/// Point of a piecewise-linear unlock schedule.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Breakpoint {
+    /// Unix timestamp of the breakpoint
+    pub timestamp: u64,
+    /// Total amount unlocked at `timestamp`
+    pub cumulative: u64,
+}
+
+/// Calculate unlocked funds along a piecewise-linear schedule. Nothing is
+/// unlocked before the first breakpoint, funds unlock linearly between
+/// consecutive breakpoints, and the last breakpoint's amount is unlocked
+/// from then on.
+pub fn calculate_piecewise(now: u64, breakpoints: &[Breakpoint]) -> u64 {
+    let next = breakpoints.iter().position(|b| b.timestamp > now);
+    let (prev, next) = match next {
+        Some(0) => return 0,
+        Some(i) => (breakpoints[i - 1], breakpoints[i]),
+        None => return breakpoints.last().map_or(0, |b| b.cumulative),
+    };
+
+    // prev.timestamp <= now < next.timestamp, so the quotient is below the
+    // difference and fits into u64. Integer division rounds down, so we
+    // never release more than what has actually been unlocked.
+    let diff = next.cumulative.saturating_sub(prev.cumulative);
+    let linear =
+        (now - prev.timestamp) as u128 * diff as u128 / (next.timestamp - prev.timestamp) as u128;
+    prev.cumulative + linear as u64
+}
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Add calendar months to a Unix timestamp, keeping the time of day.
+/// The day of the month is clamped to the length of the resulting month,
+/// so Jan 31st plus one month is the last day of February. Saturates at
+/// `u64::MAX`.
+pub fn add_months(timestamp: u64, months: u64) -> u64 {
+    let days = timestamp / SECONDS_PER_DAY;
+    let time_of_day = timestamp % SECONDS_PER_DAY;
+
+    let (year, month, day) = civil_from_days(days as u128);
+    let total = year * 12 + (month - 1) + months as u128;
+    let (year, month) = (total / 12, total % 12 + 1);
+    let day = day.min(days_in_month(year, month));
+
+    let seconds = days_from_civil(year, month, day) * SECONDS_PER_DAY as u128 + time_of_day as u128;
+    seconds.min(u64::MAX as u128) as u64
+}
+
+/// Number of whole calendar months between two Unix timestamps, as
+/// counted by `add_months`.
+pub fn elapsed_months(from: u64, now: u64) -> u64 {
+    if now <= from {
+        return 0;
+    }
+
+    let (from_year, from_month, _) = civil_from_days((from / SECONDS_PER_DAY) as u128);
+    let (now_year, now_month, _) = civil_from_days((now / SECONDS_PER_DAY) as u128);
+    let months = ((now_year * 12 + now_month) - (from_year * 12 + from_month)) as u64;
+
+    // We're in the right month, but maybe not past the day and time yet.
+    if add_months(from, months) > now {
+        months - 1
+    } else {
+        months
+    }
+}
+
+// `is_multiple_of` needs a newer compiler than the BPF toolchain ships.
+#[allow(clippy::manual_is_multiple_of)]
+fn is_leap_year(year: u128) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: u128, month: u128) -> u128 {
+    match month {
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        _ => 31,
+    }
+}
+
+// Conversions between days since the Unix epoch and the proleptic
+// Gregorian calendar, from http://howardhinnant.github.io/date_algorithms.html
+// Years are shifted to start in March, so leap days come last.
+fn civil_from_days(days: u128) -> (u128, u128, u128) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn days_from_civil(year: u128, month: u128, day: u128) -> u128 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let yoe = year - era * 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Calculate unlocked funds of an open-ended stream, which unlocks `rate`
+/// every second from `start` on until the deposited `amount` runs out.
+pub fn calculate_rate_streamed(now: u64, start: u64, rate: u64, amount: u64) -> u64 {
+    if now <= start {
+        return 0;
+    }
+
+    ((now - start) as u128 * rate as u128).min(amount as u128) as u64
+}
+
+/// Calculate the whole seconds an open-ended stream unlocking `rate` every
+/// second takes to unlock `amount`. `rate` must not be zero.
+pub fn calculate_rate_duration(amount: u64, rate: u64) -> u64 {
+    let whole = amount / rate;
+    whole + (whole * rate < amount) as u64
+}
+
+/// Largest share of a crank withdrawal a stream may pay to the caller,
+/// in basis points.
+pub const MAX_CRANK_FEE_BPS: u16 = 100;
+
+/// Do a sanity check of a new stream's whole unlock schedule.
+pub fn stream_sanity(now: u64, sf: &StreamFlow, breakpoints: &[Breakpoint]) -> bool {
+    if sf.crank_fee_bps > MAX_CRANK_FEE_BPS {
+        msg!("Crank fee is invalid!");
+        msg!("Crank fee: {} basis points", sf.crank_fee_bps);
+        return false;
+    }
+
+    // Open-ended streams only have a start and a rate.
+    if sf.rate > 0 {
+        if sf.start_time < now
+            || sf.end_time != 0
+            || sf.cliff_time != 0
+            || sf.cliff_amount != 0
+            || sf.period != 0
+            || !breakpoints.is_empty()
+        {
+            msg!("Open-ended stream is invalid!");
+            msg!("Solana cluster time: {}", now);
+            msg!("Stream start time:   {}", sf.start_time);
+            msg!("Stream end time:     {}", sf.end_time);
+            return false;
+        }
+
+        return true;
+    }
+
+    duration_sanity(now, sf.start_time, sf.end_time)
+        && cliff_sanity(
+            sf.start_time,
+            sf.end_time,
+            sf.amount,
+            sf.cliff_time,
+            sf.cliff_amount,
+        )
+        && period_sanity(sf.amount, sf.period, sf.amount_per_period)
+        && (breakpoints.is_empty()
+            || breakpoints_sanity(sf.start_time, sf.end_time, sf.amount, breakpoints))
 }
 
 /// Do a sanity check with given Unix timestamps.
@@ -103,11 +602,66 @@ pub fn duration_sanity(now: u64, start: u64, end: u64) -> bool {
         msg!("Solana cluster time: {}", now);
         msg!("Stream start time:   {}", start);
         msg!("Stream end time:     {}", end);
-        msg!("Stream duration:     {}", end - start);
+        msg!("Stream duration:     {}", end.saturating_sub(start));
+        return false;
+    }
+
+    true
+}
+
+/// Do a sanity check of the cliff against the stream's schedule.
+pub fn cliff_sanity(start: u64, end: u64, amount: u64, cliff: u64, cliff_amount: u64) -> bool {
+    if (cliff != 0 && (cliff < start || cliff > end)) || cliff_amount > amount {
+        msg!("Cliff is invalid!");
+        msg!("Stream start time: {}", start);
+        msg!("Stream end time:   {}", end);
+        msg!("Cliff time:        {}", cliff);
+        msg!("Cliff amount:      {}", cliff_amount);
+        return false;
+    }
+
+    true
+}
+
+/// Do a sanity check of the release period.
+pub fn period_sanity(amount: u64, period: u64, amount_per_period: u64) -> bool {
+    if (period > 0) != (amount_per_period > 0) || amount_per_period > amount {
+        msg!("Release period is invalid!");
+        msg!("Period:            {}", period);
+        msg!("Amount per period: {}", amount_per_period);
+        return false;
+    }
+
+    true
+}
+
+/// Do a sanity check of piecewise-linear schedule breakpoints. They have
+/// to lie within the stream's duration, be strictly increasing in time,
+/// never decrease in amount and finish by unlocking the whole stream at
+/// its end.
+pub fn breakpoints_sanity(start: u64, end: u64, amount: u64, breakpoints: &[Breakpoint]) -> bool {
+    let ordered = breakpoints
+        .windows(2)
+        .all(|w| w[0].timestamp < w[1].timestamp && w[0].cumulative <= w[1].cumulative);
+    let bounded = match (breakpoints.first(), breakpoints.last()) {
+        (Some(first), Some(last)) => {
+            first.timestamp >= start && last.timestamp == end && last.cumulative == amount
+        }
+        _ => false,
+    };
+
+    if breakpoints.len() > MAX_BREAKPOINTS || !ordered || !bounded {
+        msg!("Schedule breakpoints are invalid!");
+        msg!("Stream start time: {}", start);
+        msg!("Stream end time:   {}", end);
+        msg!("Stream amount:     {}", amount);
+        for b in breakpoints {
+            msg!("Breakpoint: {} at {}", b.cumulative, b.timestamp);
+        }
         return false;
     }
 
-    return true;
+    true
 }
 
 /// Structure used to pass parameters to spl_token_init_account()
@@ -172,6 +726,13 @@ pub fn spl_token_transfer(params: TokenTransferParams<'_, '_>) -> ProgramResult
         authority_signer_seeds,
     } = params;
 
+    // An empty set of seeds isn't a signer, and the runtime refuses to
+    // derive an address from it.
+    let signers_seeds: &[&[&[u8]]] = if authority_signer_seeds.is_empty() {
+        &[]
+    } else {
+        &[authority_signer_seeds]
+    };
     invoke_signed(
         &spl_token::instruction::transfer(
             token_program.key,
@@ -182,6 +743,74 @@ pub fn spl_token_transfer(params: TokenTransferParams<'_, '_>) -> ProgramResult
             amount,
         )?,
         &[source, destination, authority, token_program],
+        signers_seeds,
+    )
+}
+
+/// Structure used to pass parameters to spl_token_close_account()
+pub struct TokenCloseAccountParams<'a: 'b, 'b> {
+    /// Account to close
+    pub account: AccountInfo<'a>,
+    /// Account receiving the reclaimed rent
+    pub destination: AccountInfo<'a>,
+    /// Account authority
+    pub authority: AccountInfo<'a>,
+    /// Account authority signer seeds
+    pub authority_signer_seeds: &'b [&'b [u8]],
+    /// Token program account
+    pub token_program: AccountInfo<'a>,
+}
+
+/// Used to close an empty SPL token account using given parameters
+pub fn spl_token_close_account(params: TokenCloseAccountParams<'_, '_>) -> ProgramResult {
+    let TokenCloseAccountParams {
+        account,
+        destination,
+        authority,
+        authority_signer_seeds,
+        token_program,
+    } = params;
+
+    invoke_signed(
+        &spl_token::instruction::close_account(
+            token_program.key,
+            account.key,
+            destination.key,
+            authority.key,
+            &[],
+        )?,
+        &[account, destination, authority, token_program],
         &[authority_signer_seeds],
     )
 }
+
+/// Close the metadata, escrow and schedule accounts of a token stream,
+/// sending all of their rent to `destination`. The escrow must be empty.
+/// Returns the amount of lamports reclaimed.
+pub fn close_token_stream<'a>(
+    data_acc: &AccountInfo<'a>,
+    escrow_acc: &AccountInfo<'a>,
+    destination: &AccountInfo<'a>,
+    token_program: &AccountInfo<'a>,
+    escrow_bump: u8,
+    schedule_acc: Option<&AccountInfo<'a>>,
+) -> Result<u64, ProgramError> {
+    let escrow_rent = escrow_acc.lamports();
+    spl_token_close_account(TokenCloseAccountParams {
+        account: escrow_acc.clone(),
+        destination: destination.clone(),
+        authority: escrow_acc.clone(),
+        authority_signer_seeds: &[ESCROW_SEED_PREFIX, data_acc.key.as_ref(), &[escrow_bump]],
+        token_program: token_program.clone(),
+    })?;
+
+    let mut rent = escrow_rent
+        .checked_add(close_program_account(data_acc, destination)?)
+        .ok_or(StreamFlowError::MathOverflow)?;
+    if let Some(schedule_acc) = schedule_acc {
+        rent = rent
+            .checked_add(close_program_account(schedule_acc, destination)?)
+            .ok_or(StreamFlowError::MathOverflow)?;
+    }
+    Ok(rent)
+}
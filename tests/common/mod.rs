@@ -0,0 +1,427 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Helpers shared by the integration tests.
+#![allow(dead_code)]
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc, str::FromStr, sync::Once};
+
+use solana_program::{
+    account_info::AccountInfo,
+    clock::Clock,
+    entrypoint::{ProgramResult, SUCCESS},
+    fee_calculator::FeeCalculator,
+    instruction::Instruction,
+    native_token::sol_to_lamports,
+    program_error::ProgramError,
+    program_option::COption,
+    program_pack::Pack,
+    program_stubs::{set_syscall_stubs, SyscallStubs},
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction::{SystemError, SystemInstruction},
+    system_program,
+    sysvar::{self, fees::Fees},
+};
+use solana_sdk::{
+    account::{create_account_for_test, Account},
+    program_utils::limited_deserialize,
+};
+use spl_token::state::{Account as TokenAccount, AccountState, Mint};
+
+use streamflow::process_instruction;
+use streamflow::state::{Config, StreamFlow};
+
+/// Lamports per signature charged by the runtime below.
+pub const LAMPORTS_PER_SIGNATURE: u64 = 5000;
+
+/// The address the tests deploy the program at.
+pub fn program_id() -> Pubkey {
+    Pubkey::from_str("streamf1ow111111111111111111111111111111111").unwrap()
+}
+
+/// An initialized SPL token mint with the given supply.
+pub fn mint_account(authority: &Pubkey, supply: u64) -> Account {
+    let mut data = vec![0; Mint::LEN];
+    Mint::pack(
+        Mint {
+            mint_authority: COption::Some(*authority),
+            supply,
+            decimals: 6,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        },
+        &mut data,
+    )
+    .unwrap();
+
+    Account {
+        owner: spl_token::id(),
+        lamports: sol_to_lamports(1.0),
+        data,
+        ..Account::default()
+    }
+}
+
+/// An initialized SPL token account holding `amount` of `mint`.
+pub fn token_account(mint: &Pubkey, owner: &Pubkey, amount: u64) -> Account {
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount::pack(
+        TokenAccount {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            delegate: COption::None,
+            state: AccountState::Initialized,
+            is_native: COption::None,
+            delegated_amount: 0,
+            close_authority: COption::None,
+        },
+        &mut data,
+    )
+    .unwrap();
+
+    Account {
+        owner: spl_token::id(),
+        lamports: sol_to_lamports(1.0),
+        data,
+        ..Account::default()
+    }
+}
+
+/// Account data of a stream in the current layout.
+pub fn pack_stream(sf: &StreamFlow) -> Vec<u8> {
+    let mut data = vec![0; StreamFlow::LEN];
+    StreamFlow::pack(sf.clone(), &mut data).unwrap();
+    data
+}
+
+/// The program's config account, naming `collector` and some admin.
+pub fn config_account(program_id: &Pubkey, collector: &Pubkey) -> Account {
+    let mut data = vec![0; Config::LEN];
+    Config::pack(Config::new(&Pubkey::new_unique(), collector), &mut data).unwrap();
+    Account {
+        owner: *program_id,
+        lamports: sol_to_lamports(0.01),
+        data,
+        ..Account::default()
+    }
+}
+
+/// Runs the program natively against a set of accounts, without a bank.
+///
+/// Unlike solana-program-test, the system program calls made by the
+/// program may allocate account data here, so handlers creating stream
+/// accounts can be tested without building for BPF. The syscall stubs
+/// are global, so a test binary must not also use solana-program-test.
+pub struct Runtime {
+    pub program_id: Pubkey,
+    /// Unix timestamp returned by the clock sysvar
+    pub now: u64,
+    pub accounts: HashMap<Pubkey, Account>,
+}
+
+impl Runtime {
+    pub fn new(program_id: Pubkey, now: u64) -> Self {
+        let mut accounts = HashMap::new();
+        accounts.insert(
+            sysvar::rent::id(),
+            create_account_for_test(&Rent::default()),
+        );
+        for id in [program_id, system_program::id(), spl_token::id()] {
+            accounts.insert(
+                id,
+                Account {
+                    lamports: 1,
+                    executable: true,
+                    ..Account::default()
+                },
+            );
+        }
+
+        Self {
+            program_id,
+            now,
+            accounts,
+        }
+    }
+
+    pub fn add_account(&mut self, key: Pubkey, account: Account) {
+        self.accounts.insert(key, account);
+    }
+
+    pub fn account(&self, key: &Pubkey) -> Option<&Account> {
+        self.accounts.get(key)
+    }
+
+    pub fn lamports(&self, key: &Pubkey) -> u64 {
+        self.accounts.get(key).map_or(0, |acc| acc.lamports)
+    }
+
+    pub fn token_amount(&self, key: &Pubkey) -> u64 {
+        TokenAccount::unpack(&self.accounts[key].data)
+            .unwrap()
+            .amount
+    }
+
+    /// Run a single instruction of the program. Like a transaction, it
+    /// either applies all of its changes or, on failure, none of them.
+    /// Accounts left without lamports are removed afterwards.
+    pub fn process(&mut self, ix: &Instruction) -> ProgramResult {
+        assert_eq!(ix.program_id, self.program_id);
+
+        static STUBS: Once = Once::new();
+        STUBS.call_once(|| {
+            set_syscall_stubs(Box::new(Stubs));
+        });
+        CONTEXT.with(|ctx| {
+            *ctx.borrow_mut() = Some(Context {
+                program_id: self.program_id,
+                now: self.now,
+                owners: HashMap::new(),
+            })
+        });
+
+        let mut cells: HashMap<Pubkey, AccountCell> = HashMap::new();
+        let infos: Vec<AccountInfo<'static>> = ix
+            .accounts
+            .iter()
+            .map(|meta| {
+                let cell = cells.entry(meta.pubkey).or_insert_with(|| {
+                    let acc = self.accounts.get(&meta.pubkey).cloned().unwrap_or_default();
+                    AccountCell::new(meta.pubkey, acc)
+                });
+                cell.info(meta.is_signer, meta.is_writable)
+            })
+            .collect();
+
+        let before: u64 = cells.values().map(AccountCell::lamports).sum();
+        let result = process_instruction(&self.program_id, &infos, &ix.data);
+        drop(infos);
+        CONTEXT.with(|ctx| ctx.borrow_mut().take());
+        result?;
+
+        let after: u64 = cells.values().map(AccountCell::lamports).sum();
+        assert_eq!(before, after, "instruction created or destroyed lamports");
+
+        for (key, cell) in cells {
+            let acc = cell.into_account();
+            if acc.lamports == 0 {
+                self.accounts.remove(&key);
+            } else {
+                self.accounts.insert(key, acc);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Storage behind the `AccountInfo`s handed to the program. It is leaked
+/// so the infos can live as long as the stubs need them.
+struct AccountCell {
+    key: &'static Pubkey,
+    lamports: Rc<RefCell<&'static mut u64>>,
+    data: Rc<RefCell<&'static mut [u8]>>,
+    owner: &'static Pubkey,
+    executable: bool,
+}
+
+impl AccountCell {
+    fn new(key: Pubkey, acc: Account) -> Self {
+        let owner: *mut Pubkey = Box::into_raw(Box::new(acc.owner));
+        CONTEXT.with(|ctx| {
+            let mut ctx = ctx.borrow_mut();
+            ctx.as_mut().unwrap().owners.insert(key, owner);
+        });
+
+        Self {
+            key: Box::leak(Box::new(key)),
+            lamports: Rc::new(RefCell::new(Box::leak(Box::new(acc.lamports)))),
+            data: Rc::new(RefCell::new(Box::leak(acc.data.into_boxed_slice()))),
+            owner: unsafe { &*owner },
+            executable: acc.executable,
+        }
+    }
+
+    fn info(&self, is_signer: bool, is_writable: bool) -> AccountInfo<'static> {
+        AccountInfo {
+            key: self.key,
+            is_signer,
+            is_writable,
+            lamports: self.lamports.clone(),
+            data: self.data.clone(),
+            owner: self.owner,
+            executable: self.executable,
+            rent_epoch: 0,
+        }
+    }
+
+    fn lamports(&self) -> u64 {
+        **self.lamports.borrow()
+    }
+
+    fn into_account(self) -> Account {
+        Account {
+            lamports: self.lamports(),
+            data: self.data.borrow().to_vec(),
+            owner: *self.owner,
+            executable: self.executable,
+            rent_epoch: 0,
+        }
+    }
+}
+
+/// State of the instruction being processed on this thread.
+struct Context {
+    program_id: Pubkey,
+    now: u64,
+    /// Where the owner of each account is kept, so the system program
+    /// can assign accounts.
+    owners: HashMap<Pubkey, *mut Pubkey>,
+}
+
+thread_local! {
+    static CONTEXT: RefCell<Option<Context>> = const { RefCell::new(None) };
+}
+
+fn with_context<T>(f: impl FnOnce(&Context) -> T) -> T {
+    CONTEXT.with(|ctx| f(ctx.borrow().as_ref().expect("no instruction in progress")))
+}
+
+struct Stubs;
+
+impl SyscallStubs for Stubs {
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let caller = with_context(|ctx| ctx.program_id);
+        let signers: Vec<Pubkey> = signers_seeds
+            .iter()
+            .map(|seeds| Pubkey::create_program_address(seeds, &caller).unwrap())
+            .collect();
+
+        if !account_infos
+            .iter()
+            .any(|info| info.key == &instruction.program_id)
+        {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+
+        let mut accounts = Vec::with_capacity(instruction.accounts.len());
+        for meta in &instruction.accounts {
+            let info = account_infos
+                .iter()
+                .find(|info| info.key == &meta.pubkey)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?;
+            if meta.is_signer && !info.is_signer && !signers.contains(&meta.pubkey) {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            if meta.is_writable && !info.is_writable {
+                return Err(ProgramError::InvalidArgument);
+            }
+
+            let mut info = info.clone();
+            info.is_signer = meta.is_signer;
+            info.is_writable = meta.is_writable;
+            accounts.push(info);
+        }
+
+        if instruction.program_id == system_program::id() {
+            process_system_instruction(&accounts, &instruction.data)
+        } else if instruction.program_id == spl_token::id() {
+            spl_token::processor::Processor::process(
+                &instruction.program_id,
+                &accounts,
+                &instruction.data,
+            )
+        } else {
+            Err(ProgramError::IncorrectProgramId)
+        }
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        let clock = Clock {
+            unix_timestamp: with_context(|ctx| ctx.now) as i64,
+            ..Clock::default()
+        };
+        unsafe { *(var_addr as *mut Clock) = clock };
+        SUCCESS
+    }
+
+    fn sol_get_fees_sysvar(&self, var_addr: *mut u8) -> u64 {
+        let fees = Fees {
+            fee_calculator: FeeCalculator::new(LAMPORTS_PER_SIGNATURE),
+        };
+        unsafe { *(var_addr as *mut Fees) = fees };
+        SUCCESS
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *(var_addr as *mut Rent) = Rent::default() };
+        SUCCESS
+    }
+}
+
+/// The parts of the system program the streamflow program calls into.
+fn process_system_instruction(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let ix: SystemInstruction =
+        limited_deserialize(data).map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    match ix {
+        SystemInstruction::CreateAccount {
+            lamports,
+            space,
+            owner,
+        } => {
+            let (from, to) = (&accounts[0], &accounts[1]);
+            if to.lamports() > 0 || !to.data_is_empty() {
+                return Err(ProgramError::Custom(
+                    SystemError::AccountAlreadyInUse as u32,
+                ));
+            }
+            move_lamports(from, to, lamports)?;
+
+            *to.data.borrow_mut() = Box::leak(vec![0; space as usize].into_boxed_slice());
+            with_context(|ctx| unsafe { *ctx.owners[to.key] = owner });
+            Ok(())
+        }
+        SystemInstruction::Transfer { lamports } => {
+            let (from, to) = (&accounts[0], &accounts[1]);
+            if from.owner != &system_program::id() || !from.data_is_empty() {
+                return Err(ProgramError::InvalidArgument);
+            }
+            move_lamports(from, to, lamports)
+        }
+        ix => panic!("Unsupported system instruction {:?}", ix),
+    }
+}
+
+fn move_lamports(from: &AccountInfo, to: &AccountInfo, lamports: u64) -> ProgramResult {
+    if from.lamports() < lamports {
+        return Err(ProgramError::Custom(
+            SystemError::ResultWithNegativeLamports as u32,
+        ));
+    }
+
+    **from.try_borrow_mut_lamports()? -= lamports;
+    **to.try_borrow_mut_lamports()? += lamports;
+    Ok(())
+}
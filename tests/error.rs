@@ -0,0 +1,53 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+use num_traits::FromPrimitive;
+use solana_program::program_error::ProgramError;
+
+use streamflow::error::StreamFlowError;
+
+/// Clients rely on these codes, so they must never change.
+#[test]
+fn test_error_codes_are_stable() {
+    let codes = vec![
+        (StreamFlowError::UnauthorizedSigner, 0),
+        (StreamFlowError::WrongRecipient, 1),
+        (StreamFlowError::InvalidSchedule, 2),
+        (StreamFlowError::AmountExceedsAvailable, 3),
+        (StreamFlowError::StreamClosed, 4),
+        (StreamFlowError::InvalidStreamAccount, 5),
+        (StreamFlowError::InvalidEscrow, 6),
+        (StreamFlowError::InvalidTokenAccount, 7),
+        (StreamFlowError::InvalidRentReaper, 8),
+        (StreamFlowError::InvalidScheduleAccount, 9),
+        (StreamFlowError::CancelNotAllowed, 10),
+        (StreamFlowError::AlreadyPaused, 11),
+        (StreamFlowError::NotPaused, 12),
+        (StreamFlowError::NotFullyWithdrawn, 13),
+        (StreamFlowError::InvalidConfigAccount, 14),
+        (StreamFlowError::InvariantViolation, 15),
+        (StreamFlowError::MathOverflow, 16),
+        (StreamFlowError::DuplicateRecipient, 17),
+    ];
+
+    for (error, code) in codes {
+        assert_eq!(
+            ProgramError::from(error.clone()),
+            ProgramError::Custom(code)
+        );
+        assert_eq!(StreamFlowError::from_u32(code), Some(error));
+    }
+}
@@ -0,0 +1,120 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+mod common;
+
+use solana_program::{
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    native_token::sol_to_lamports,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use solana_sdk::account::Account;
+
+use streamflow::error::StreamFlowError;
+use streamflow::instruction::initialize_config;
+use streamflow::state::Config;
+use streamflow::utils::find_config_address;
+
+use common::{program_id, Runtime};
+
+#[test]
+fn test_initialize_config() {
+    let program_id = program_id();
+
+    let deployer = Pubkey::new_unique();
+    let carol = Pubkey::new_unique();
+    let dave = Pubkey::new_unique();
+    let (config_acc, _) = find_config_address(&program_id);
+    let (program_data, _) =
+        Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+
+    let mut runtime = Runtime::new(program_id, 0);
+    for payer in [deployer, carol] {
+        runtime.add_account(
+            payer,
+            Account {
+                lamports: sol_to_lamports(1.0),
+                ..Account::default()
+            },
+        );
+    }
+    runtime.add_account(
+        program_data,
+        Account {
+            owner: bpf_loader_upgradeable::id(),
+            lamports: sol_to_lamports(1.0),
+            data: bincode::serialize(&UpgradeableLoaderState::ProgramData {
+                slot: 0,
+                upgrade_authority_address: Some(deployer),
+            })
+            .unwrap(),
+            ..Account::default()
+        },
+    );
+
+    // Nobody but the upgrade authority gets to be the first admin.
+    assert_eq!(
+        runtime.process(&initialize_config(&program_id, &carol, &carol)),
+        Err(StreamFlowError::UnauthorizedSigner.into())
+    );
+    assert!(runtime.account(&config_acc).is_none());
+
+    runtime
+        .process(&initialize_config(&program_id, &deployer, &carol))
+        .unwrap();
+
+    let acc = runtime.account(&config_acc).unwrap();
+    assert_eq!(acc.owner, program_id);
+    assert_eq!(
+        Config::unpack(&acc.data).unwrap(),
+        Config::new(&deployer, &carol)
+    );
+
+    // The config can only be created once.
+    assert_eq!(
+        runtime.process(&initialize_config(&program_id, &deployer, &dave)),
+        Err(ProgramError::AccountAlreadyInitialized)
+    );
+
+    // Programs without an upgrade authority can't get a config at all.
+    let mut runtime = Runtime::new(program_id, 0);
+    runtime.add_account(
+        deployer,
+        Account {
+            lamports: sol_to_lamports(1.0),
+            ..Account::default()
+        },
+    );
+    runtime.add_account(
+        program_data,
+        Account {
+            owner: bpf_loader_upgradeable::id(),
+            lamports: sol_to_lamports(1.0),
+            data: bincode::serialize(&UpgradeableLoaderState::ProgramData {
+                slot: 0,
+                upgrade_authority_address: None,
+            })
+            .unwrap(),
+            ..Account::default()
+        },
+    );
+    assert_eq!(
+        runtime.process(&initialize_config(&program_id, &deployer, &carol)),
+        Err(StreamFlowError::UnauthorizedSigner.into())
+    );
+}
@@ -0,0 +1,328 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+use std::str::FromStr;
+
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+use solana_sdk::{packet::PACKET_DATA_SIZE, transaction::Transaction};
+
+use streamflow::instruction::{
+    crank_token, create_batch_native, create_batch_token, initialize_native, top_up_token,
+    withdraw_token, StreamFlowInstruction, StreamParams,
+};
+use streamflow::utils::{
+    find_batch_metadata_address, find_config_address, find_escrow_address, find_schedule_address,
+    find_stream_address, Breakpoint, PeriodUnit, TopUpMode, MAX_BATCH_SIZE, MAX_BREAKPOINTS,
+};
+
+fn params() -> StreamParams {
+    StreamParams {
+        start_time: 1_600_000_000,
+        end_time: 1_600_000_600,
+        amount: 42,
+        cliff_time: 0,
+        cliff_amount: 0,
+        period: 0,
+        amount_per_period: 0,
+        period_unit: PeriodUnit::Seconds,
+        breakpoints: vec![],
+        rate: 0,
+        transfer_by_sender_only: false,
+        cancelable_by_sender: true,
+        cancelable_by_recipient: false,
+        rent_destination: None,
+        crank_fee_bps: 0,
+    }
+}
+
+#[test]
+fn test_pack_unpack() {
+    let instructions = vec![
+        StreamFlowInstruction::InitializeNative {
+            params: params(),
+            nonce: 7,
+        },
+        StreamFlowInstruction::WithdrawNative { amount: 3 },
+        StreamFlowInstruction::CancelNative,
+        StreamFlowInstruction::InitializeToken { params: params() },
+        StreamFlowInstruction::InitializeNative {
+            params: StreamParams {
+                end_time: 0,
+                rate: 5,
+                ..params()
+            },
+            nonce: 1,
+        },
+        StreamFlowInstruction::InitializeToken {
+            params: StreamParams {
+                breakpoints: vec![
+                    Breakpoint {
+                        timestamp: 1_600_000_000,
+                        cumulative: 4,
+                    },
+                    Breakpoint {
+                        timestamp: 1_600_000_600,
+                        cumulative: 42,
+                    },
+                ],
+                ..params()
+            },
+        },
+        StreamFlowInstruction::WithdrawToken { amount: u64::MAX },
+        StreamFlowInstruction::CancelToken,
+        StreamFlowInstruction::TopUpNative {
+            amount: 9,
+            mode: TopUpMode::Extend,
+        },
+        StreamFlowInstruction::TopUpToken {
+            amount: 9,
+            mode: TopUpMode::Increase,
+        },
+        StreamFlowInstruction::InitializeToken {
+            params: StreamParams {
+                transfer_by_sender_only: true,
+                ..params()
+            },
+        },
+        StreamFlowInstruction::TransferRecipient,
+        StreamFlowInstruction::Pause,
+        StreamFlowInstruction::Resume,
+        StreamFlowInstruction::InitializeNative {
+            params: StreamParams {
+                rent_destination: Some(Pubkey::new_unique()),
+                crank_fee_bps: 25,
+                ..params()
+            },
+            nonce: 2,
+        },
+        StreamFlowInstruction::CloseNative,
+        StreamFlowInstruction::CloseToken,
+        StreamFlowInstruction::InitializeConfig {
+            collector: Pubkey::new_unique(),
+        },
+        StreamFlowInstruction::SetConfig {
+            admin: Pubkey::new_unique(),
+            collector: Pubkey::new_unique(),
+        },
+        StreamFlowInstruction::CrankNative,
+        StreamFlowInstruction::CrankToken,
+        StreamFlowInstruction::CreateBatchNative {
+            entries: vec![params()],
+            nonce: 3,
+        },
+        StreamFlowInstruction::CreateBatchToken {
+            entries: vec![params(); MAX_BATCH_SIZE],
+            nonce: 4,
+        },
+    ];
+
+    for ix in instructions {
+        let packed = ix.pack();
+        assert_eq!(StreamFlowInstruction::unpack(&packed).unwrap(), ix);
+    }
+}
+
+#[test]
+fn test_wire_format() {
+    let packed = StreamFlowInstruction::InitializeNative {
+        params: params(),
+        nonce: 7,
+    }
+    .pack();
+    assert_eq!(packed.len(), 112);
+    assert_eq!(packed[0], 0);
+    assert_eq!(&packed[1..9], &1_600_000_000u64.to_le_bytes());
+    assert_eq!(&packed[25..33], &0u64.to_le_bytes());
+    assert_eq!(packed[57], PeriodUnit::Seconds as u8);
+    assert_eq!(packed[58], 0);
+    assert_eq!(&packed[59..67], &0u64.to_le_bytes());
+    assert_eq!(packed[67..70], [0, 1, 0]);
+    assert_eq!(&packed[70..102], &[0; 32]);
+    assert_eq!(packed[102..104], [0, 0]);
+    assert_eq!(&packed[104..112], &7u64.to_le_bytes());
+
+    let packed = StreamFlowInstruction::WithdrawNative { amount: 3 }.pack();
+    assert_eq!(packed, vec![1, 3, 0, 0, 0, 0, 0, 0, 0]);
+
+    let packed = StreamFlowInstruction::TopUpToken {
+        amount: 3,
+        mode: TopUpMode::Increase,
+    }
+    .pack();
+    assert_eq!(packed, vec![7, 3, 0, 0, 0, 0, 0, 0, 0, 1]);
+}
+
+#[test]
+fn test_unpack_invalid() {
+    let mut bad_unit = [0; 112];
+    bad_unit[57] = 255;
+
+    let mut bad_flag = [0; 102];
+    bad_flag[0] = 3;
+    bad_flag[68] = 2;
+
+    let mut no_crank_fee = [0; 103];
+    no_crank_fee[0] = 3;
+
+    let too_many_streams = StreamFlowInstruction::CreateBatchNative {
+        entries: vec![params(); MAX_BATCH_SIZE + 1],
+        nonce: 0,
+    }
+    .pack();
+
+    let mut too_many_breakpoints = vec![3; 59];
+    too_many_breakpoints[57] = 0;
+    too_many_breakpoints[58] = MAX_BREAKPOINTS as u8 + 1;
+    too_many_breakpoints.extend(vec![0; 16 * (MAX_BREAKPOINTS + 1)]);
+
+    let invalid: Vec<&[u8]> = vec![
+        // Empty buffer
+        &[],
+        // Unknown instruction
+        &[255],
+        // Withdraw amount too short
+        &[1, 0, 0, 0],
+        // Trailing bytes
+        &[2, 0],
+        // Unknown period unit
+        &bad_unit,
+        // More breakpoints than a schedule can hold
+        &too_many_breakpoints,
+        // Invalid cancellation flag
+        &bad_flag,
+        // Init missing the nonce
+        &[0; 104],
+        // Init with half a crank fee
+        &no_crank_fee,
+        // Top-up missing the mode
+        &[6, 3, 0, 0, 0, 0, 0, 0, 0],
+        // Unknown top-up mode
+        &[6, 3, 0, 0, 0, 0, 0, 0, 0, 2],
+        // Config update missing the collector
+        &[14; 33],
+        // Empty batch
+        &[17, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        // Batch larger than a transaction can hold
+        &too_many_streams,
+    ];
+
+    for input in invalid {
+        assert_eq!(
+            StreamFlowInstruction::unpack(input),
+            Err(ProgramError::InvalidInstructionData)
+        );
+    }
+}
+
+#[test]
+fn test_builders() {
+    let program_id = Pubkey::from_str("streamf1ow111111111111111111111111111111111").unwrap();
+    let alice = Pubkey::new_unique();
+    let bob = Pubkey::new_unique();
+    let bob_tokens = Pubkey::new_unique();
+    let metadata = Pubkey::new_unique();
+
+    let ix = initialize_native(&program_id, &alice, &bob, params(), 7);
+    let (stream, _) = find_stream_address(&program_id, &alice, &bob, 7);
+    assert_eq!(ix.program_id, program_id);
+    assert_eq!(ix.accounts[2].pubkey, stream);
+    assert!(ix.accounts[0].is_signer && !ix.accounts[2].is_signer);
+    let (schedule, _) = find_schedule_address(&program_id, &stream);
+    assert_eq!(ix.accounts[4].pubkey, schedule);
+
+    let ix = withdraw_token(&program_id, &bob, &bob_tokens, &metadata, &alice, None, 0);
+    let (escrow, _) = find_escrow_address(&program_id, &metadata);
+    assert_eq!(ix.accounts[3].pubkey, escrow);
+    assert_eq!(ix.accounts[4].pubkey, spl_token::id());
+    let (config, _) = find_config_address(&program_id);
+    assert_eq!(ix.accounts[6].pubkey, config);
+    assert_eq!(
+        StreamFlowInstruction::unpack(&ix.data).unwrap(),
+        StreamFlowInstruction::WithdrawToken { amount: 0 }
+    );
+
+    let ix = crank_token(&program_id, &alice, &bob_tokens, &metadata, &alice, None);
+    assert!(ix.accounts[0].is_signer && !ix.accounts[0].is_writable);
+    assert_eq!(ix.accounts[3].pubkey, escrow);
+    assert_eq!(ix.accounts.len(), 8);
+    let ix = crank_token(
+        &program_id,
+        &alice,
+        &bob_tokens,
+        &metadata,
+        &alice,
+        Some(&alice),
+    );
+    assert!(ix.accounts[8].is_writable);
+
+    let ix = top_up_token(
+        &program_id,
+        &alice,
+        &bob_tokens,
+        &metadata,
+        5,
+        TopUpMode::Extend,
+    );
+    assert_eq!(ix.accounts[3].pubkey, escrow);
+    assert!(ix.accounts[0].is_signer && !ix.accounts[0].is_writable);
+}
+
+#[test]
+fn test_batch_builders() {
+    let program_id = Pubkey::from_str("streamf1ow111111111111111111111111111111111").unwrap();
+    let alice = Pubkey::new_unique();
+    let alice_tokens = Pubkey::new_unique();
+    let mint = Pubkey::new_unique();
+    let recipients: Vec<_> = (0..MAX_BATCH_SIZE).map(|_| Pubkey::new_unique()).collect();
+
+    let ix = create_batch_native(
+        &program_id,
+        &alice,
+        recipients.iter().map(|bob| (*bob, params())).collect(),
+        7,
+    );
+    assert_eq!(ix.accounts.len(), 2 + 2 * MAX_BATCH_SIZE);
+    for (i, bob) in recipients.iter().enumerate() {
+        let (stream, _) = find_stream_address(&program_id, &alice, bob, 7);
+        assert_eq!(ix.accounts[2 + 2 * i].pubkey, *bob);
+        assert_eq!(ix.accounts[3 + 2 * i].pubkey, stream);
+    }
+
+    let ix = create_batch_token(
+        &program_id,
+        &alice,
+        &alice_tokens,
+        &mint,
+        recipients
+            .iter()
+            .map(|bob| (*bob, Pubkey::new_unique(), params()))
+            .collect(),
+        7,
+    );
+    assert_eq!(ix.accounts.len(), 6 + 4 * MAX_BATCH_SIZE);
+    for (i, bob) in recipients.iter().enumerate() {
+        let (metadata, _) = find_batch_metadata_address(&program_id, &alice, &mint, 7, i as u8);
+        let (escrow, _) = find_escrow_address(&program_id, &metadata);
+        assert_eq!(ix.accounts[6 + 4 * i].pubkey, *bob);
+        assert_eq!(ix.accounts[8 + 4 * i].pubkey, metadata);
+        assert_eq!(ix.accounts[9 + 4 * i].pubkey, escrow);
+    }
+
+    // A full batch fits into a single transaction signed by the sender.
+    let transaction = Transaction::new_with_payer(&[ix], Some(&alice));
+    let size = 1 + 64 + transaction.message_data().len();
+    assert!(size <= PACKET_DATA_SIZE, "{}", size);
+}
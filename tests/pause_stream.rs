@@ -0,0 +1,150 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+use std::{str::FromStr, time::SystemTime};
+
+use solana_program::{
+    instruction::InstructionError, native_token::sol_to_lamports, program_pack::Pack,
+    pubkey::Pubkey,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    signature::Signer,
+    signer::keypair::Keypair,
+    transaction::{Transaction, TransactionError},
+};
+
+use streamflow::error::StreamFlowError;
+use streamflow::instruction::{pause, resume};
+use streamflow::process_instruction;
+use streamflow::state::{StreamFlow, STREAM_VERSION};
+use streamflow::utils::PeriodUnit;
+
+#[tokio::test]
+async fn test_pause_resume_stream() {
+    let program_id = Pubkey::from_str("streamf1ow111111111111111111111111111111111").unwrap();
+
+    let alice = Keypair::new();
+    let bob = Keypair::new();
+    let pda = Keypair::new();
+
+    let mut program_test =
+        ProgramTest::new("streamflow", program_id, processor!(process_instruction));
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let dat = StreamFlow {
+        version: STREAM_VERSION,
+        start_time: now - 300,
+        end_time: now + 300,
+        amount: sol_to_lamports(1.0),
+        withdrawn: 0,
+        sender: alice.pubkey().to_bytes(),
+        recipient: bob.pubkey().to_bytes(),
+        mint: bob.pubkey().to_bytes(),   // placeholder
+        escrow: bob.pubkey().to_bytes(), // placeholder
+        nonce: 0,
+        bump: 0,
+        escrow_bump: 0,
+        cliff_time: 0,
+        cliff_amount: 0,
+        period: 0,
+        amount_per_period: 0,
+        period_unit: PeriodUnit::Seconds,
+        schedule: [0; 32],
+        rate: 0,
+        recipient_tokens: [0; 32],
+        transfer_by_sender_only: false,
+        cancelable_by_sender: true,
+        cancelable_by_recipient: false,
+        paused_at: 0,
+        paused_time: 0,
+        rent_destination: alice.pubkey().to_bytes(),
+        crank_fee_bps: 0,
+    };
+
+    let mut data = vec![0; StreamFlow::LEN];
+    StreamFlow::pack(dat, &mut data).unwrap();
+    program_test.add_account(
+        pda.pubkey(),
+        Account {
+            owner: program_id,
+            lamports: sol_to_lamports(1.1),
+            data,
+            ..Account::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // Only the sender gets to pause the stream.
+    let mut transaction = Transaction::new_with_payer(
+        &[pause(&program_id, &bob.pubkey(), &pda.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &bob], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StreamFlowError::UnauthorizedSigner as u32)
+        )
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[pause(&program_id, &alice.pubkey(), &pda.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &alice], recent_blockhash);
+    match banks_client.process_transaction(transaction).await {
+        Ok(()) => (),
+        Err(e) => panic!("{}", e),
+    }
+
+    let pda_acc = banks_client
+        .get_account(pda.pubkey())
+        .await
+        .unwrap()
+        .unwrap();
+    let sf = StreamFlow::unpack(&pda_acc.data).unwrap();
+    assert!(sf.is_paused());
+
+    let mut transaction = Transaction::new_with_payer(
+        &[resume(&program_id, &alice.pubkey(), &pda.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &alice], recent_blockhash);
+    match banks_client.process_transaction(transaction).await {
+        Ok(()) => (),
+        Err(e) => panic!("{}", e),
+    }
+
+    let pda_acc = banks_client
+        .get_account(pda.pubkey())
+        .await
+        .unwrap()
+        .unwrap();
+    let sf = StreamFlow::unpack(&pda_acc.data).unwrap();
+    assert!(!sf.is_paused());
+}
@@ -0,0 +1,107 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+use std::str::FromStr;
+
+use solana_program::{
+    instruction::InstructionError, native_token::sol_to_lamports, program_pack::Pack,
+    pubkey::Pubkey,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    signature::Signer,
+    signer::keypair::Keypair,
+    transaction::{Transaction, TransactionError},
+};
+
+use streamflow::error::StreamFlowError;
+use streamflow::instruction::set_config;
+use streamflow::process_instruction;
+use streamflow::state::Config;
+use streamflow::utils::find_config_address;
+
+#[tokio::test]
+async fn test_set_config() {
+    let program_id = Pubkey::from_str("streamf1ow111111111111111111111111111111111").unwrap();
+
+    let admin = Keypair::new();
+    let carol = Keypair::new();
+    let dave = Keypair::new();
+    let (config_acc, _) = find_config_address(&program_id);
+
+    let mut program_test =
+        ProgramTest::new("streamflow", program_id, processor!(process_instruction));
+
+    let mut data = vec![0; Config::LEN];
+    Config::pack(Config::new(&admin.pubkey(), &carol.pubkey()), &mut data).unwrap();
+    program_test.add_account(
+        config_acc,
+        Account {
+            owner: program_id,
+            lamports: sol_to_lamports(0.01),
+            data,
+            ..Account::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // Only the admin may change it.
+    let mut transaction = Transaction::new_with_payer(
+        &[set_config(
+            &program_id,
+            &dave.pubkey(),
+            &dave.pubkey(),
+            &dave.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &dave], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StreamFlowError::UnauthorizedSigner as u32)
+        )
+    );
+
+    // The admin hands the config over to Dave.
+    let mut transaction = Transaction::new_with_payer(
+        &[set_config(
+            &program_id,
+            &admin.pubkey(),
+            &dave.pubkey(),
+            &dave.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &admin], recent_blockhash);
+    match banks_client.process_transaction(transaction).await {
+        Ok(()) => (),
+        Err(e) => panic!("{}", e),
+    }
+
+    let acc = banks_client.get_account(config_acc).await.unwrap().unwrap();
+    assert_eq!(
+        Config::unpack(&acc.data).unwrap(),
+        Config::new(&dave.pubkey(), &dave.pubkey())
+    );
+}
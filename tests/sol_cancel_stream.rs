@@ -14,30 +14,35 @@
 //
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
+mod common;
+
 use std::{str::FromStr, time::SystemTime};
 
 use solana_program::{
-    instruction::{AccountMeta, Instruction},
+    instruction::{AccountMeta, Instruction, InstructionError},
     native_token::sol_to_lamports,
     pubkey::Pubkey,
 };
 use solana_program_test::*;
 use solana_sdk::{
-    account::Account, signature::Signer, signer::keypair::Keypair, system_program,
-    transaction::Transaction,
+    account::Account,
+    signature::Signer,
+    signer::keypair::Keypair,
+    system_program,
+    transaction::{Transaction, TransactionError},
 };
 
+use streamflow::error::StreamFlowError;
+use streamflow::instruction::{cancel_native, StreamFlowInstruction};
 use streamflow::process_instruction;
-use streamflow::utils::{any_as_u8_slice, StreamFlow};
+use streamflow::state::{StreamFlow, STREAM_VERSION};
+use streamflow::utils::PeriodUnit;
 
-#[repr(packed(1))]
-struct StFl {
-    instruction: u8,
-}
+use common::pack_stream;
 
 #[tokio::test]
 async fn test_cancel_stream() {
-    let program_id = Pubkey::from_str(&"streamf1ow111111111111111111111111111111111").unwrap();
+    let program_id = Pubkey::from_str("streamf1ow111111111111111111111111111111111").unwrap();
 
     let alice = Keypair::new();
     let bob = Keypair::new();
@@ -51,11 +56,12 @@ async fn test_cancel_stream() {
         .unwrap()
         .as_secs();
 
-    let sf = StFl { instruction: 2 };
+    let sf = StreamFlowInstruction::CancelNative;
 
-    println!("instruction: {}", { sf.instruction });
+    println!("instruction: {:?}", sf);
 
     let dat = StreamFlow {
+        version: STREAM_VERSION,
         start_time: now - 610,
         end_time: now - 10,
         amount: sol_to_lamports(90.0),
@@ -64,6 +70,24 @@ async fn test_cancel_stream() {
         recipient: bob.pubkey().to_bytes(),
         mint: bob.pubkey().to_bytes(),   // placeholder
         escrow: bob.pubkey().to_bytes(), // placeholder
+        nonce: 0,
+        bump: 0,
+        escrow_bump: 0,
+        cliff_time: 0,
+        cliff_amount: 0,
+        period: 0,
+        amount_per_period: 0,
+        period_unit: PeriodUnit::Seconds,
+        schedule: [0; 32],
+        rate: 0,
+        recipient_tokens: [0; 32],
+        transfer_by_sender_only: false,
+        cancelable_by_sender: true,
+        cancelable_by_recipient: false,
+        paused_at: 0,
+        paused_time: 0,
+        rent_destination: alice.pubkey().to_bytes(),
+        crank_fee_bps: 0,
     };
 
     program_test.add_account(
@@ -71,7 +95,7 @@ async fn test_cancel_stream() {
         Account {
             owner: program_id,
             lamports: sol_to_lamports(95.0),
-            data: unsafe { any_as_u8_slice(&dat).to_vec() },
+            data: pack_stream(&dat),
             ..Account::default()
         },
     );
@@ -81,7 +105,7 @@ async fn test_cancel_stream() {
     let mut transaction = Transaction::new_with_payer(
         &[Instruction::new_with_bytes(
             program_id,
-            unsafe { any_as_u8_slice(&sf) },
+            &sf.pack(),
             vec![
                 AccountMeta::new(alice.pubkey(), true),
                 AccountMeta::new(bob.pubkey(), false),
@@ -101,3 +125,194 @@ async fn test_cancel_stream() {
 
     // TODO: Asserts
 }
+
+#[tokio::test]
+async fn test_cancel_stream_unauthorized() {
+    let program_id = Pubkey::from_str("streamf1ow111111111111111111111111111111111").unwrap();
+
+    let alice = Keypair::new();
+    let bob = Keypair::new();
+    let pda = Keypair::new();
+
+    let mut program_test =
+        ProgramTest::new("streamflow", program_id, processor!(process_instruction));
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let dat = StreamFlow {
+        version: STREAM_VERSION,
+        start_time: now - 610,
+        end_time: now - 10,
+        amount: sol_to_lamports(90.0),
+        withdrawn: 0,
+        sender: alice.pubkey().to_bytes(),
+        recipient: bob.pubkey().to_bytes(),
+        mint: bob.pubkey().to_bytes(),   // placeholder
+        escrow: bob.pubkey().to_bytes(), // placeholder
+        nonce: 0,
+        bump: 0,
+        escrow_bump: 0,
+        cliff_time: 0,
+        cliff_amount: 0,
+        period: 0,
+        amount_per_period: 0,
+        period_unit: PeriodUnit::Seconds,
+        schedule: [0; 32],
+        rate: 0,
+        recipient_tokens: [0; 32],
+        transfer_by_sender_only: false,
+        cancelable_by_sender: true,
+        cancelable_by_recipient: false,
+        paused_at: 0,
+        paused_time: 0,
+        rent_destination: alice.pubkey().to_bytes(),
+        crank_fee_bps: 0,
+    };
+
+    program_test.add_account(
+        pda.pubkey(),
+        Account {
+            owner: program_id,
+            lamports: sol_to_lamports(95.0),
+            data: pack_stream(&dat),
+            ..Account::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // Bob tries to cancel a stream he is only the recipient of.
+    let mut transaction = Transaction::new_with_payer(
+        &[cancel_native(
+            &program_id,
+            &bob.pubkey(),
+            &bob.pubkey(),
+            &pda.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &bob], recent_blockhash);
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StreamFlowError::UnauthorizedSigner as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_cancel_stream_by_recipient() {
+    let program_id = Pubkey::from_str("streamf1ow111111111111111111111111111111111").unwrap();
+
+    let alice = Keypair::new();
+    let bob = Keypair::new();
+    let pda = Keypair::new();
+
+    let mut program_test =
+        ProgramTest::new("streamflow", program_id, processor!(process_instruction));
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let dat = StreamFlow {
+        version: STREAM_VERSION,
+        start_time: now - 610,
+        end_time: now - 10,
+        amount: sol_to_lamports(90.0),
+        withdrawn: 0,
+        sender: alice.pubkey().to_bytes(),
+        recipient: bob.pubkey().to_bytes(),
+        mint: bob.pubkey().to_bytes(),   // placeholder
+        escrow: bob.pubkey().to_bytes(), // placeholder
+        nonce: 0,
+        bump: 0,
+        escrow_bump: 0,
+        cliff_time: 0,
+        cliff_amount: 0,
+        period: 0,
+        amount_per_period: 0,
+        period_unit: PeriodUnit::Seconds,
+        schedule: [0; 32],
+        rate: 0,
+        recipient_tokens: [0; 32],
+        transfer_by_sender_only: false,
+        cancelable_by_sender: false,
+        cancelable_by_recipient: true,
+        paused_at: 0,
+        paused_time: 0,
+        rent_destination: alice.pubkey().to_bytes(),
+        crank_fee_bps: 0,
+    };
+
+    program_test.add_account(
+        pda.pubkey(),
+        Account {
+            owner: program_id,
+            lamports: sol_to_lamports(95.0),
+            data: pack_stream(&dat),
+            ..Account::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // The sender gave up on cancelling this stream.
+    let mut transaction = Transaction::new_with_payer(
+        &[cancel_native(
+            &program_id,
+            &alice.pubkey(),
+            &bob.pubkey(),
+            &pda.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &alice], recent_blockhash);
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StreamFlowError::CancelNotAllowed as u32)
+        )
+    );
+
+    let mut ix = cancel_native(&program_id, &alice.pubkey(), &bob.pubkey(), &pda.pubkey());
+    ix.accounts[0].is_signer = false;
+    ix.accounts[1].is_signer = true;
+    let mut transaction = Transaction::new_with_payer(&[ix], Some(&payer.pubkey()));
+
+    transaction.sign(&[&payer, &bob], recent_blockhash);
+
+    match banks_client.process_transaction(transaction).await {
+        Ok(()) => (),
+        Err(e) => panic!("{}", e),
+    }
+
+    // Everything was unlocked already, and Alice only gets the rest back.
+    assert_eq!(
+        banks_client.get_balance(bob.pubkey()).await.unwrap(),
+        sol_to_lamports(90.0)
+    );
+    assert_eq!(
+        banks_client.get_balance(alice.pubkey()).await.unwrap(),
+        sol_to_lamports(5.0)
+    );
+    assert_eq!(banks_client.get_balance(pda.pubkey()).await.unwrap(), 0);
+}
@@ -0,0 +1,260 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+mod common;
+
+use std::{str::FromStr, time::SystemTime};
+
+use solana_program::{
+    instruction::InstructionError, native_token::sol_to_lamports,
+    pubkey::Pubkey,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    signature::Signer,
+    signer::keypair::Keypair,
+    transaction::{Transaction, TransactionError},
+};
+
+use streamflow::error::StreamFlowError;
+use streamflow::instruction::close_native;
+use streamflow::process_instruction;
+use streamflow::state::{StreamFlow, STREAM_VERSION};
+use streamflow::utils::{find_config_address, PeriodUnit};
+
+use common::{config_account, pack_stream};
+
+fn finished_stream(
+    alice: &Pubkey,
+    bob: &Pubkey,
+    rent_destination: &Pubkey,
+    now: u64,
+) -> StreamFlow {
+    StreamFlow {
+        version: STREAM_VERSION,
+        start_time: now - 610,
+        end_time: now - 10,
+        amount: sol_to_lamports(1.0),
+        withdrawn: sol_to_lamports(1.0),
+        sender: alice.to_bytes(),
+        recipient: bob.to_bytes(),
+        mint: bob.to_bytes(),   // placeholder
+        escrow: bob.to_bytes(), // placeholder
+        nonce: 0,
+        bump: 0,
+        escrow_bump: 0,
+        cliff_time: 0,
+        cliff_amount: 0,
+        period: 0,
+        amount_per_period: 0,
+        period_unit: PeriodUnit::Seconds,
+        schedule: [0; 32],
+        rate: 0,
+        recipient_tokens: [0; 32],
+        transfer_by_sender_only: false,
+        cancelable_by_sender: true,
+        cancelable_by_recipient: false,
+        paused_at: 0,
+        paused_time: 0,
+        rent_destination: rent_destination.to_bytes(),
+        crank_fee_bps: 0,
+    }
+}
+
+#[tokio::test]
+async fn test_close_stream() {
+    let program_id = Pubkey::from_str("streamf1ow111111111111111111111111111111111").unwrap();
+
+    let alice = Keypair::new();
+    let bob = Keypair::new();
+    let carol = Keypair::new();
+    let collector = Keypair::new();
+    let finished = Keypair::new();
+    let running = Keypair::new();
+    let legacy = Keypair::new();
+
+    let mut program_test =
+        ProgramTest::new("streamflow", program_id, processor!(process_instruction));
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let dat = finished_stream(&alice.pubkey(), &bob.pubkey(), &carol.pubkey(), now);
+
+    for (account, sf) in [
+        (&finished, dat.clone()),
+        (
+            &running,
+            StreamFlow {
+                withdrawn: 0,
+                ..dat.clone()
+            },
+        ),
+        (
+            &legacy,
+            StreamFlow {
+                rent_destination: [0; 32],
+                ..dat.clone()
+            },
+        ),
+    ] {
+        program_test.add_account(
+            account.pubkey(),
+            Account {
+                owner: program_id,
+                lamports: sol_to_lamports(0.1),
+                data: pack_stream(&sf),
+                ..Account::default()
+            },
+        );
+    }
+
+    program_test.add_account(
+        find_config_address(&program_id).0,
+        config_account(&program_id, &collector.pubkey()),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let failures = [
+        (
+            &finished,
+            alice.pubkey(),
+            StreamFlowError::InvalidRentReaper,
+        ),
+        (&running, carol.pubkey(), StreamFlowError::NotFullyWithdrawn),
+        (&legacy, carol.pubkey(), StreamFlowError::InvalidRentReaper),
+    ];
+    for (stream, destination, error) in failures {
+        let mut transaction = Transaction::new_with_payer(
+            &[close_native(&program_id, &stream.pubkey(), &destination)],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer], recent_blockhash);
+        assert_eq!(
+            banks_client
+                .process_transaction(transaction)
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+        );
+    }
+
+    let mut transaction = Transaction::new_with_payer(
+        &[close_native(
+            &program_id,
+            &finished.pubkey(),
+            &carol.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    match banks_client.process_transaction(transaction).await {
+        Ok(()) => (),
+        Err(e) => panic!("{}", e),
+    }
+
+    assert_eq!(
+        banks_client.get_balance(carol.pubkey()).await.unwrap(),
+        sol_to_lamports(0.1)
+    );
+    assert!(banks_client
+        .get_account(finished.pubkey())
+        .await
+        .unwrap()
+        .is_none());
+
+    // Streams without a rent destination of their own leave it to the
+    // program's collector.
+    let mut transaction = Transaction::new_with_payer(
+        &[close_native(
+            &program_id,
+            &legacy.pubkey(),
+            &collector.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    match banks_client.process_transaction(transaction).await {
+        Ok(()) => (),
+        Err(e) => panic!("{}", e),
+    }
+
+    assert_eq!(
+        banks_client.get_balance(collector.pubkey()).await.unwrap(),
+        sol_to_lamports(0.1)
+    );
+}
+
+#[tokio::test]
+async fn test_close_stream_without_config() {
+    let program_id = Pubkey::from_str("streamf1ow111111111111111111111111111111111").unwrap();
+
+    let alice = Keypair::new();
+    let bob = Keypair::new();
+    let carol = Keypair::new();
+    let finished = Keypair::new();
+
+    let mut program_test =
+        ProgramTest::new("streamflow", program_id, processor!(process_instruction));
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    // Streams with a rent destination of their own don't need the config,
+    // which doesn't even exist here.
+    program_test.add_account(
+        finished.pubkey(),
+        Account {
+            owner: program_id,
+            lamports: sol_to_lamports(0.1),
+            data: pack_stream(&finished_stream(
+                &alice.pubkey(),
+                &bob.pubkey(),
+                &carol.pubkey(),
+                now,
+            )),
+            ..Account::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[close_native(
+            &program_id,
+            &finished.pubkey(),
+            &carol.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    match banks_client.process_transaction(transaction).await {
+        Ok(()) => (),
+        Err(e) => panic!("{}", e),
+    }
+
+    assert_eq!(
+        banks_client.get_balance(carol.pubkey()).await.unwrap(),
+        sol_to_lamports(0.1)
+    );
+}
@@ -0,0 +1,164 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+mod common;
+
+use std::time::SystemTime;
+
+use solana_program::{
+    instruction::InstructionError, native_token::sol_to_lamports, pubkey::Pubkey,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    signature::Signer,
+    signer::keypair::Keypair,
+    transaction::{Transaction, TransactionError},
+};
+
+use streamflow::error::StreamFlowError;
+use streamflow::instruction::crank_native;
+use streamflow::process_instruction;
+use streamflow::state::{StreamFlow, STREAM_VERSION};
+use streamflow::utils::{find_config_address, PeriodUnit};
+
+use common::{config_account, pack_stream, program_id};
+
+#[tokio::test]
+async fn test_crank_withdraw() {
+    let program_id = program_id();
+
+    let alice = Keypair::new();
+    let bob = Keypair::new();
+    let keeper = Keypair::new();
+    let pda = Keypair::new();
+
+    let mut program_test =
+        ProgramTest::new("streamflow", program_id, processor!(process_instruction));
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    // Everything is unlocked, and 1% of it goes to whoever cranks.
+    let dat = StreamFlow {
+        version: STREAM_VERSION,
+        start_time: now - 610,
+        end_time: now - 10,
+        amount: sol_to_lamports(100.0),
+        withdrawn: 0,
+        sender: alice.pubkey().to_bytes(),
+        recipient: bob.pubkey().to_bytes(),
+        mint: bob.pubkey().to_bytes(),   // placeholder
+        escrow: bob.pubkey().to_bytes(), // placeholder
+        nonce: 0,
+        bump: 0,
+        escrow_bump: 0,
+        cliff_time: 0,
+        cliff_amount: 0,
+        period: 0,
+        amount_per_period: 0,
+        period_unit: PeriodUnit::Seconds,
+        schedule: [0; 32],
+        rate: 0,
+        recipient_tokens: [0; 32],
+        transfer_by_sender_only: false,
+        cancelable_by_sender: true,
+        cancelable_by_recipient: false,
+        paused_at: 0,
+        paused_time: 0,
+        rent_destination: alice.pubkey().to_bytes(),
+        crank_fee_bps: 100,
+    };
+
+    program_test.add_account(
+        pda.pubkey(),
+        Account {
+            owner: program_id,
+            lamports: sol_to_lamports(101.0),
+            data: pack_stream(&dat),
+            ..Account::default()
+        },
+    );
+
+    program_test.add_account(
+        find_config_address(&program_id).0,
+        config_account(&program_id, &Pubkey::new_unique()),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // The keeper can't have the funds sent anywhere but to Bob.
+    let mut transaction = Transaction::new_with_payer(
+        &[crank_native(
+            &program_id,
+            &keeper.pubkey(),
+            &keeper.pubkey(),
+            &pda.pubkey(),
+            &alice.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &keeper], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StreamFlowError::WrongRecipient as u32)
+        )
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[crank_native(
+            &program_id,
+            &keeper.pubkey(),
+            &bob.pubkey(),
+            &pda.pubkey(),
+            &alice.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &keeper], recent_blockhash);
+
+    match banks_client.process_transaction(transaction).await {
+        Ok(()) => (),
+        Err(e) => panic!("{}", e),
+    }
+
+    assert_eq!(
+        banks_client.get_balance(bob.pubkey()).await.unwrap(),
+        sol_to_lamports(99.0)
+    );
+    assert_eq!(
+        banks_client.get_balance(keeper.pubkey()).await.unwrap(),
+        sol_to_lamports(1.0)
+    );
+    // The stream is closed like after any other full withdrawal.
+    assert_eq!(
+        banks_client.get_balance(alice.pubkey()).await.unwrap(),
+        sol_to_lamports(1.0)
+    );
+    assert!(banks_client
+        .get_account(pda.pubkey())
+        .await
+        .unwrap()
+        .is_none());
+}
@@ -0,0 +1,186 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+mod common;
+
+use std::time::SystemTime;
+
+use solana_program::{
+    native_token::sol_to_lamports, program_error::ProgramError, program_pack::Pack, pubkey::Pubkey,
+    rent::Rent,
+};
+use solana_sdk::account::Account;
+
+use streamflow::error::StreamFlowError;
+use streamflow::instruction::{create_batch_native, StreamParams};
+use streamflow::state::StreamFlow;
+use streamflow::utils::{find_stream_address, PeriodUnit, MAX_BATCH_SIZE};
+
+use common::{program_id, Runtime, LAMPORTS_PER_SIGNATURE};
+
+fn params(now: u64, amount: u64) -> StreamParams {
+    StreamParams {
+        start_time: now + 10,
+        end_time: now + 610,
+        amount,
+        cliff_time: 0,
+        cliff_amount: 0,
+        period: 0,
+        amount_per_period: 0,
+        period_unit: PeriodUnit::Seconds,
+        breakpoints: vec![],
+        rate: 0,
+        transfer_by_sender_only: false,
+        cancelable_by_sender: true,
+        cancelable_by_recipient: false,
+        rent_destination: None,
+        crank_fee_bps: 0,
+    }
+}
+
+/// A runtime where Alice holds 100 SOL.
+fn setup() -> (Runtime, Pubkey, u64) {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let alice = Pubkey::new_unique();
+
+    let mut runtime = Runtime::new(program_id(), now);
+    runtime.add_account(
+        alice,
+        Account {
+            lamports: sol_to_lamports(100.0),
+            ..Account::default()
+        },
+    );
+
+    (runtime, alice, now)
+}
+
+#[test]
+fn test_create_batch_native() {
+    let (mut runtime, alice, now) = setup();
+    let program_id = program_id();
+    let bob = Pubkey::new_unique();
+    let carol = Pubkey::new_unique();
+
+    // Alice can't fund both streams, so neither is created.
+    assert_eq!(
+        runtime.process(&create_batch_native(
+            &program_id,
+            &alice,
+            vec![
+                (bob, params(now, sol_to_lamports(50.0))),
+                (carol, params(now, sol_to_lamports(50.0))),
+            ],
+            0,
+        )),
+        Err(ProgramError::InsufficientFunds)
+    );
+    assert_eq!(runtime.lamports(&alice), sol_to_lamports(100.0));
+
+    runtime
+        .process(&create_batch_native(
+            &program_id,
+            &alice,
+            vec![
+                (bob, params(now, sol_to_lamports(30.0))),
+                (carol, params(now, sol_to_lamports(20.0))),
+            ],
+            0,
+        ))
+        .unwrap();
+
+    let rent = Rent::default().minimum_balance(StreamFlow::LEN);
+    let fee = 2 * LAMPORTS_PER_SIGNATURE;
+    for (recipient, amount) in [(bob, 30.0), (carol, 20.0)] {
+        let (pda, bump) = find_stream_address(&program_id, &alice, &recipient, 0);
+        let acc = runtime.account(&pda).unwrap();
+        assert_eq!(acc.owner, program_id);
+        assert_eq!(acc.lamports, sol_to_lamports(amount) + rent - fee);
+        let sf = StreamFlow::unpack(&acc.data).unwrap();
+        assert_eq!(sf.amount, sol_to_lamports(amount));
+        assert_eq!(sf.recipient, recipient.to_bytes());
+        assert_eq!(sf.withdrawn, fee);
+        assert_eq!(sf.bump, bump);
+
+        // Every recipient can pay for their first withdrawal.
+        assert_eq!(runtime.lamports(&recipient), fee);
+    }
+    assert_eq!(runtime.lamports(&alice), sol_to_lamports(50.0) - 2 * rent);
+}
+
+#[test]
+fn test_create_batch_native_duplicate_recipient() {
+    let (mut runtime, alice, now) = setup();
+    let program_id = program_id();
+    let bob = Pubkey::new_unique();
+
+    assert_eq!(
+        runtime.process(&create_batch_native(
+            &program_id,
+            &alice,
+            vec![
+                (bob, params(now, sol_to_lamports(30.0))),
+                (bob, params(now, sol_to_lamports(20.0))),
+            ],
+            0,
+        )),
+        Err(StreamFlowError::DuplicateRecipient.into())
+    );
+
+    let (pda, _) = find_stream_address(&program_id, &alice, &bob, 0);
+    assert!(runtime.account(&pda).is_none());
+    assert_eq!(runtime.lamports(&alice), sol_to_lamports(100.0));
+}
+
+#[test]
+fn test_create_batch_native_size() {
+    let (mut runtime, alice, now) = setup();
+    let program_id = program_id();
+    let recipients: Vec<_> = (0..=MAX_BATCH_SIZE).map(|_| Pubkey::new_unique()).collect();
+
+    // One stream more than a batch may hold isn't even decoded.
+    assert_eq!(
+        runtime.process(&create_batch_native(
+            &program_id,
+            &alice,
+            recipients
+                .iter()
+                .map(|bob| (*bob, params(now, sol_to_lamports(1.0))))
+                .collect(),
+            0,
+        )),
+        Err(ProgramError::InvalidInstructionData)
+    );
+
+    runtime
+        .process(&create_batch_native(
+            &program_id,
+            &alice,
+            recipients[..MAX_BATCH_SIZE]
+                .iter()
+                .map(|bob| (*bob, params(now, sol_to_lamports(1.0))))
+                .collect(),
+            0,
+        ))
+        .unwrap();
+    for bob in &recipients[..MAX_BATCH_SIZE] {
+        let (pda, _) = find_stream_address(&program_id, &alice, bob, 0);
+        assert!(runtime.account(&pda).is_some());
+    }
+}
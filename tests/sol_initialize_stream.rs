@@ -14,70 +14,98 @@
 //
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
-use std::{str::FromStr, time::SystemTime};
+mod common;
+
+use std::time::SystemTime;
 
 use solana_program::{
     instruction::{AccountMeta, Instruction},
     native_token::sol_to_lamports,
-    pubkey::Pubkey,
-};
-use solana_program_test::*;
-use solana_sdk::{
-    account::Account, signature::Signer, signer::keypair::Keypair, system_program,
-    transaction::Transaction,
+    program_error::ProgramError,
+    program_pack::Pack,
+    rent::Rent,
 };
+use solana_sdk::{account::Account, signature::Signer, signer::keypair::Keypair, system_program};
 
-use streamflow::process_instruction;
-use streamflow::utils::{any_as_u8_slice, StreamFlow};
+use streamflow::error::StreamFlowError;
+use streamflow::instruction::{
+    cancel_native, initialize_native, withdraw_native, StreamFlowInstruction, StreamParams,
+};
+use streamflow::state::{StreamFlow, STREAM_VERSION};
+use streamflow::utils::{find_stream_address, PeriodUnit};
 
-#[repr(packed(1))]
-struct StFl {
-    instruction: u8,
-    start_time: u64,
-    end_time: u64,
-    amount: u64,
-}
+use common::{program_id, Runtime, LAMPORTS_PER_SIGNATURE};
 
-#[tokio::test]
-async fn test_initialize_stream() {
-    let program_id = Pubkey::from_str(&"streamf1ow111111111111111111111111111111111").unwrap();
+#[test]
+fn test_initialize_stream() {
+    let program_id = program_id();
 
     let alice = Keypair::new();
     let bob = Keypair::new();
-    let pda = Keypair::new();
-
-    let mut program_test =
-        ProgramTest::new("streamflow", program_id, processor!(process_instruction));
+    let (pda, bump) = find_stream_address(&program_id, &alice.pubkey(), &bob.pubkey(), 0);
 
     let now = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap()
         .as_secs();
 
-    let sf = StFl {
-        instruction: 0,
-        start_time: now as u64 + 10,
-        end_time: now as u64 + 20,
-        amount: sol_to_lamports(90.0),
+    let mut runtime = Runtime::new(program_id, now);
+
+    let sf = StreamFlowInstruction::InitializeNative {
+        params: StreamParams {
+            start_time: now + 10,
+            end_time: now + 20,
+            amount: sol_to_lamports(90.0),
+            cliff_time: 0,
+            cliff_amount: 0,
+            period: 0,
+            amount_per_period: 0,
+            period_unit: PeriodUnit::Seconds,
+            breakpoints: vec![],
+            rate: 0,
+            transfer_by_sender_only: false,
+            cancelable_by_sender: true,
+            cancelable_by_recipient: false,
+            rent_destination: None,
+            crank_fee_bps: 0,
+        },
+        nonce: 0,
     };
 
-    println!("instruction: {}", { sf.instruction });
-    println!("start_time: {}", { sf.start_time });
-    println!("end_time: {}", { sf.end_time });
-    println!("amount: {}", { sf.amount });
+    println!("instruction: {:?}", sf);
 
+    let fee = 2 * LAMPORTS_PER_SIGNATURE;
     let dat = StreamFlow {
-        start_time: now as u64 + 10,
-        end_time: now as u64 + 20,
+        version: STREAM_VERSION,
+        start_time: now + 10,
+        end_time: now + 20,
         amount: sol_to_lamports(90.0),
-        withdrawn: 0,
+        withdrawn: fee,
         sender: alice.pubkey().to_bytes(),
         recipient: bob.pubkey().to_bytes(),
         mint: bob.pubkey().to_bytes(),   // placeholder
         escrow: bob.pubkey().to_bytes(), // placeholder
+        nonce: 0,
+        bump,
+        escrow_bump: 0,
+        cliff_time: 0,
+        cliff_amount: 0,
+        period: 0,
+        amount_per_period: 0,
+        period_unit: PeriodUnit::Seconds,
+        schedule: [0; 32],
+        rate: 0,
+        recipient_tokens: [0; 32],
+        transfer_by_sender_only: false,
+        cancelable_by_sender: true,
+        cancelable_by_recipient: false,
+        paused_at: 0,
+        paused_time: 0,
+        rent_destination: alice.pubkey().to_bytes(),
+        crank_fee_bps: 0,
     };
 
-    program_test.add_account(
+    runtime.add_account(
         alice.pubkey(),
         Account {
             lamports: sol_to_lamports(1000.0),
@@ -85,38 +113,125 @@ async fn test_initialize_stream() {
         },
     );
 
-    program_test.add_account(
-        pda.pubkey(),
+    let instruction = Instruction::new_with_bytes(
+        program_id,
+        &sf.pack(),
+        vec![
+            AccountMeta::new(alice.pubkey(), true),
+            AccountMeta::new(bob.pubkey(), false),
+            AccountMeta::new(pda, false),
+            AccountMeta::new(system_program::ID, false),
+        ],
+    );
+
+    runtime.process(&instruction).unwrap();
+
+    let rent = Rent::default().minimum_balance(StreamFlow::LEN);
+    let stream = runtime.account(&pda).unwrap();
+    assert_eq!(stream.owner, program_id);
+    assert_eq!(stream.lamports, sol_to_lamports(90.0) + rent - fee);
+    assert_eq!(StreamFlow::unpack(&stream.data).unwrap(), dat);
+    assert_eq!(runtime.lamports(&bob.pubkey()), fee);
+    assert_eq!(
+        runtime.lamports(&alice.pubkey()),
+        sol_to_lamports(910.0) - rent
+    );
+
+    // The stream account can't be initialized twice.
+    assert_eq!(
+        runtime.process(&instruction),
+        Err(ProgramError::AccountAlreadyInitialized)
+    );
+}
+
+#[test]
+fn test_cancel_and_withdraw_before_start() {
+    let program_id = program_id();
+
+    let alice = Keypair::new();
+    let bob = Keypair::new();
+    let (pda, _) = find_stream_address(&program_id, &alice.pubkey(), &bob.pubkey(), 0);
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let mut runtime = Runtime::new(program_id, now);
+    runtime.add_account(
+        alice.pubkey(),
         Account {
-            lamports: 0,
-            data: unsafe { any_as_u8_slice(&dat).to_vec() },
+            lamports: sol_to_lamports(1000.0),
             ..Account::default()
         },
     );
 
-    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
-
-    let mut transaction = Transaction::new_with_payer(
-        &[Instruction::new_with_bytes(
-            program_id,
-            unsafe { any_as_u8_slice(&sf) },
-            vec![
-                AccountMeta::new(alice.pubkey(), true),
-                AccountMeta::new(bob.pubkey(), false),
-                AccountMeta::new(pda.pubkey(), true),
-                AccountMeta::new(bob.pubkey(), false), // placeholder
-                AccountMeta::new(system_program::ID, false),
-            ],
-        )],
-        Some(&payer.pubkey()),
+    let params = StreamParams {
+        start_time: now + 10,
+        end_time: now + 20,
+        amount: sol_to_lamports(90.0),
+        cliff_time: 0,
+        cliff_amount: 0,
+        period: 0,
+        amount_per_period: 0,
+        period_unit: PeriodUnit::Seconds,
+        breakpoints: vec![],
+        rate: 0,
+        transfer_by_sender_only: false,
+        cancelable_by_sender: true,
+        cancelable_by_recipient: false,
+        rent_destination: None,
+        crank_fee_bps: 0,
+    };
+    runtime
+        .process(&initialize_native(
+            &program_id,
+            &alice.pubkey(),
+            &bob.pubkey(),
+            params,
+            0,
+        ))
+        .unwrap();
+
+    // The fee Bob got at creation counts as withdrawn, which is more than
+    // has unlocked before the start, so there's nothing to withdraw yet.
+    let fee = 2 * LAMPORTS_PER_SIGNATURE;
+    runtime
+        .process(&withdraw_native(
+            &program_id,
+            &bob.pubkey(),
+            &pda,
+            &alice.pubkey(),
+            None,
+            0,
+        ))
+        .unwrap();
+    assert_eq!(runtime.lamports(&bob.pubkey()), fee);
+    assert_eq!(
+        runtime.process(&withdraw_native(
+            &program_id,
+            &bob.pubkey(),
+            &pda,
+            &alice.pubkey(),
+            None,
+            1,
+        )),
+        Err(StreamFlowError::AmountExceedsAvailable.into())
     );
 
-    transaction.sign(&[&payer, &alice, &pda], recent_blockhash);
-
-    match banks_client.process_transaction(transaction).await {
-        Ok(()) => (),
-        Err(e) => panic!("{}", e),
-    }
-
-    // TODO: Asserts
+    // Alice can still cancel, and gets back everything but the fee.
+    runtime
+        .process(&cancel_native(
+            &program_id,
+            &alice.pubkey(),
+            &bob.pubkey(),
+            &pda,
+        ))
+        .unwrap();
+    assert!(runtime.account(&pda).is_none());
+    assert_eq!(runtime.lamports(&bob.pubkey()), fee);
+    assert_eq!(
+        runtime.lamports(&alice.pubkey()),
+        sol_to_lamports(1000.0) - fee
+    );
 }
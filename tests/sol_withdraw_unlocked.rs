@@ -14,36 +14,41 @@
 //
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
+mod common;
+
 use std::{str::FromStr, time::SystemTime};
 
 use solana_program::{
-    instruction::{AccountMeta, Instruction},
+    instruction::{AccountMeta, Instruction, InstructionError},
     native_token::sol_to_lamports,
+    program_pack::Pack,
     pubkey::Pubkey,
 };
 use solana_program_test::*;
 use solana_sdk::{
-    account::Account, signature::Signer, signer::keypair::Keypair, system_program,
-    transaction::Transaction,
+    account::Account,
+    signature::Signer,
+    signer::keypair::Keypair,
+    system_program,
+    transaction::{Transaction, TransactionError},
 };
 
+use streamflow::error::StreamFlowError;
+use streamflow::instruction::{withdraw_native, StreamFlowInstruction};
 use streamflow::process_instruction;
-use streamflow::utils::{any_as_u8_slice, StreamFlow};
+use streamflow::state::{StreamFlow, STREAM_VERSION};
+use streamflow::utils::{find_config_address, PeriodUnit};
 
-#[repr(packed(1))]
-struct StFl {
-    instruction: u8,
-    amount: u64,
-}
+use common::{config_account, pack_stream};
 
 #[tokio::test]
 async fn test_withdraw_unlocked() {
-    let program_id = Pubkey::from_str(&"streamf1ow111111111111111111111111111111111").unwrap();
+    let program_id = Pubkey::from_str("streamf1ow111111111111111111111111111111111").unwrap();
 
     let alice = Keypair::new();
     let bob = Keypair::new();
     let pda = Keypair::new();
-    let lld = Pubkey::from_str("DrFtxPb9F6SxpHHHFiEtSNXE3SZCUNLXMaHS6r8pkoz2").unwrap();
+    let lld = alice.pubkey();
 
     let mut program_test =
         ProgramTest::new("streamflow", program_id, processor!(process_instruction));
@@ -53,15 +58,12 @@ async fn test_withdraw_unlocked() {
         .unwrap()
         .as_secs();
 
-    let sf = StFl {
-        instruction: 1,
-        amount: 0,
-    };
+    let sf = StreamFlowInstruction::WithdrawNative { amount: 0 };
 
-    println!("instruction: {}", { sf.instruction });
-    println!("amount: {}", { sf.amount });
+    println!("instruction: {:?}", sf);
 
     let dat = StreamFlow {
+        version: STREAM_VERSION,
         start_time: now - 610,
         end_time: now - 10,
         amount: sol_to_lamports(90.0),
@@ -70,6 +72,24 @@ async fn test_withdraw_unlocked() {
         recipient: bob.pubkey().to_bytes(),
         mint: bob.pubkey().to_bytes(),   // placeholder
         escrow: bob.pubkey().to_bytes(), // placeholder
+        nonce: 0,
+        bump: 0,
+        escrow_bump: 0,
+        cliff_time: 0,
+        cliff_amount: 0,
+        period: 0,
+        amount_per_period: 0,
+        period_unit: PeriodUnit::Seconds,
+        schedule: [0; 32],
+        rate: 0,
+        recipient_tokens: [0; 32],
+        transfer_by_sender_only: false,
+        cancelable_by_sender: true,
+        cancelable_by_recipient: false,
+        paused_at: 0,
+        paused_time: 0,
+        rent_destination: alice.pubkey().to_bytes(),
+        crank_fee_bps: 0,
     };
 
     program_test.add_account(
@@ -77,21 +97,27 @@ async fn test_withdraw_unlocked() {
         Account {
             owner: program_id,
             lamports: sol_to_lamports(91.0),
-            data: unsafe { any_as_u8_slice(&dat).to_vec() },
+            data: pack_stream(&dat),
             ..Account::default()
         },
     );
 
+    program_test.add_account(
+        find_config_address(&program_id).0,
+        config_account(&program_id, &Pubkey::new_unique()),
+    );
+
     let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
 
     let mut transaction = Transaction::new_with_payer(
         &[Instruction::new_with_bytes(
             program_id,
-            unsafe { any_as_u8_slice(&sf) },
+            &sf.pack(),
             vec![
                 AccountMeta::new(bob.pubkey(), true),
                 AccountMeta::new(pda.pubkey(), false),
                 AccountMeta::new(lld, false),
+                AccountMeta::new_readonly(find_config_address(&program_id).0, false),
                 AccountMeta::new(system_program::ID, false),
             ],
         )],
@@ -105,5 +131,421 @@ async fn test_withdraw_unlocked() {
         Err(e) => panic!("{}", e),
     }
 
-    // TODO: Asserts
+    // Everything is withdrawn, so the stream is closed and Alice gets
+    // the rent back.
+    assert_eq!(
+        banks_client.get_balance(bob.pubkey()).await.unwrap(),
+        sol_to_lamports(90.0)
+    );
+    assert_eq!(
+        banks_client.get_balance(alice.pubkey()).await.unwrap(),
+        sol_to_lamports(1.0)
+    );
+    assert!(banks_client
+        .get_account(pda.pubkey())
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_withdraw_unlocked_open_ended() {
+    let program_id = Pubkey::from_str("streamf1ow111111111111111111111111111111111").unwrap();
+
+    let alice = Keypair::new();
+    let bob = Keypair::new();
+    let pda = Keypair::new();
+    let lld = alice.pubkey();
+
+    let mut program_test =
+        ProgramTest::new("streamflow", program_id, processor!(process_instruction));
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    // The rate would have unlocked more than the deposit by now, so the
+    // deposit is what Bob gets.
+    let deposit = sol_to_lamports(1.0);
+    let dat = StreamFlow {
+        version: STREAM_VERSION,
+        start_time: now - 610,
+        end_time: 0,
+        amount: deposit,
+        withdrawn: 0,
+        sender: alice.pubkey().to_bytes(),
+        recipient: bob.pubkey().to_bytes(),
+        mint: bob.pubkey().to_bytes(),   // placeholder
+        escrow: bob.pubkey().to_bytes(), // placeholder
+        nonce: 0,
+        bump: 0,
+        escrow_bump: 0,
+        cliff_time: 0,
+        cliff_amount: 0,
+        period: 0,
+        amount_per_period: 0,
+        period_unit: PeriodUnit::Seconds,
+        schedule: [0; 32],
+        rate: deposit / 100,
+        recipient_tokens: [0; 32],
+        transfer_by_sender_only: false,
+        cancelable_by_sender: true,
+        cancelable_by_recipient: false,
+        paused_at: 0,
+        paused_time: 0,
+        rent_destination: alice.pubkey().to_bytes(),
+        crank_fee_bps: 0,
+    };
+
+    program_test.add_account(
+        pda.pubkey(),
+        Account {
+            owner: program_id,
+            lamports: sol_to_lamports(2.0),
+            data: pack_stream(&dat),
+            ..Account::default()
+        },
+    );
+
+    program_test.add_account(
+        find_config_address(&program_id).0,
+        config_account(&program_id, &Pubkey::new_unique()),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[withdraw_native(
+            &program_id,
+            &bob.pubkey(),
+            &pda.pubkey(),
+            &lld,
+            None,
+            0,
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &bob], recent_blockhash);
+
+    match banks_client.process_transaction(transaction).await {
+        Ok(()) => (),
+        Err(e) => panic!("{}", e),
+    }
+
+    assert_eq!(
+        banks_client.get_balance(bob.pubkey()).await.unwrap(),
+        deposit
+    );
+    assert_eq!(
+        banks_client.get_balance(alice.pubkey()).await.unwrap(),
+        sol_to_lamports(2.0) - deposit
+    );
+    assert!(banks_client
+        .get_account(pda.pubkey())
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_withdraw_partial() {
+    let program_id = Pubkey::from_str("streamf1ow111111111111111111111111111111111").unwrap();
+
+    let alice = Keypair::new();
+    let bob = Keypair::new();
+    let pda = Keypair::new();
+    let carol = Keypair::new();
+    let broke = Keypair::new();
+    let huge = Keypair::new();
+
+    let mut program_test =
+        ProgramTest::new("streamflow", program_id, processor!(process_instruction));
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let dat = StreamFlow {
+        version: STREAM_VERSION,
+        start_time: now - 610,
+        end_time: now - 10,
+        amount: sol_to_lamports(90.0),
+        withdrawn: 0,
+        sender: alice.pubkey().to_bytes(),
+        recipient: bob.pubkey().to_bytes(),
+        mint: bob.pubkey().to_bytes(),   // placeholder
+        escrow: bob.pubkey().to_bytes(), // placeholder
+        nonce: 0,
+        bump: 0,
+        escrow_bump: 0,
+        cliff_time: 0,
+        cliff_amount: 0,
+        period: 0,
+        amount_per_period: 0,
+        period_unit: PeriodUnit::Seconds,
+        schedule: [0; 32],
+        rate: 0,
+        recipient_tokens: [0; 32],
+        transfer_by_sender_only: false,
+        cancelable_by_sender: true,
+        cancelable_by_recipient: false,
+        paused_at: 0,
+        paused_time: 0,
+        rent_destination: alice.pubkey().to_bytes(),
+        crank_fee_bps: 0,
+    };
+
+    // The second stream holds less than it claims to, and the third
+    // claims more than any account could hold.
+    let huge_dat = StreamFlow {
+        amount: u64::MAX,
+        ..dat.clone()
+    };
+    for (account, lamports, sf) in [
+        (&pda, 91.0, &dat),
+        (&broke, 50.0, &dat),
+        (&huge, 91.0, &huge_dat),
+    ] {
+        program_test.add_account(
+            account.pubkey(),
+            Account {
+                owner: program_id,
+                lamports: sol_to_lamports(lamports),
+                data: pack_stream(sf),
+                ..Account::default()
+            },
+        );
+    }
+
+    program_test.add_account(
+        find_config_address(&program_id).0,
+        config_account(&program_id, &Pubkey::new_unique()),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // A destination given without filling the schedule slot first must not
+    // be skipped as the placeholder.
+    let mut ix = withdraw_native(
+        &program_id,
+        &bob.pubkey(),
+        &pda.pubkey(),
+        &alice.pubkey(),
+        Some(&carol.pubkey()),
+        sol_to_lamports(30.0),
+    );
+    ix.accounts.remove(4);
+    let mut transaction = Transaction::new_with_payer(&[ix], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &bob], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StreamFlowError::InvalidScheduleAccount as u32)
+        )
+    );
+
+    // The program id is accepted as the placeholder.
+    let mut ix = withdraw_native(
+        &program_id,
+        &bob.pubkey(),
+        &pda.pubkey(),
+        &alice.pubkey(),
+        Some(&carol.pubkey()),
+        sol_to_lamports(30.0),
+    );
+    ix.accounts[4] = AccountMeta::new_readonly(program_id, false);
+    let mut transaction = Transaction::new_with_payer(&[ix], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &bob], recent_blockhash);
+    match banks_client.process_transaction(transaction).await {
+        Ok(()) => (),
+        Err(e) => panic!("{}", e),
+    }
+
+    // Bob had the first part sent to Carol, and only what was
+    // transferred counts as withdrawn.
+    assert_eq!(
+        banks_client.get_balance(carol.pubkey()).await.unwrap(),
+        sol_to_lamports(30.0)
+    );
+    let pda_acc = banks_client
+        .get_account(pda.pubkey())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        StreamFlow::unpack(&pda_acc.data).unwrap().withdrawn,
+        sol_to_lamports(30.0)
+    );
+    assert_eq!(pda_acc.lamports, sol_to_lamports(61.0));
+
+    // The rest of the unlocked funds can still be withdrawn.
+    let mut transaction = Transaction::new_with_payer(
+        &[withdraw_native(
+            &program_id,
+            &bob.pubkey(),
+            &pda.pubkey(),
+            &alice.pubkey(),
+            None,
+            0,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &bob], recent_blockhash);
+    match banks_client.process_transaction(transaction).await {
+        Ok(()) => (),
+        Err(e) => panic!("{}", e),
+    }
+
+    assert_eq!(
+        banks_client.get_balance(bob.pubkey()).await.unwrap(),
+        sol_to_lamports(60.0)
+    );
+    assert!(banks_client
+        .get_account(pda.pubkey())
+        .await
+        .unwrap()
+        .is_none());
+
+    let failures = [
+        (&broke, StreamFlowError::InvariantViolation),
+        (&huge, StreamFlowError::MathOverflow),
+    ];
+    for (stream, error) in failures {
+        let mut transaction = Transaction::new_with_payer(
+            &[withdraw_native(
+                &program_id,
+                &bob.pubkey(),
+                &stream.pubkey(),
+                &alice.pubkey(),
+                None,
+                0,
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &bob], recent_blockhash);
+        assert_eq!(
+            banks_client
+                .process_transaction(transaction)
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_withdraw_without_config() {
+    let program_id = Pubkey::from_str("streamf1ow111111111111111111111111111111111").unwrap();
+
+    let alice = Keypair::new();
+    let bob = Keypair::new();
+    let pda = Keypair::new();
+    let collector = Keypair::new();
+
+    let mut program_test =
+        ProgramTest::new("streamflow", program_id, processor!(process_instruction));
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    // The stream leaves its rent to the program's collector, and the
+    // program has no config yet.
+    let dat = StreamFlow {
+        version: STREAM_VERSION,
+        start_time: now - 610,
+        end_time: now - 10,
+        amount: sol_to_lamports(90.0),
+        withdrawn: 0,
+        sender: alice.pubkey().to_bytes(),
+        recipient: bob.pubkey().to_bytes(),
+        mint: bob.pubkey().to_bytes(),   // placeholder
+        escrow: bob.pubkey().to_bytes(), // placeholder
+        nonce: 0,
+        bump: 0,
+        escrow_bump: 0,
+        cliff_time: 0,
+        cliff_amount: 0,
+        period: 0,
+        amount_per_period: 0,
+        period_unit: PeriodUnit::Seconds,
+        schedule: [0; 32],
+        rate: 0,
+        recipient_tokens: [0; 32],
+        transfer_by_sender_only: false,
+        cancelable_by_sender: true,
+        cancelable_by_recipient: false,
+        paused_at: 0,
+        paused_time: 0,
+        rent_destination: [0; 32],
+        crank_fee_bps: 0,
+    };
+    program_test.add_account(
+        pda.pubkey(),
+        Account {
+            owner: program_id,
+            lamports: sol_to_lamports(91.0),
+            data: pack_stream(&dat),
+            ..Account::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // Withdrawals that leave the stream open don't need the config.
+    let mut transaction = Transaction::new_with_payer(
+        &[withdraw_native(
+            &program_id,
+            &bob.pubkey(),
+            &pda.pubkey(),
+            &collector.pubkey(),
+            None,
+            sol_to_lamports(30.0),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &bob], recent_blockhash);
+    match banks_client.process_transaction(transaction).await {
+        Ok(()) => (),
+        Err(e) => panic!("{}", e),
+    }
+    assert_eq!(
+        banks_client.get_balance(bob.pubkey()).await.unwrap(),
+        sol_to_lamports(30.0)
+    );
+
+    // Closing it has to find out where the rent goes.
+    let mut transaction = Transaction::new_with_payer(
+        &[withdraw_native(
+            &program_id,
+            &bob.pubkey(),
+            &pda.pubkey(),
+            &collector.pubkey(),
+            None,
+            0,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &bob], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StreamFlowError::InvalidConfigAccount as u32)
+        )
+    );
 }
@@ -0,0 +1,670 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+use solana_program::{
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack},
+};
+
+use streamflow::error::StreamFlowError;
+use streamflow::state::{
+    Config, StreamFlow, StreamSchedule, CONFIG_MAGIC, CONFIG_VERSION, LEGACY_STREAM_LEN,
+    SCHEDULE_MAGIC, SCHEDULE_VERSION, STREAM_MAGIC, STREAM_VERSION,
+};
+use streamflow::utils::{Breakpoint, PeriodUnit, TopUpMode, MAX_BREAKPOINTS};
+
+fn stream(version: u8) -> StreamFlow {
+    let legacy = version == 0;
+    StreamFlow {
+        version,
+        start_time: 1,
+        end_time: 2,
+        amount: 3,
+        withdrawn: 4,
+        sender: [5; 32],
+        recipient: [6; 32],
+        mint: [7; 32],
+        escrow: [8; 32],
+        // Legacy accounts end after the escrow, everything below keeps the
+        // defaults they are read with.
+        nonce: if legacy { 0 } else { 9 },
+        bump: if legacy { 0 } else { 10 },
+        escrow_bump: if legacy { 0 } else { 11 },
+        cliff_time: if legacy { 0 } else { 12 },
+        cliff_amount: if legacy { 0 } else { 13 },
+        period: if legacy { 0 } else { 14 },
+        amount_per_period: if legacy { 0 } else { 15 },
+        period_unit: if legacy {
+            PeriodUnit::Seconds
+        } else {
+            PeriodUnit::Months
+        },
+        schedule: if legacy { [0; 32] } else { [16; 32] },
+        rate: if legacy { 0 } else { 17 },
+        recipient_tokens: if legacy { [0; 32] } else { [18; 32] },
+        transfer_by_sender_only: !legacy,
+        // Only the sender could cancel legacy streams.
+        cancelable_by_sender: legacy,
+        cancelable_by_recipient: !legacy,
+        paused_at: if legacy { 0 } else { 19 },
+        paused_time: if legacy { 0 } else { 20 },
+        rent_destination: if legacy { [0; 32] } else { [21; 32] },
+        crank_fee_bps: if legacy { 0 } else { 22 },
+    }
+}
+
+#[test]
+fn test_roundtrip_current_version() {
+    let sf = stream(STREAM_VERSION);
+    let mut data = vec![0; StreamFlow::LEN];
+    StreamFlow::pack(sf.clone(), &mut data).unwrap();
+
+    assert_eq!(data[..STREAM_MAGIC.len()], STREAM_MAGIC);
+    assert_eq!(data[STREAM_MAGIC.len()], STREAM_VERSION);
+    assert_eq!(StreamFlow::unpack(&data).unwrap(), sf);
+}
+
+#[test]
+fn test_wire_format() {
+    let mut data = vec![0; StreamFlow::LEN];
+    stream(STREAM_VERSION).pack_into_slice(&mut data);
+
+    // The layout is fixed by the byte offsets below, not by how the
+    // compiler happens to lay out the struct in memory.
+    assert_eq!(data[9..17], 1u64.to_le_bytes());
+    assert_eq!(data[17..25], 2u64.to_le_bytes());
+    assert_eq!(data[25..33], 3u64.to_le_bytes());
+    assert_eq!(data[33..41], 4u64.to_le_bytes());
+    assert_eq!(data[41..73], [5; 32]);
+    assert_eq!(data[73..105], [6; 32]);
+    assert_eq!(data[105..137], [7; 32]);
+    assert_eq!(data[137..169], [8; 32]);
+    assert_eq!(data[169..177], 9u64.to_le_bytes());
+    assert_eq!(data[177..179], [10, 11]);
+    assert_eq!(data[179..187], 12u64.to_le_bytes());
+    assert_eq!(data[187..195], 13u64.to_le_bytes());
+    assert_eq!(data[195..203], 14u64.to_le_bytes());
+    assert_eq!(data[203..211], 15u64.to_le_bytes());
+    assert_eq!(data[211], PeriodUnit::Months as u8);
+    assert_eq!(data[212..244], [16; 32]);
+    assert_eq!(data[244..252], 17u64.to_le_bytes());
+    assert_eq!(data[252..284], [18; 32]);
+    assert_eq!(data[284], 1);
+    assert_eq!(data[285..287], [0, 1]);
+    assert_eq!(data[287..295], 19u64.to_le_bytes());
+    assert_eq!(data[295..303], 20u64.to_le_bytes());
+    assert_eq!(data[303..335], [21; 32]);
+    assert_eq!(data[335..], 22u16.to_le_bytes());
+}
+
+#[test]
+fn test_read_legacy_layout() {
+    // Version 0 accounts hold the original fields without any header.
+    let mut data = vec![];
+    data.extend_from_slice(&1u64.to_le_bytes());
+    data.extend_from_slice(&2u64.to_le_bytes());
+    data.extend_from_slice(&3u64.to_le_bytes());
+    data.extend_from_slice(&4u64.to_le_bytes());
+    data.extend_from_slice(&[5; 32]);
+    data.extend_from_slice(&[6; 32]);
+    data.extend_from_slice(&[7; 32]);
+    data.extend_from_slice(&[8; 32]);
+    assert_eq!(data.len(), LEGACY_STREAM_LEN);
+    assert_eq!(data.len(), 160);
+
+    let sf = StreamFlow::unpack_from_slice(&data).unwrap();
+    assert_eq!(sf, stream(0));
+
+    // Writing it back keeps the legacy layout.
+    let mut out = vec![0; data.len()];
+    sf.pack_into_slice(&mut out);
+    assert_eq!(out, data);
+
+    // Shorter accounts aren't streams of any version.
+    assert_eq!(
+        StreamFlow::unpack_from_slice(&data[..LEGACY_STREAM_LEN - 1]),
+        Err(ProgramError::InvalidAccountData)
+    );
+}
+
+#[test]
+fn test_reject_header_with_legacy_version() {
+    // Legacy accounts never had a header, so one claiming version 0 is bogus.
+    let mut data = vec![0; StreamFlow::LEN];
+    stream(STREAM_VERSION).pack_into_slice(&mut data);
+    data[STREAM_MAGIC.len()] = 0;
+
+    assert_eq!(
+        StreamFlow::unpack(&data),
+        Err(ProgramError::InvalidAccountData)
+    );
+}
+
+#[test]
+fn test_reject_unknown_version() {
+    let mut data = vec![0; StreamFlow::LEN];
+    stream(STREAM_VERSION).pack_into_slice(&mut data);
+    data[STREAM_MAGIC.len()] = STREAM_VERSION + 1;
+
+    assert_eq!(
+        StreamFlow::unpack(&data),
+        Err(ProgramError::InvalidAccountData)
+    );
+}
+
+#[test]
+fn test_reject_unknown_period_unit() {
+    let mut data = vec![0; StreamFlow::LEN];
+    stream(STREAM_VERSION).pack_into_slice(&mut data);
+    data[211] = 255;
+
+    assert_eq!(
+        StreamFlow::unpack(&data),
+        Err(ProgramError::InvalidAccountData)
+    );
+}
+
+#[test]
+fn test_reject_invalid_flag() {
+    let mut data = vec![0; StreamFlow::LEN];
+    stream(STREAM_VERSION).pack_into_slice(&mut data);
+    data[286] = 2;
+
+    assert_eq!(
+        StreamFlow::unpack(&data),
+        Err(ProgramError::InvalidAccountData)
+    );
+}
+
+#[test]
+fn test_invalid_buffers() {
+    let sf = stream(STREAM_VERSION);
+    let mut data = vec![0; StreamFlow::LEN - 1];
+    assert_eq!(
+        StreamFlow::pack(sf.clone(), &mut data),
+        Err(ProgramError::InvalidAccountData)
+    );
+
+    let mut data = vec![0; StreamFlow::LEN];
+    sf.pack_into_slice(&mut data);
+    assert_eq!(
+        StreamFlow::unpack_from_slice(&data[..StreamFlow::LEN - 1]),
+        Err(ProgramError::InvalidAccountData)
+    );
+
+    // A zeroed account is not a stream.
+    let data = vec![0; StreamFlow::LEN];
+    assert!(!StreamFlow::unpack_unchecked(&data)
+        .unwrap()
+        .is_initialized());
+    assert_eq!(
+        StreamFlow::unpack(&data),
+        Err(ProgramError::UninitializedAccount)
+    );
+}
+
+fn schedule(len: usize) -> StreamSchedule {
+    let breakpoints = (0..len as u64)
+        .map(|i| Breakpoint {
+            timestamp: 100 + i,
+            cumulative: 10 * i,
+        })
+        .collect();
+    StreamSchedule {
+        version: SCHEDULE_VERSION,
+        stream: [1; 32],
+        breakpoints,
+    }
+}
+
+#[test]
+fn test_schedule_roundtrip() {
+    for len in [1, 3, MAX_BREAKPOINTS] {
+        let schedule = schedule(len);
+        let mut data = vec![0; StreamSchedule::LEN];
+        StreamSchedule::pack(schedule.clone(), &mut data).unwrap();
+
+        assert_eq!(data[..SCHEDULE_MAGIC.len()], SCHEDULE_MAGIC);
+        assert_eq!(data[8], SCHEDULE_VERSION);
+        assert_eq!(data[9..41], [1; 32]);
+        assert_eq!(data[41], len as u8);
+        assert_eq!(data[42..50], 100u64.to_le_bytes());
+        assert_eq!(StreamSchedule::unpack(&data).unwrap(), schedule);
+    }
+}
+
+#[test]
+fn test_schedule_invalid_buffers() {
+    let mut data = vec![0; StreamSchedule::LEN];
+    schedule(3).pack_into_slice(&mut data);
+
+    // Wrong discriminator, e.g. a stream account
+    let mut bad = data.clone();
+    bad[0] = 0;
+    assert_eq!(
+        StreamSchedule::unpack(&bad),
+        Err(ProgramError::InvalidAccountData)
+    );
+
+    // Unknown version
+    let mut bad = data.clone();
+    bad[8] = SCHEDULE_VERSION + 1;
+    assert_eq!(
+        StreamSchedule::unpack(&bad),
+        Err(ProgramError::InvalidAccountData)
+    );
+
+    // Too many breakpoints
+    let mut bad = data;
+    bad[41] = MAX_BREAKPOINTS as u8 + 1;
+    assert_eq!(
+        StreamSchedule::unpack(&bad),
+        Err(ProgramError::InvalidAccountData)
+    );
+
+    // A zeroed account is not a schedule.
+    assert_eq!(
+        StreamSchedule::unpack(&[0; StreamSchedule::LEN]),
+        Err(ProgramError::InvalidAccountData)
+    );
+}
+
+#[test]
+fn test_config_roundtrip() {
+    let config = Config {
+        version: CONFIG_VERSION,
+        admin: [1; 32],
+        collector: [2; 32],
+    };
+    let mut data = vec![0; Config::LEN];
+    Config::pack(config.clone(), &mut data).unwrap();
+
+    assert_eq!(data[..CONFIG_MAGIC.len()], CONFIG_MAGIC);
+    assert_eq!(data[8], CONFIG_VERSION);
+    assert_eq!(data[9..41], [1; 32]);
+    assert_eq!(data[41..], [2; 32]);
+    assert_eq!(Config::unpack(&data).unwrap(), config);
+
+    // Wrong discriminator, e.g. a schedule account
+    let mut bad = data.clone();
+    bad[0] = 0;
+    assert_eq!(Config::unpack(&bad), Err(ProgramError::InvalidAccountData));
+
+    // Unknown version
+    let mut bad = data;
+    bad[8] = CONFIG_VERSION + 1;
+    assert_eq!(Config::unpack(&bad), Err(ProgramError::InvalidAccountData));
+}
+
+/// 1000 unlocking linearly from 100 to 200.
+fn linear() -> StreamFlow {
+    StreamFlow {
+        version: STREAM_VERSION,
+        start_time: 100,
+        end_time: 200,
+        amount: 1000,
+        withdrawn: 0,
+        cliff_time: 0,
+        cliff_amount: 0,
+        period: 0,
+        amount_per_period: 0,
+        period_unit: PeriodUnit::Seconds,
+        schedule: [0; 32],
+        rate: 0,
+        cancelable_by_sender: true,
+        cancelable_by_recipient: false,
+        paused_at: 0,
+        paused_time: 0,
+        ..stream(STREAM_VERSION)
+    }
+}
+
+#[test]
+fn test_top_up_before_start() {
+    let mut sf = linear();
+    sf.top_up(50, 500, TopUpMode::Extend).unwrap();
+    assert_eq!((sf.amount, sf.end_time, sf.cliff_time), (1500, 250, 0));
+    assert_eq!(sf.unlocked(150, None), 500);
+
+    let mut sf = linear();
+    sf.top_up(50, 500, TopUpMode::Increase).unwrap();
+    assert_eq!((sf.amount, sf.end_time, sf.cliff_time), (1500, 200, 0));
+    assert_eq!(sf.unlocked(150, None), 750);
+}
+
+#[test]
+fn test_top_up_keeps_unlocked() {
+    // Halfway through, 500 is unlocked and 500 left for 50 seconds.
+    let mut sf = linear();
+    sf.top_up(150, 500, TopUpMode::Extend).unwrap();
+    assert_eq!((sf.amount, sf.end_time), (1500, 250));
+    assert_eq!((sf.cliff_time, sf.cliff_amount), (150, 500));
+    assert_eq!(sf.unlocked(150, None), 500);
+    assert_eq!(sf.unlocked(200, None), 1000);
+    assert_eq!(sf.unlocked(250, None), 1500);
+
+    let mut sf = linear();
+    sf.top_up(150, 500, TopUpMode::Increase).unwrap();
+    assert_eq!((sf.amount, sf.end_time), (1500, 200));
+    assert_eq!(sf.unlocked(150, None), 500);
+    assert_eq!(sf.unlocked(175, None), 1000);
+    assert_eq!(sf.unlocked(200, None), 1500);
+
+    // Unlocking only starts at a future cliff, which stays where it is.
+    let mut sf = StreamFlow {
+        cliff_time: 150,
+        cliff_amount: 200,
+        ..linear()
+    };
+    sf.top_up(120, 400, TopUpMode::Extend).unwrap();
+    assert_eq!((sf.amount, sf.end_time), (1400, 225));
+    assert_eq!((sf.cliff_time, sf.cliff_amount), (150, 200));
+    assert_eq!(sf.unlocked(149, None), 0);
+}
+
+#[test]
+fn test_top_up_open_ended() {
+    let mut sf = StreamFlow {
+        end_time: 0,
+        rate: 10,
+        ..linear()
+    };
+    sf.top_up(150, 500, TopUpMode::Extend).unwrap();
+    assert_eq!((sf.amount, sf.end_time), (1500, 0));
+    assert_eq!(sf.unlocked(200, None), 1000);
+    assert_eq!(sf.unlocked(300, None), 1500);
+
+    assert_eq!(
+        sf.top_up(150, 500, TopUpMode::Increase),
+        Err(StreamFlowError::InvalidSchedule.into())
+    );
+}
+
+#[test]
+fn test_top_up_exhausted() {
+    // Everything unlocked at 200, the new funds pick up at 300 at the
+    // same 10 per second instead of unlocking at once.
+    let mut sf = linear();
+    sf.top_up(300, 500, TopUpMode::Extend).unwrap();
+    assert_eq!((sf.amount, sf.end_time), (1500, 350));
+    assert_eq!((sf.cliff_time, sf.cliff_amount), (300, 1000));
+    assert_eq!(sf.unlocked(300, None), 1000);
+    assert_eq!(sf.unlocked(325, None), 1250);
+    assert_eq!(sf.unlocked(350, None), 1500);
+
+    // Open-ended streams are moved to have run dry just now.
+    let mut sf = StreamFlow {
+        end_time: 0,
+        rate: 10,
+        ..linear()
+    };
+    sf.top_up(300, 500, TopUpMode::Extend).unwrap();
+    assert_eq!((sf.amount, sf.start_time), (1500, 200));
+    assert_eq!(sf.unlocked(300, None), 1000);
+    assert_eq!(sf.unlocked(325, None), 1250);
+    assert_eq!(sf.unlocked(350, None), 1500);
+
+    // They run dry in whole seconds, so at most what unlocks in a second
+    // is unlocked at once.
+    let mut sf = StreamFlow {
+        end_time: 0,
+        rate: 300,
+        ..linear()
+    };
+    sf.top_up(300, 500, TopUpMode::Extend).unwrap();
+    assert_eq!(sf.start_time, 296);
+    assert_eq!(sf.unlocked(300, None), 1200);
+    assert_eq!(sf.unlocked(301, None), 1500);
+
+    // Time spent paused doesn't count.
+    let mut sf = StreamFlow {
+        paused_time: 50,
+        ..linear()
+    };
+    sf.top_up(350, 500, TopUpMode::Extend).unwrap();
+    assert_eq!((sf.cliff_time, sf.end_time), (300, 350));
+    assert_eq!(sf.unlocked(350, None), 1000);
+    assert_eq!(sf.unlocked(400, None), 1500);
+}
+
+#[test]
+fn test_top_up_rejected() {
+    let finished = linear();
+    let periodic = StreamFlow {
+        period: 10,
+        amount_per_period: 100,
+        ..linear()
+    };
+    let scheduled = StreamFlow {
+        schedule: [1; 32],
+        ..linear()
+    };
+    let all_at_cliff = StreamFlow {
+        cliff_time: 150,
+        cliff_amount: 1000,
+        ..linear()
+    };
+    let overflow = StreamFlow {
+        amount: u64::MAX,
+        ..linear()
+    };
+
+    let cases = vec![
+        (
+            finished,
+            200,
+            TopUpMode::Increase,
+            StreamFlowError::StreamClosed,
+        ),
+        (
+            periodic,
+            150,
+            TopUpMode::Extend,
+            StreamFlowError::InvalidSchedule,
+        ),
+        (
+            scheduled,
+            150,
+            TopUpMode::Extend,
+            StreamFlowError::InvalidSchedule,
+        ),
+        (
+            all_at_cliff,
+            120,
+            TopUpMode::Extend,
+            StreamFlowError::InvalidSchedule,
+        ),
+        (
+            overflow,
+            150,
+            TopUpMode::Increase,
+            StreamFlowError::MathOverflow,
+        ),
+    ];
+
+    for (mut sf, now, mode, error) in cases {
+        let before = sf.clone();
+        assert_eq!(sf.top_up(now, 1, mode), Err(error.into()));
+        assert_eq!(sf, before);
+    }
+
+    // Extending a stream ending near the end of time overflows its end.
+    let mut sf = StreamFlow {
+        end_time: u64::MAX - 10,
+        ..linear()
+    };
+    assert_eq!(
+        sf.top_up(50, 1000, TopUpMode::Extend),
+        Err(StreamFlowError::MathOverflow.into())
+    );
+
+    // Restarting from now needs a cliff, which legacy accounts lack.
+    let mut sf = StreamFlow {
+        version: 0,
+        ..linear()
+    };
+    assert_eq!(
+        sf.top_up(150, 1, TopUpMode::Extend),
+        Err(ProgramError::InvalidAccountData)
+    );
+}
+
+#[test]
+fn test_check_cancel() {
+    let sf = linear();
+    assert_eq!(sf.check_cancel(true, false), Ok(()));
+    assert_eq!(
+        sf.check_cancel(false, true),
+        Err(StreamFlowError::CancelNotAllowed.into())
+    );
+    assert_eq!(
+        sf.check_cancel(false, false),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+
+    let sf = StreamFlow {
+        cancelable_by_sender: false,
+        cancelable_by_recipient: true,
+        ..linear()
+    };
+    assert_eq!(
+        sf.check_cancel(true, false),
+        Err(StreamFlowError::CancelNotAllowed.into())
+    );
+    assert_eq!(sf.check_cancel(false, true), Ok(()));
+    assert_eq!(sf.check_cancel(true, true), Ok(()));
+
+    let sf = StreamFlow {
+        cancelable_by_sender: false,
+        ..linear()
+    };
+    assert_eq!(
+        sf.check_cancel(true, true),
+        Err(StreamFlowError::CancelNotAllowed.into())
+    );
+}
+
+#[test]
+fn test_remaining_and_available() {
+    let sf = StreamFlow {
+        withdrawn: 400,
+        ..linear()
+    };
+    assert_eq!(sf.remaining(), Ok(600));
+    assert_eq!(sf.available(150, None), Ok(100));
+    // Withdrawn ahead of the schedule, like the fee paid at creation
+    assert_eq!(sf.available(120, None), Ok(0));
+    assert_eq!(sf.available(0, None), Ok(0));
+
+    // More withdrawn than the stream ever held
+    let sf = StreamFlow {
+        withdrawn: 1001,
+        ..linear()
+    };
+    assert_eq!(
+        sf.remaining(),
+        Err(StreamFlowError::InvariantViolation.into())
+    );
+    assert_eq!(
+        sf.available(2000, None),
+        Err(StreamFlowError::InvariantViolation.into())
+    );
+}
+
+#[test]
+fn test_crank_fee() {
+    let sf = StreamFlow {
+        crank_fee_bps: 25,
+        ..linear()
+    };
+    assert_eq!(sf.crank_fee(10_000), 25);
+    // Rounded down, in favour of the recipient.
+    assert_eq!(sf.crank_fee(399), 0);
+    assert_eq!(sf.crank_fee(u64::MAX), u64::MAX / 400);
+    let sf = StreamFlow {
+        crank_fee_bps: 0,
+        ..linear()
+    };
+    assert_eq!(sf.crank_fee(10_000), 0);
+}
+
+#[test]
+fn test_pause_resume() {
+    let mut sf = linear();
+    sf.pause(120).unwrap();
+    assert!(sf.is_paused());
+    assert_eq!(sf.pause(130), Err(StreamFlowError::AlreadyPaused.into()));
+
+    // Nothing more unlocks while paused.
+    assert_eq!(sf.unlocked(120, None), 200);
+    assert_eq!(sf.unlocked(500, None), 200);
+
+    // The rest of the stream is 30 seconds late after resuming.
+    sf.resume(150).unwrap();
+    assert!(!sf.is_paused());
+    assert_eq!(sf.paused_time, 30);
+    assert_eq!(sf.resume(160), Err(StreamFlowError::NotPaused.into()));
+    assert_eq!(sf.unlocked(150, None), 200);
+    assert_eq!(sf.unlocked(180, None), 500);
+    assert_eq!(sf.unlocked(229, None), 990);
+    assert_eq!(sf.unlocked(230, None), 1000);
+
+    // Pauses add up.
+    sf.pause(200).unwrap();
+    sf.resume(210).unwrap();
+    assert_eq!(sf.paused_time, 40);
+    assert_eq!(sf.unlocked(239, None), 990);
+    assert_eq!(sf.unlocked(240, None), 1000);
+
+    // Senders can't hold back what they couldn't cancel.
+    let mut sf = StreamFlow {
+        cancelable_by_sender: false,
+        cancelable_by_recipient: true,
+        ..linear()
+    };
+    assert_eq!(sf.pause(120), Err(StreamFlowError::CancelNotAllowed.into()));
+    assert!(!sf.is_paused());
+
+    // Legacy accounts have no room for the pause.
+    let mut sf = StreamFlow {
+        version: 0,
+        ..linear()
+    };
+    assert_eq!(sf.pause(120), Err(ProgramError::InvalidAccountData));
+
+    // Pauses adding up past the end of time overflow.
+    let mut sf = StreamFlow {
+        paused_at: 1,
+        paused_time: u64::MAX - 10,
+        ..linear()
+    };
+    assert_eq!(sf.resume(20), Err(StreamFlowError::MathOverflow.into()));
+    assert!(sf.is_paused());
+}
+
+#[test]
+fn test_top_up_after_pause() {
+    // Paused for 50 seconds, so at 200 the stream is where it was at 150.
+    let mut sf = StreamFlow {
+        paused_time: 50,
+        ..linear()
+    };
+    sf.top_up(200, 500, TopUpMode::Extend).unwrap();
+    assert_eq!((sf.cliff_time, sf.cliff_amount), (150, 500));
+    assert_eq!(sf.end_time, 250);
+    assert_eq!(sf.unlocked(200, None), 500);
+    assert_eq!(sf.unlocked(300, None), 1500);
+}
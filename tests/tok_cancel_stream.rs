@@ -0,0 +1,197 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+mod common;
+
+use std::{str::FromStr, time::SystemTime};
+
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    native_token::sol_to_lamports,
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account, signature::Signer, signer::keypair::Keypair, transaction::Transaction,
+};
+use spl_token::state::{Account as TokenAccount, Mint};
+
+use streamflow::instruction::StreamFlowInstruction;
+use streamflow::process_instruction;
+use streamflow::state::{StreamFlow, STREAM_VERSION};
+
+use common::{pack_stream, token_account};
+use streamflow::utils::{find_escrow_address, PeriodUnit};
+
+#[tokio::test]
+async fn test_tok_cancel_stream() {
+    let program_id = Pubkey::from_str("streamf1ow111111111111111111111111111111111").unwrap();
+
+    let alice = Keypair::new();
+    let bob = Keypair::new();
+    let alice_tokens = Keypair::new();
+    let bob_tokens = Keypair::new();
+    let data_acc = Keypair::new();
+    let mint = Keypair::new();
+    let (escrow_acc, escrow_bump) = find_escrow_address(&program_id, &data_acc.pubkey());
+
+    let mut program_test =
+        ProgramTest::new("streamflow", program_id, processor!(process_instruction));
+    program_test.add_program(
+        "spl_token",
+        spl_token::id(),
+        processor!(spl_token::processor::Processor::process),
+    );
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let sf = StreamFlowInstruction::CancelToken;
+
+    let dat = StreamFlow {
+        version: STREAM_VERSION,
+        start_time: now - 300,
+        end_time: now + 300,
+        amount: 1_000_000,
+        withdrawn: 0,
+        sender: alice.pubkey().to_bytes(),
+        recipient: bob.pubkey().to_bytes(),
+        mint: mint.pubkey().to_bytes(),
+        escrow: escrow_acc.to_bytes(),
+        nonce: 0,
+        bump: 0,
+        escrow_bump,
+        cliff_time: 0,
+        cliff_amount: 0,
+        period: 0,
+        amount_per_period: 0,
+        period_unit: PeriodUnit::Seconds,
+        schedule: [0; 32],
+        rate: 0,
+        recipient_tokens: [0; 32],
+        transfer_by_sender_only: false,
+        cancelable_by_sender: true,
+        cancelable_by_recipient: false,
+        paused_at: 0,
+        paused_time: 0,
+        rent_destination: alice.pubkey().to_bytes(),
+        crank_fee_bps: 0,
+    };
+
+    let mut mint_data = vec![0; Mint::LEN];
+    Mint::pack(
+        Mint {
+            mint_authority: COption::Some(alice.pubkey()),
+            supply: 1_000_000,
+            decimals: 6,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        },
+        &mut mint_data,
+    )
+    .unwrap();
+
+    program_test.add_account(
+        mint.pubkey(),
+        Account {
+            owner: spl_token::id(),
+            lamports: sol_to_lamports(1.0),
+            data: mint_data,
+            ..Account::default()
+        },
+    );
+
+    program_test.add_account(
+        data_acc.pubkey(),
+        Account {
+            owner: program_id,
+            lamports: sol_to_lamports(1.0),
+            data: pack_stream(&dat),
+            ..Account::default()
+        },
+    );
+
+    program_test.add_account(
+        escrow_acc,
+        token_account(&mint.pubkey(), &escrow_acc, 1_000_000),
+    );
+
+    program_test.add_account(
+        alice_tokens.pubkey(),
+        token_account(&mint.pubkey(), &alice.pubkey(), 0),
+    );
+
+    program_test.add_account(
+        bob_tokens.pubkey(),
+        token_account(&mint.pubkey(), &bob.pubkey(), 0),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[Instruction::new_with_bytes(
+            program_id,
+            &sf.pack(),
+            vec![
+                AccountMeta::new(alice.pubkey(), true),
+                AccountMeta::new(alice_tokens.pubkey(), false),
+                AccountMeta::new(bob.pubkey(), false),
+                AccountMeta::new(bob_tokens.pubkey(), false),
+                AccountMeta::new(data_acc.pubkey(), false),
+                AccountMeta::new(escrow_acc, false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+            ],
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &alice], recent_blockhash);
+
+    match banks_client.process_transaction(transaction).await {
+        Ok(()) => (),
+        Err(e) => panic!("{}", e),
+    }
+
+    let alice_tokens_acc = banks_client
+        .get_account(alice_tokens.pubkey())
+        .await
+        .unwrap()
+        .unwrap();
+    let bob_tokens_acc = banks_client
+        .get_account(bob_tokens.pubkey())
+        .await
+        .unwrap()
+        .unwrap();
+    let returned = TokenAccount::unpack(&alice_tokens_acc.data).unwrap().amount;
+    let unlocked = TokenAccount::unpack(&bob_tokens_acc.data).unwrap().amount;
+    assert!(unlocked > 0 && returned > 0);
+    assert_eq!(unlocked + returned, 1_000_000);
+
+    assert!(banks_client
+        .get_account(escrow_acc)
+        .await
+        .unwrap()
+        .is_none());
+    assert!(banks_client
+        .get_account(data_acc.pubkey())
+        .await
+        .unwrap()
+        .is_none());
+}
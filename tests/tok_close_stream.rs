@@ -0,0 +1,181 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+mod common;
+
+use std::{str::FromStr, time::SystemTime};
+
+use solana_program::{
+    instruction::InstructionError, native_token::sol_to_lamports, program_pack::Pack,
+    pubkey::Pubkey,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    signature::Signer,
+    signer::keypair::Keypair,
+    transaction::{Transaction, TransactionError},
+};
+use spl_token::state::Account as TokenAccount;
+
+use streamflow::error::StreamFlowError;
+use streamflow::instruction::close_token;
+use streamflow::process_instruction;
+use streamflow::state::{StreamFlow, STREAM_VERSION};
+use streamflow::utils::{find_config_address, find_escrow_address, PeriodUnit};
+
+use common::{config_account, pack_stream, token_account};
+
+#[tokio::test]
+async fn test_tok_close_sweeps_escrow() {
+    let program_id = Pubkey::from_str("streamf1ow111111111111111111111111111111111").unwrap();
+
+    let alice = Keypair::new();
+    let bob = Keypair::new();
+    let bob_tokens = Keypair::new();
+    let alice_tokens = Keypair::new();
+    let data_acc = Keypair::new();
+    let mint = Keypair::new();
+    let (escrow_acc, escrow_bump) = find_escrow_address(&program_id, &data_acc.pubkey());
+
+    let mut program_test =
+        ProgramTest::new("streamflow", program_id, processor!(process_instruction));
+    program_test.add_program(
+        "spl_token",
+        spl_token::id(),
+        processor!(spl_token::processor::Processor::process),
+    );
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    // Fully withdrawn, but someone sent 500 more tokens to the escrow.
+    let dat = StreamFlow {
+        version: STREAM_VERSION,
+        start_time: now - 610,
+        end_time: now - 10,
+        amount: 1_000_000,
+        withdrawn: 1_000_000,
+        sender: alice.pubkey().to_bytes(),
+        recipient: bob.pubkey().to_bytes(),
+        mint: mint.pubkey().to_bytes(),
+        escrow: escrow_acc.to_bytes(),
+        nonce: 0,
+        bump: 0,
+        escrow_bump,
+        cliff_time: 0,
+        cliff_amount: 0,
+        period: 0,
+        amount_per_period: 0,
+        period_unit: PeriodUnit::Seconds,
+        schedule: [0; 32],
+        rate: 0,
+        recipient_tokens: bob_tokens.pubkey().to_bytes(),
+        transfer_by_sender_only: false,
+        cancelable_by_sender: true,
+        cancelable_by_recipient: false,
+        paused_at: 0,
+        paused_time: 0,
+        rent_destination: alice.pubkey().to_bytes(),
+        crank_fee_bps: 0,
+    };
+
+    program_test.add_account(
+        data_acc.pubkey(),
+        Account {
+            owner: program_id,
+            lamports: sol_to_lamports(1.0),
+            data: pack_stream(&dat),
+            ..Account::default()
+        },
+    );
+
+    program_test.add_account(escrow_acc, token_account(&mint.pubkey(), &escrow_acc, 500));
+
+    program_test.add_account(
+        bob_tokens.pubkey(),
+        token_account(&mint.pubkey(), &bob.pubkey(), 0),
+    );
+
+    program_test.add_account(
+        alice_tokens.pubkey(),
+        token_account(&mint.pubkey(), &alice.pubkey(), 0),
+    );
+
+    program_test.add_account(
+        find_config_address(&program_id).0,
+        config_account(&program_id, &Pubkey::new_unique()),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // The extra tokens only ever go to the recipient.
+    let mut transaction = Transaction::new_with_payer(
+        &[close_token(
+            &program_id,
+            &data_acc.pubkey(),
+            &alice_tokens.pubkey(),
+            &alice.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StreamFlowError::InvalidTokenAccount as u32)
+        )
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[close_token(
+            &program_id,
+            &data_acc.pubkey(),
+            &bob_tokens.pubkey(),
+            &alice.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+
+    match banks_client.process_transaction(transaction).await {
+        Ok(()) => (),
+        Err(e) => panic!("{}", e),
+    }
+
+    let acc = banks_client
+        .get_account(bob_tokens.pubkey())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(TokenAccount::unpack(&acc.data).unwrap().amount, 500);
+
+    for closed in [data_acc.pubkey(), escrow_acc] {
+        assert!(banks_client.get_account(closed).await.unwrap().is_none());
+    }
+    assert_eq!(
+        banks_client.get_balance(alice.pubkey()).await.unwrap(),
+        sol_to_lamports(2.0)
+    );
+}
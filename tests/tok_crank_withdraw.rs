@@ -0,0 +1,232 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+mod common;
+
+use std::time::SystemTime;
+
+use solana_program::{
+    instruction::InstructionError, native_token::sol_to_lamports, program_option::COption,
+    program_pack::Pack, pubkey::Pubkey,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    signature::Signer,
+    signer::keypair::Keypair,
+    transaction::{Transaction, TransactionError},
+};
+use spl_token::state::{Account as TokenAccount, Mint};
+
+use streamflow::error::StreamFlowError;
+use streamflow::instruction::crank_token;
+use streamflow::process_instruction;
+use streamflow::state::{StreamFlow, STREAM_VERSION};
+use streamflow::utils::{find_config_address, find_escrow_address, PeriodUnit};
+
+use common::{config_account, pack_stream, program_id, token_account};
+
+#[tokio::test]
+async fn test_tok_crank_withdraw() {
+    let program_id = program_id();
+
+    let alice = Keypair::new();
+    let bob = Keypair::new();
+    let bob_tokens = Keypair::new();
+    let keeper = Keypair::new();
+    let keeper_tokens = Keypair::new();
+    let data_acc = Keypair::new();
+    let mint = Keypair::new();
+    let (escrow_acc, escrow_bump) = find_escrow_address(&program_id, &data_acc.pubkey());
+
+    let mut program_test =
+        ProgramTest::new("streamflow", program_id, processor!(process_instruction));
+    program_test.add_program(
+        "spl_token",
+        spl_token::id(),
+        processor!(spl_token::processor::Processor::process),
+    );
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    // Everything is unlocked, and 0.5% of it goes to whoever cranks.
+    let dat = StreamFlow {
+        version: STREAM_VERSION,
+        start_time: now - 610,
+        end_time: now - 10,
+        amount: 1_000_000,
+        withdrawn: 0,
+        sender: alice.pubkey().to_bytes(),
+        recipient: bob.pubkey().to_bytes(),
+        mint: mint.pubkey().to_bytes(),
+        escrow: escrow_acc.to_bytes(),
+        nonce: 0,
+        bump: 0,
+        escrow_bump,
+        cliff_time: 0,
+        cliff_amount: 0,
+        period: 0,
+        amount_per_period: 0,
+        period_unit: PeriodUnit::Seconds,
+        schedule: [0; 32],
+        rate: 0,
+        recipient_tokens: bob_tokens.pubkey().to_bytes(),
+        transfer_by_sender_only: false,
+        cancelable_by_sender: true,
+        cancelable_by_recipient: false,
+        paused_at: 0,
+        paused_time: 0,
+        rent_destination: alice.pubkey().to_bytes(),
+        crank_fee_bps: 50,
+    };
+
+    let mut mint_data = vec![0; Mint::LEN];
+    Mint::pack(
+        Mint {
+            mint_authority: COption::Some(alice.pubkey()),
+            supply: 1_000_000,
+            decimals: 6,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        },
+        &mut mint_data,
+    )
+    .unwrap();
+
+    program_test.add_account(
+        mint.pubkey(),
+        Account {
+            owner: spl_token::id(),
+            lamports: sol_to_lamports(1.0),
+            data: mint_data,
+            ..Account::default()
+        },
+    );
+
+    program_test.add_account(
+        data_acc.pubkey(),
+        Account {
+            owner: program_id,
+            lamports: sol_to_lamports(1.0),
+            data: pack_stream(&dat),
+            ..Account::default()
+        },
+    );
+
+    program_test.add_account(
+        escrow_acc,
+        token_account(&mint.pubkey(), &escrow_acc, 1_000_000),
+    );
+
+    program_test.add_account(
+        bob_tokens.pubkey(),
+        token_account(&mint.pubkey(), &bob.pubkey(), 0),
+    );
+
+    program_test.add_account(
+        keeper_tokens.pubkey(),
+        token_account(&mint.pubkey(), &keeper.pubkey(), 0),
+    );
+
+    program_test.add_account(
+        find_config_address(&program_id).0,
+        config_account(&program_id, &Pubkey::new_unique()),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // A stream paying a fee needs the keeper's token account.
+    let mut transaction = Transaction::new_with_payer(
+        &[crank_token(
+            &program_id,
+            &keeper.pubkey(),
+            &bob_tokens.pubkey(),
+            &data_acc.pubkey(),
+            &alice.pubkey(),
+            None,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &keeper], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(0, InstructionError::NotEnoughAccountKeys)
+    );
+
+    // The keeper's token account can't take the place of the schedule's.
+    let mut ix = crank_token(
+        &program_id,
+        &keeper.pubkey(),
+        &bob_tokens.pubkey(),
+        &data_acc.pubkey(),
+        &alice.pubkey(),
+        Some(&keeper_tokens.pubkey()),
+    );
+    ix.accounts.remove(7);
+    let mut transaction = Transaction::new_with_payer(&[ix], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &keeper], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StreamFlowError::InvalidScheduleAccount as u32)
+        )
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[crank_token(
+            &program_id,
+            &keeper.pubkey(),
+            &bob_tokens.pubkey(),
+            &data_acc.pubkey(),
+            &alice.pubkey(),
+            Some(&keeper_tokens.pubkey()),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &keeper], recent_blockhash);
+
+    match banks_client.process_transaction(transaction).await {
+        Ok(()) => (),
+        Err(e) => panic!("{}", e),
+    }
+
+    for (tokens, amount) in [(&bob_tokens, 995_000), (&keeper_tokens, 5_000)] {
+        let acc = banks_client
+            .get_account(tokens.pubkey())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(TokenAccount::unpack(&acc.data).unwrap().amount, amount);
+    }
+
+    assert!(banks_client
+        .get_account(data_acc.pubkey())
+        .await
+        .unwrap()
+        .is_none());
+}
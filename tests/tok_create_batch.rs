@@ -0,0 +1,242 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+mod common;
+
+use std::time::SystemTime;
+
+use solana_program::{
+    native_token::sol_to_lamports, program_error::ProgramError, program_pack::Pack, pubkey::Pubkey,
+    rent::Rent,
+};
+use solana_sdk::account::Account;
+use spl_token::state::Account as TokenAccount;
+
+use streamflow::instruction::{create_batch_token, StreamParams};
+use streamflow::state::StreamFlow;
+use streamflow::utils::{find_batch_metadata_address, find_escrow_address, PeriodUnit};
+
+use common::{mint_account, program_id, token_account, Runtime, LAMPORTS_PER_SIGNATURE};
+
+fn params(now: u64, amount: u64) -> StreamParams {
+    StreamParams {
+        start_time: now + 10,
+        end_time: now + 610,
+        amount,
+        cliff_time: 0,
+        cliff_amount: 0,
+        period: 0,
+        amount_per_period: 0,
+        period_unit: PeriodUnit::Seconds,
+        breakpoints: vec![],
+        rate: 0,
+        transfer_by_sender_only: false,
+        cancelable_by_sender: true,
+        cancelable_by_recipient: false,
+        rent_destination: None,
+        crank_fee_bps: 0,
+    }
+}
+
+/// The lamports Alice pays for every stream of a batch.
+fn stream_funds() -> u64 {
+    let rent = Rent::default();
+    rent.minimum_balance(StreamFlow::LEN)
+        + rent.minimum_balance(TokenAccount::LEN)
+        + 3 * LAMPORTS_PER_SIGNATURE
+}
+
+struct Setup {
+    runtime: Runtime,
+    alice: Pubkey,
+    alice_tokens: Pubkey,
+    mint: Pubkey,
+    now: u64,
+}
+
+/// A runtime where Alice holds `lamports` and 1000 tokens of a new mint.
+fn setup(lamports: u64) -> Setup {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let alice = Pubkey::new_unique();
+    let alice_tokens = Pubkey::new_unique();
+    let mint = Pubkey::new_unique();
+
+    let mut runtime = Runtime::new(program_id(), now);
+    runtime.add_account(
+        alice,
+        Account {
+            lamports,
+            ..Account::default()
+        },
+    );
+    runtime.add_account(mint, mint_account(&alice, 1000));
+    runtime.add_account(alice_tokens, token_account(&mint, &alice, 1000));
+
+    Setup {
+        runtime,
+        alice,
+        alice_tokens,
+        mint,
+        now,
+    }
+}
+
+/// A recipient without lamports and their empty token account.
+fn recipient(s: &mut Setup) -> (Pubkey, Pubkey) {
+    let bob = Pubkey::new_unique();
+    let bob_tokens = Pubkey::new_unique();
+    s.runtime
+        .add_account(bob_tokens, token_account(&s.mint, &bob, 0));
+    (bob, bob_tokens)
+}
+
+#[test]
+fn test_create_batch_token() {
+    let mut s = setup(sol_to_lamports(1.0));
+    let program_id = program_id();
+    let (bob, bob_tokens) = recipient(&mut s);
+    let (carol, carol_tokens) = recipient(&mut s);
+
+    let ix = create_batch_token(
+        &program_id,
+        &s.alice,
+        &s.alice_tokens,
+        &s.mint,
+        vec![
+            (bob, bob_tokens, params(s.now, 300)),
+            (carol, carol_tokens, params(s.now, 200)),
+        ],
+        7,
+    );
+    s.runtime.process(&ix).unwrap();
+
+    let data_rent = Rent::default().minimum_balance(StreamFlow::LEN);
+    for (index, recipient, recipient_tokens, amount) in
+        [(0, bob, bob_tokens, 300), (1, carol, carol_tokens, 200)]
+    {
+        let (metadata, bump) =
+            find_batch_metadata_address(&program_id, &s.alice, &s.mint, 7, index);
+        let (escrow, escrow_bump) = find_escrow_address(&program_id, &metadata);
+
+        let acc = s.runtime.account(&metadata).unwrap();
+        assert_eq!(acc.owner, program_id);
+        assert_eq!(acc.lamports, data_rent);
+        let sf = StreamFlow::unpack(&acc.data).unwrap();
+        assert_eq!(sf.sender, s.alice.to_bytes());
+        assert_eq!(sf.recipient, recipient.to_bytes());
+        assert_eq!(sf.recipient_tokens, recipient_tokens.to_bytes());
+        assert_eq!(sf.mint, s.mint.to_bytes());
+        assert_eq!(sf.escrow, escrow.to_bytes());
+        assert_eq!(sf.amount, amount);
+        assert_eq!((sf.nonce, sf.bump, sf.escrow_bump), (7, bump, escrow_bump));
+        assert_eq!(s.runtime.token_amount(&escrow), amount);
+
+        // Every recipient can pay for their first withdrawal.
+        assert_eq!(s.runtime.lamports(&recipient), 3 * LAMPORTS_PER_SIGNATURE);
+    }
+
+    assert_eq!(s.runtime.token_amount(&s.alice_tokens), 500);
+    assert_eq!(
+        s.runtime.lamports(&s.alice),
+        sol_to_lamports(1.0) - 2 * stream_funds()
+    );
+
+    // The same batch can't be created twice.
+    assert_eq!(
+        s.runtime.process(&ix),
+        Err(ProgramError::AccountAlreadyInitialized)
+    );
+}
+
+#[test]
+fn test_create_batch_token_insufficient_funds() {
+    // Alice can pay for the first stream, but not for the second.
+    let mut s = setup(stream_funds() + 1);
+    let program_id = program_id();
+    let (bob, bob_tokens) = recipient(&mut s);
+    let (carol, carol_tokens) = recipient(&mut s);
+
+    let ix = create_batch_token(
+        &program_id,
+        &s.alice,
+        &s.alice_tokens,
+        &s.mint,
+        vec![
+            (bob, bob_tokens, params(s.now, 300)),
+            (carol, carol_tokens, params(s.now, 200)),
+        ],
+        7,
+    );
+    assert_eq!(s.runtime.process(&ix), Err(ProgramError::InsufficientFunds));
+
+    // Nothing of the batch was created, and Alice kept everything.
+    for index in 0..2 {
+        let (metadata, _) = find_batch_metadata_address(&program_id, &s.alice, &s.mint, 7, index);
+        let (escrow, _) = find_escrow_address(&program_id, &metadata);
+        assert!(s.runtime.account(&metadata).is_none());
+        assert!(s.runtime.account(&escrow).is_none());
+    }
+    assert_eq!(s.runtime.lamports(&s.alice), stream_funds() + 1);
+    assert_eq!(s.runtime.token_amount(&s.alice_tokens), 1000);
+    assert_eq!(s.runtime.lamports(&bob), 0);
+
+    // Likewise when Alice holds too few tokens.
+    let ix = create_batch_token(
+        &program_id,
+        &s.alice,
+        &s.alice_tokens,
+        &s.mint,
+        vec![(bob, bob_tokens, params(s.now, 1001))],
+        7,
+    );
+    assert_eq!(s.runtime.process(&ix), Err(ProgramError::InsufficientFunds));
+    assert_eq!(s.runtime.token_amount(&s.alice_tokens), 1000);
+}
+
+#[test]
+fn test_create_batch_token_duplicate_recipient() {
+    let mut s = setup(sol_to_lamports(1.0));
+    let program_id = program_id();
+    let (bob, bob_tokens) = recipient(&mut s);
+
+    let ix = create_batch_token(
+        &program_id,
+        &s.alice,
+        &s.alice_tokens,
+        &s.mint,
+        vec![
+            (bob, bob_tokens, params(s.now, 300)),
+            (bob, bob_tokens, params(s.now, 200)),
+        ],
+        7,
+    );
+    s.runtime.process(&ix).unwrap();
+
+    // Bob got two separate streams, and lamports for each of them.
+    for (index, amount) in [(0, 300), (1, 200)] {
+        let (metadata, _) = find_batch_metadata_address(&program_id, &s.alice, &s.mint, 7, index);
+        let (escrow, _) = find_escrow_address(&program_id, &metadata);
+        let sf = StreamFlow::unpack(&s.runtime.account(&metadata).unwrap().data).unwrap();
+        assert_eq!(sf.recipient, bob.to_bytes());
+        assert_eq!(sf.amount, amount);
+        assert_eq!(s.runtime.token_amount(&escrow), amount);
+    }
+    assert_eq!(s.runtime.lamports(&bob), 6 * LAMPORTS_PER_SIGNATURE);
+    assert_eq!(s.runtime.token_amount(&s.alice_tokens), 500);
+}
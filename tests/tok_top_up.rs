@@ -0,0 +1,171 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+mod common;
+
+use std::{str::FromStr, time::SystemTime};
+
+use solana_program::{
+    native_token::sol_to_lamports, program_option::COption, program_pack::Pack, pubkey::Pubkey,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account, signature::Signer, signer::keypair::Keypair, transaction::Transaction,
+};
+use spl_token::state::{Account as TokenAccount, Mint};
+
+use streamflow::instruction::top_up_token;
+use streamflow::process_instruction;
+use streamflow::state::{StreamFlow, STREAM_VERSION};
+
+use common::token_account;
+use streamflow::utils::{find_escrow_address, PeriodUnit, TopUpMode};
+
+#[tokio::test]
+async fn test_tok_top_up_stream() {
+    let program_id = Pubkey::from_str("streamf1ow111111111111111111111111111111111").unwrap();
+
+    let alice = Keypair::new();
+    let alice_tokens = Keypair::new();
+    let bob = Keypair::new();
+    let data_acc = Keypair::new();
+    let mint = Keypair::new();
+    let (escrow_acc, escrow_bump) = find_escrow_address(&program_id, &data_acc.pubkey());
+
+    let mut program_test =
+        ProgramTest::new("streamflow", program_id, processor!(process_instruction));
+    program_test.add_program(
+        "spl_token",
+        spl_token::id(),
+        processor!(spl_token::processor::Processor::process),
+    );
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let dat = StreamFlow {
+        version: STREAM_VERSION,
+        start_time: now + 600,
+        end_time: now + 1200,
+        amount: 1_000_000,
+        withdrawn: 0,
+        sender: alice.pubkey().to_bytes(),
+        recipient: bob.pubkey().to_bytes(),
+        mint: mint.pubkey().to_bytes(),
+        escrow: escrow_acc.to_bytes(),
+        nonce: 0,
+        bump: 0,
+        escrow_bump,
+        cliff_time: 0,
+        cliff_amount: 0,
+        period: 0,
+        amount_per_period: 0,
+        period_unit: PeriodUnit::Seconds,
+        schedule: [0; 32],
+        rate: 0,
+        recipient_tokens: [0; 32],
+        transfer_by_sender_only: false,
+        cancelable_by_sender: true,
+        cancelable_by_recipient: false,
+        paused_at: 0,
+        paused_time: 0,
+        rent_destination: alice.pubkey().to_bytes(),
+        crank_fee_bps: 0,
+    };
+
+    let mut mint_data = vec![0; Mint::LEN];
+    Mint::pack(
+        Mint {
+            mint_authority: COption::Some(alice.pubkey()),
+            supply: 1_500_000,
+            decimals: 6,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        },
+        &mut mint_data,
+    )
+    .unwrap();
+
+    program_test.add_account(
+        mint.pubkey(),
+        Account {
+            owner: spl_token::id(),
+            lamports: sol_to_lamports(1.0),
+            data: mint_data,
+            ..Account::default()
+        },
+    );
+
+    let mut data = vec![0; StreamFlow::LEN];
+    StreamFlow::pack(dat, &mut data).unwrap();
+    program_test.add_account(
+        data_acc.pubkey(),
+        Account {
+            owner: program_id,
+            lamports: sol_to_lamports(1.0),
+            data,
+            ..Account::default()
+        },
+    );
+
+    program_test.add_account(
+        escrow_acc,
+        token_account(&mint.pubkey(), &escrow_acc, 1_000_000),
+    );
+
+    program_test.add_account(
+        alice_tokens.pubkey(),
+        token_account(&mint.pubkey(), &alice.pubkey(), 500_000),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[top_up_token(
+            &program_id,
+            &alice.pubkey(),
+            &alice_tokens.pubkey(),
+            &data_acc.pubkey(),
+            500_000,
+            TopUpMode::Increase,
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &alice], recent_blockhash);
+
+    match banks_client.process_transaction(transaction).await {
+        Ok(()) => (),
+        Err(e) => panic!("{}", e),
+    }
+
+    let escrow = banks_client.get_account(escrow_acc).await.unwrap().unwrap();
+    assert_eq!(
+        TokenAccount::unpack(&escrow.data).unwrap().amount,
+        1_500_000
+    );
+
+    let data = banks_client
+        .get_account(data_acc.pubkey())
+        .await
+        .unwrap()
+        .unwrap();
+    let sf = StreamFlow::unpack(&data.data).unwrap();
+    assert_eq!(sf.amount, 1_500_000);
+    assert_eq!(sf.end_time, now + 1200);
+}
@@ -0,0 +1,412 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+mod common;
+
+use std::{str::FromStr, time::SystemTime};
+
+use solana_program::{
+    instruction::{AccountMeta, Instruction, InstructionError},
+    native_token::sol_to_lamports,
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    signature::Signer,
+    signer::keypair::Keypair,
+    transaction::{Transaction, TransactionError},
+};
+use spl_token::state::{Account as TokenAccount, Mint};
+
+use streamflow::error::StreamFlowError;
+use streamflow::instruction::{withdraw_token, StreamFlowInstruction};
+use streamflow::process_instruction;
+use streamflow::state::{StreamFlow, StreamSchedule, STREAM_VERSION};
+use streamflow::utils::{
+    find_config_address, find_escrow_address, find_schedule_address, Breakpoint, PeriodUnit,
+};
+
+use common::{config_account, pack_stream, token_account};
+
+#[tokio::test]
+async fn test_tok_withdraw_unlocked() {
+    let program_id = Pubkey::from_str("streamf1ow111111111111111111111111111111111").unwrap();
+
+    let alice = Keypair::new();
+    let bob = Keypair::new();
+    let bob_tokens = Keypair::new();
+    let data_acc = Keypair::new();
+    let mint = Keypair::new();
+    let (escrow_acc, escrow_bump) = find_escrow_address(&program_id, &data_acc.pubkey());
+
+    let mut program_test =
+        ProgramTest::new("streamflow", program_id, processor!(process_instruction));
+    program_test.add_program(
+        "spl_token",
+        spl_token::id(),
+        processor!(spl_token::processor::Processor::process),
+    );
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let sf = StreamFlowInstruction::WithdrawToken { amount: 0 };
+
+    let dat = StreamFlow {
+        version: STREAM_VERSION,
+        start_time: now - 610,
+        end_time: now - 10,
+        amount: 1_000_000,
+        withdrawn: 0,
+        sender: alice.pubkey().to_bytes(),
+        recipient: bob.pubkey().to_bytes(),
+        mint: mint.pubkey().to_bytes(),
+        escrow: escrow_acc.to_bytes(),
+        nonce: 0,
+        bump: 0,
+        escrow_bump,
+        cliff_time: 0,
+        cliff_amount: 0,
+        period: 0,
+        amount_per_period: 0,
+        period_unit: PeriodUnit::Seconds,
+        schedule: [0; 32],
+        rate: 0,
+        recipient_tokens: [0; 32],
+        transfer_by_sender_only: false,
+        cancelable_by_sender: true,
+        cancelable_by_recipient: false,
+        paused_at: 0,
+        paused_time: 0,
+        rent_destination: alice.pubkey().to_bytes(),
+        crank_fee_bps: 0,
+    };
+
+    let mut mint_data = vec![0; Mint::LEN];
+    Mint::pack(
+        Mint {
+            mint_authority: COption::Some(alice.pubkey()),
+            supply: 1_000_000,
+            decimals: 6,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        },
+        &mut mint_data,
+    )
+    .unwrap();
+
+    program_test.add_account(
+        mint.pubkey(),
+        Account {
+            owner: spl_token::id(),
+            lamports: sol_to_lamports(1.0),
+            data: mint_data,
+            ..Account::default()
+        },
+    );
+
+    program_test.add_account(
+        data_acc.pubkey(),
+        Account {
+            owner: program_id,
+            lamports: sol_to_lamports(1.0),
+            data: pack_stream(&dat),
+            ..Account::default()
+        },
+    );
+
+    program_test.add_account(
+        escrow_acc,
+        token_account(&mint.pubkey(), &escrow_acc, 1_000_000),
+    );
+
+    program_test.add_account(
+        bob_tokens.pubkey(),
+        token_account(&mint.pubkey(), &bob.pubkey(), 0),
+    );
+
+    program_test.add_account(
+        find_config_address(&program_id).0,
+        config_account(&program_id, &Pubkey::new_unique()),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[Instruction::new_with_bytes(
+            program_id,
+            &sf.pack(),
+            vec![
+                AccountMeta::new(bob.pubkey(), true),
+                AccountMeta::new(bob_tokens.pubkey(), false),
+                AccountMeta::new(data_acc.pubkey(), false),
+                AccountMeta::new(escrow_acc, false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new(alice.pubkey(), false),
+                AccountMeta::new_readonly(find_config_address(&program_id).0, false),
+            ],
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &bob], recent_blockhash);
+
+    match banks_client.process_transaction(transaction).await {
+        Ok(()) => (),
+        Err(e) => panic!("{}", e),
+    }
+
+    let bob_tokens_acc = banks_client
+        .get_account(bob_tokens.pubkey())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        TokenAccount::unpack(&bob_tokens_acc.data).unwrap().amount,
+        1_000_000
+    );
+
+    // The empty stream is closed, and Alice gets the rent of both accounts.
+    assert!(banks_client
+        .get_account(escrow_acc)
+        .await
+        .unwrap()
+        .is_none());
+    assert!(banks_client
+        .get_account(data_acc.pubkey())
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        banks_client.get_balance(alice.pubkey()).await.unwrap(),
+        sol_to_lamports(2.0)
+    );
+}
+
+#[tokio::test]
+async fn test_tok_withdraw_unlocked_schedule() {
+    let program_id = Pubkey::from_str("streamf1ow111111111111111111111111111111111").unwrap();
+
+    let alice = Keypair::new();
+    let bob = Keypair::new();
+    let bob_tokens = Keypair::new();
+    let savings_tokens = Keypair::new();
+    let stray_tokens = Keypair::new();
+    let data_acc = Keypair::new();
+    let mint = Keypair::new();
+    let (escrow_acc, escrow_bump) = find_escrow_address(&program_id, &data_acc.pubkey());
+    let (schedule_acc, _) = find_schedule_address(&program_id, &data_acc.pubkey());
+
+    let mut program_test =
+        ProgramTest::new("streamflow", program_id, processor!(process_instruction));
+    program_test.add_program(
+        "spl_token",
+        spl_token::id(),
+        processor!(spl_token::processor::Processor::process),
+    );
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    // 300k unlocked at once, 300k more over five minutes, and the rest
+    // over the next hour.
+    let schedule = StreamSchedule::new(
+        &data_acc.pubkey(),
+        vec![
+            Breakpoint {
+                timestamp: now - 600,
+                cumulative: 300_000,
+            },
+            Breakpoint {
+                timestamp: now - 300,
+                cumulative: 600_000,
+            },
+            Breakpoint {
+                timestamp: now + 3600,
+                cumulative: 1_000_000,
+            },
+        ],
+    );
+
+    let dat = StreamFlow {
+        version: STREAM_VERSION,
+        start_time: now - 600,
+        end_time: now + 3600,
+        amount: 1_000_000,
+        withdrawn: 0,
+        sender: alice.pubkey().to_bytes(),
+        recipient: bob.pubkey().to_bytes(),
+        mint: mint.pubkey().to_bytes(),
+        escrow: escrow_acc.to_bytes(),
+        nonce: 0,
+        bump: 0,
+        escrow_bump,
+        cliff_time: 0,
+        cliff_amount: 0,
+        period: 0,
+        amount_per_period: 0,
+        period_unit: PeriodUnit::Seconds,
+        schedule: schedule_acc.to_bytes(),
+        rate: 0,
+        recipient_tokens: [0; 32],
+        transfer_by_sender_only: false,
+        cancelable_by_sender: true,
+        cancelable_by_recipient: false,
+        paused_at: 0,
+        paused_time: 0,
+        rent_destination: alice.pubkey().to_bytes(),
+        crank_fee_bps: 0,
+    };
+
+    let mut mint_data = vec![0; Mint::LEN];
+    Mint::pack(
+        Mint {
+            mint_authority: COption::Some(alice.pubkey()),
+            supply: 1_000_000,
+            decimals: 6,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        },
+        &mut mint_data,
+    )
+    .unwrap();
+
+    program_test.add_account(
+        mint.pubkey(),
+        Account {
+            owner: spl_token::id(),
+            lamports: sol_to_lamports(1.0),
+            data: mint_data,
+            ..Account::default()
+        },
+    );
+
+    program_test.add_account(
+        data_acc.pubkey(),
+        Account {
+            owner: program_id,
+            lamports: sol_to_lamports(1.0),
+            data: pack_stream(&dat),
+            ..Account::default()
+        },
+    );
+
+    let mut schedule_data = vec![0; StreamSchedule::LEN];
+    StreamSchedule::pack(schedule, &mut schedule_data).unwrap();
+    program_test.add_account(
+        schedule_acc,
+        Account {
+            owner: program_id,
+            lamports: sol_to_lamports(1.0),
+            data: schedule_data,
+            ..Account::default()
+        },
+    );
+
+    program_test.add_account(
+        escrow_acc,
+        token_account(&mint.pubkey(), &escrow_acc, 1_000_000),
+    );
+
+    program_test.add_account(
+        bob_tokens.pubkey(),
+        token_account(&mint.pubkey(), &bob.pubkey(), 0),
+    );
+
+    // Bob wants his tokens in another account of his, and a token account
+    // of another mint can't take them.
+    program_test.add_account(
+        savings_tokens.pubkey(),
+        token_account(&mint.pubkey(), &bob.pubkey(), 0),
+    );
+    program_test.add_account(
+        stray_tokens.pubkey(),
+        token_account(&Pubkey::new_unique(), &bob.pubkey(), 0),
+    );
+
+    program_test.add_account(
+        find_config_address(&program_id).0,
+        config_account(&program_id, &Pubkey::new_unique()),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[withdraw_token(
+            &program_id,
+            &bob.pubkey(),
+            &bob_tokens.pubkey(),
+            &data_acc.pubkey(),
+            &alice.pubkey(),
+            Some(&stray_tokens.pubkey()),
+            0,
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &bob], recent_blockhash);
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StreamFlowError::InvalidTokenAccount as u32)
+        )
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[withdraw_token(
+            &program_id,
+            &bob.pubkey(),
+            &bob_tokens.pubkey(),
+            &data_acc.pubkey(),
+            &alice.pubkey(),
+            Some(&savings_tokens.pubkey()),
+            0,
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &bob], recent_blockhash);
+
+    match banks_client.process_transaction(transaction).await {
+        Ok(()) => (),
+        Err(e) => panic!("{}", e),
+    }
+
+    // About 300 seconds past the second breakpoint, 400k over 3900 seconds
+    // are unlocking.
+    let savings_tokens_acc = banks_client
+        .get_account(savings_tokens.pubkey())
+        .await
+        .unwrap()
+        .unwrap();
+    let unlocked = TokenAccount::unpack(&savings_tokens_acc.data)
+        .unwrap()
+        .amount;
+    assert!((630_000..640_000).contains(&unlocked), "{}", unlocked);
+}
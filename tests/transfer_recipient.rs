@@ -0,0 +1,270 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+mod common;
+
+use std::{str::FromStr, time::SystemTime};
+
+use solana_program::{
+    instruction::InstructionError, native_token::sol_to_lamports, program_pack::Pack,
+    pubkey::Pubkey,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    signature::Signer,
+    signer::keypair::Keypair,
+    transaction::{Transaction, TransactionError},
+};
+
+use streamflow::error::StreamFlowError;
+use streamflow::instruction::transfer_recipient;
+use streamflow::process_instruction;
+use streamflow::state::{StreamFlow, STREAM_VERSION};
+use streamflow::utils::PeriodUnit;
+
+use common::token_account;
+
+fn token_stream(alice: &Pubkey, bob: &Pubkey, bob_tokens: &Pubkey, mint: &Pubkey) -> StreamFlow {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    StreamFlow {
+        version: STREAM_VERSION,
+        start_time: now - 300,
+        end_time: now + 300,
+        amount: 1_000_000,
+        withdrawn: 0,
+        sender: alice.to_bytes(),
+        recipient: bob.to_bytes(),
+        mint: mint.to_bytes(),
+        escrow: [0; 32], // unused
+        nonce: 0,
+        bump: 0,
+        escrow_bump: 0,
+        cliff_time: 0,
+        cliff_amount: 0,
+        period: 0,
+        amount_per_period: 0,
+        period_unit: PeriodUnit::Seconds,
+        schedule: [0; 32],
+        rate: 0,
+        recipient_tokens: bob_tokens.to_bytes(),
+        transfer_by_sender_only: false,
+        cancelable_by_sender: true,
+        cancelable_by_recipient: false,
+        paused_at: 0,
+        paused_time: 0,
+        rent_destination: alice.to_bytes(),
+        crank_fee_bps: 0,
+    }
+}
+
+fn stream_account(program_id: &Pubkey, sf: &StreamFlow) -> Account {
+    let mut data = vec![0; StreamFlow::LEN];
+    StreamFlow::pack(sf.clone(), &mut data).unwrap();
+    Account {
+        owner: *program_id,
+        lamports: sol_to_lamports(1.0),
+        data,
+        ..Account::default()
+    }
+}
+
+#[tokio::test]
+async fn test_transfer_recipient() {
+    let program_id = Pubkey::from_str("streamf1ow111111111111111111111111111111111").unwrap();
+
+    let alice = Keypair::new();
+    let bob = Keypair::new();
+    let bob_tokens = Keypair::new();
+    let carol = Keypair::new();
+    let carol_tokens = Keypair::new();
+    let data_acc = Keypair::new();
+    let mint = Keypair::new();
+
+    let mut program_test =
+        ProgramTest::new("streamflow", program_id, processor!(process_instruction));
+
+    let sf = token_stream(
+        &alice.pubkey(),
+        &bob.pubkey(),
+        &bob_tokens.pubkey(),
+        &mint.pubkey(),
+    );
+    program_test.add_account(data_acc.pubkey(), stream_account(&program_id, &sf));
+    program_test.add_account(
+        carol_tokens.pubkey(),
+        token_account(&mint.pubkey(), &carol.pubkey(), 0),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[transfer_recipient(
+            &program_id,
+            &bob.pubkey(),
+            &data_acc.pubkey(),
+            &carol.pubkey(),
+            Some(&carol_tokens.pubkey()),
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &bob], recent_blockhash);
+
+    match banks_client.process_transaction(transaction).await {
+        Ok(()) => (),
+        Err(e) => panic!("{}", e),
+    }
+
+    let data = banks_client
+        .get_account(data_acc.pubkey())
+        .await
+        .unwrap()
+        .unwrap();
+    let transferred = StreamFlow::unpack(&data.data).unwrap();
+    assert_eq!(
+        transferred,
+        StreamFlow {
+            recipient: carol.pubkey().to_bytes(),
+            recipient_tokens: carol_tokens.pubkey().to_bytes(),
+            ..sf
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_transfer_recipient_sender_only() {
+    let program_id = Pubkey::from_str("streamf1ow111111111111111111111111111111111").unwrap();
+
+    let alice = Keypair::new();
+    let bob = Keypair::new();
+    let bob_tokens = Keypair::new();
+    let carol = Keypair::new();
+    let carol_tokens = Keypair::new();
+    let data_acc = Keypair::new();
+    let irrevocable_acc = Keypair::new();
+    let mint = Keypair::new();
+
+    let mut program_test =
+        ProgramTest::new("streamflow", program_id, processor!(process_instruction));
+
+    let sf = StreamFlow {
+        transfer_by_sender_only: true,
+        ..token_stream(
+            &alice.pubkey(),
+            &bob.pubkey(),
+            &bob_tokens.pubkey(),
+            &mint.pubkey(),
+        )
+    };
+    program_test.add_account(data_acc.pubkey(), stream_account(&program_id, &sf));
+    let irrevocable = StreamFlow {
+        cancelable_by_sender: false,
+        ..sf.clone()
+    };
+    program_test.add_account(
+        irrevocable_acc.pubkey(),
+        stream_account(&program_id, &irrevocable),
+    );
+    program_test.add_account(
+        carol_tokens.pubkey(),
+        token_account(&mint.pubkey(), &carol.pubkey(), 0),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // The recipient can't move the stream away on their own.
+    let mut transaction = Transaction::new_with_payer(
+        &[transfer_recipient(
+            &program_id,
+            &bob.pubkey(),
+            &data_acc.pubkey(),
+            &carol.pubkey(),
+            Some(&carol_tokens.pubkey()),
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &bob], recent_blockhash);
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StreamFlowError::UnauthorizedSigner as u32)
+        )
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[transfer_recipient(
+            &program_id,
+            &alice.pubkey(),
+            &data_acc.pubkey(),
+            &carol.pubkey(),
+            Some(&carol_tokens.pubkey()),
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &alice], recent_blockhash);
+
+    match banks_client.process_transaction(transaction).await {
+        Ok(()) => (),
+        Err(e) => panic!("{}", e),
+    }
+
+    let data = banks_client
+        .get_account(data_acc.pubkey())
+        .await
+        .unwrap()
+        .unwrap();
+    let transferred = StreamFlow::unpack(&data.data).unwrap();
+    assert_eq!(transferred.recipient, carol.pubkey().to_bytes());
+
+    // A stream the sender can't cancel can't be taken back this way.
+    let mut transaction = Transaction::new_with_payer(
+        &[transfer_recipient(
+            &program_id,
+            &alice.pubkey(),
+            &irrevocable_acc.pubkey(),
+            &carol.pubkey(),
+            Some(&carol_tokens.pubkey()),
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &alice], recent_blockhash);
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StreamFlowError::CancelNotAllowed as u32)
+        )
+    );
+}
@@ -0,0 +1,561 @@
+// Copyright (c) 2021 Ivan Jelincic <parazyd@dyne.org>
+//
+// This file is part of streamflow-program
+// https://github.com/StreamFlow-Finance/streamflow-program
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License version 3
+// as published by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+use proptest::prelude::*;
+use solana_program::pubkey::Pubkey;
+
+use streamflow::instruction::StreamParams;
+use streamflow::state::StreamFlow;
+use streamflow::utils::{
+    add_months, breakpoints_sanity, calculate_piecewise, calculate_rate_duration,
+    calculate_rate_streamed, calculate_streamed, cliff_sanity, duration_sanity, elapsed_months,
+    period_sanity, stream_sanity, Breakpoint, LinearSchedule, PeriodUnit, MAX_BREAKPOINTS,
+    MAX_CRANK_FEE_BPS,
+};
+
+fn linear(start: u64, end: u64, amount: u64) -> LinearSchedule {
+    LinearSchedule {
+        start,
+        end,
+        amount,
+        cliff: 0,
+        cliff_amount: 0,
+        period: 0,
+        amount_per_period: 0,
+        unit: PeriodUnit::Seconds,
+    }
+}
+
+#[test]
+fn test_calculate_streamed_bounds() {
+    assert_eq!(calculate_streamed(0, &linear(100, 200, 1000)), 0);
+    assert_eq!(calculate_streamed(100, &linear(100, 200, 1000)), 0);
+    assert_eq!(calculate_streamed(150, &linear(100, 200, 1000)), 500);
+    assert_eq!(calculate_streamed(200, &linear(100, 200, 1000)), 1000);
+    assert_eq!(calculate_streamed(u64::MAX, &linear(100, 200, 1000)), 1000);
+}
+
+#[test]
+fn test_calculate_streamed_precision() {
+    // Above 2^53 an f64 can't represent every integer anymore.
+    let amount = u64::MAX;
+    assert_eq!(calculate_streamed(1, &linear(0, 2, amount)), amount / 2);
+    assert_eq!(calculate_streamed(2, &linear(0, 3, amount)), amount / 3 * 2);
+    assert_eq!(
+        calculate_streamed(u64::MAX - 1, &linear(0, u64::MAX, amount)),
+        amount - 1
+    );
+}
+
+#[test]
+fn test_calculate_streamed_rounds_down() {
+    // 1000 over 3 seconds is 333.33... per second.
+    assert_eq!(calculate_streamed(1, &linear(0, 3, 1000)), 333);
+    assert_eq!(calculate_streamed(2, &linear(0, 3, 1000)), 666);
+    assert_eq!(calculate_streamed(3, &linear(0, 3, 1000)), 1000);
+}
+
+#[test]
+fn test_calculate_streamed_cliff() {
+    // 1000 over 100..200, with 400 unlocking at 150.
+    let schedule = LinearSchedule {
+        cliff: 150,
+        cliff_amount: 400,
+        ..linear(100, 200, 1000)
+    };
+    assert_eq!(calculate_streamed(149, &schedule), 0);
+    assert_eq!(calculate_streamed(150, &schedule), 400);
+    assert_eq!(calculate_streamed(175, &schedule), 700);
+    assert_eq!(calculate_streamed(200, &schedule), 1000);
+
+    // A cliff at the end releases everything at once.
+    let at_end = LinearSchedule {
+        cliff: 200,
+        ..schedule
+    };
+    assert_eq!(calculate_streamed(199, &at_end), 0);
+    assert_eq!(calculate_streamed(200, &at_end), 1000);
+
+    // A cliff at the start only adds the initial unlock.
+    let at_start = LinearSchedule {
+        cliff: 100,
+        ..schedule
+    };
+    assert_eq!(calculate_streamed(99, &at_start), 0);
+    assert_eq!(calculate_streamed(100, &at_start), 400);
+    assert_eq!(calculate_streamed(150, &at_start), 700);
+}
+
+#[test]
+fn test_calculate_streamed_period() {
+    // 1000 over 100..200, released as 100 every 10 seconds.
+    let schedule = LinearSchedule {
+        period: 10,
+        amount_per_period: 100,
+        ..linear(100, 200, 1000)
+    };
+    assert_eq!(calculate_streamed(100, &schedule), 0);
+    assert_eq!(calculate_streamed(109, &schedule), 0);
+    assert_eq!(calculate_streamed(110, &schedule), 100);
+    assert_eq!(calculate_streamed(155, &schedule), 500);
+    assert_eq!(calculate_streamed(199, &schedule), 900);
+    assert_eq!(calculate_streamed(200, &schedule), 1000);
+
+    // Periods are counted from the cliff.
+    let with_cliff = LinearSchedule {
+        cliff: 150,
+        cliff_amount: 500,
+        ..schedule
+    };
+    assert_eq!(calculate_streamed(150, &with_cliff), 500);
+    assert_eq!(calculate_streamed(165, &with_cliff), 600);
+
+    // Steps never release more than the stream holds.
+    let large_steps = LinearSchedule {
+        amount_per_period: 300,
+        ..schedule
+    };
+    assert_eq!(calculate_streamed(190, &large_steps), 1000);
+}
+
+// 2024-01-31 12:00:00 UTC
+const JAN_31_2024: u64 = 1_706_702_400;
+// 2024-02-29 12:00:00 UTC
+const FEB_29_2024: u64 = 1_709_208_000;
+// 2024-03-31 12:00:00 UTC
+const MAR_31_2024: u64 = 1_711_886_400;
+// 2024-04-30 12:00:00 UTC
+const APR_30_2024: u64 = 1_714_478_400;
+// 2025-02-28 12:00:00 UTC
+const FEB_28_2025: u64 = 1_740_744_000;
+// 2028-02-29 12:00:00 UTC
+const FEB_29_2028: u64 = 1_835_438_400;
+
+#[test]
+fn test_add_months() {
+    assert_eq!(add_months(0, 0), 0);
+    // 1970-02-01 and 1971-01-01
+    assert_eq!(add_months(0, 1), 2_678_400);
+    assert_eq!(add_months(0, 12), 31_536_000);
+
+    // Month-end starts stick to the end of shorter months, but every
+    // release point is computed from the start, so they don't drift.
+    assert_eq!(add_months(JAN_31_2024, 1), FEB_29_2024);
+    assert_eq!(add_months(JAN_31_2024, 2), MAR_31_2024);
+    assert_eq!(add_months(JAN_31_2024, 3), APR_30_2024);
+
+    // Leap days fall back to Feb 28th in common years.
+    assert_eq!(add_months(FEB_29_2024, 12), FEB_28_2025);
+    assert_eq!(add_months(FEB_29_2024, 48), FEB_29_2028);
+
+    assert_eq!(add_months(u64::MAX, 1), u64::MAX);
+    assert_eq!(add_months(0, u64::MAX), u64::MAX);
+}
+
+#[test]
+fn test_elapsed_months() {
+    assert_eq!(elapsed_months(JAN_31_2024, JAN_31_2024), 0);
+    assert_eq!(elapsed_months(JAN_31_2024, FEB_29_2024 - 1), 0);
+    assert_eq!(elapsed_months(JAN_31_2024, FEB_29_2024), 1);
+    assert_eq!(elapsed_months(JAN_31_2024, MAR_31_2024 - 1), 1);
+    assert_eq!(elapsed_months(JAN_31_2024, MAR_31_2024), 2);
+    assert_eq!(elapsed_months(JAN_31_2024, APR_30_2024), 3);
+    assert_eq!(elapsed_months(FEB_29_2024, FEB_28_2025 - 1), 11);
+    assert_eq!(elapsed_months(FEB_29_2024, FEB_28_2025), 12);
+    assert_eq!(elapsed_months(FEB_29_2024, FEB_29_2028), 48);
+    assert_eq!(elapsed_months(FEB_29_2024, JAN_31_2024), 0);
+}
+
+#[test]
+fn test_calculate_streamed_calendar() {
+    let end = FEB_29_2028;
+
+    // 100 released every month from Jan 31st.
+    let monthly = |now| {
+        calculate_streamed(
+            now,
+            &LinearSchedule {
+                period: 1,
+                amount_per_period: 100,
+                unit: PeriodUnit::Months,
+                ..linear(JAN_31_2024, end, 1000)
+            },
+        )
+    };
+    assert_eq!(monthly(FEB_29_2024 - 1), 0);
+    assert_eq!(monthly(FEB_29_2024), 100);
+    assert_eq!(monthly(MAR_31_2024), 200);
+    assert_eq!(monthly(APR_30_2024), 300);
+
+    // 250 released every year from Feb 29th.
+    let yearly = |now| {
+        calculate_streamed(
+            now,
+            &LinearSchedule {
+                period: 1,
+                amount_per_period: 250,
+                unit: PeriodUnit::Years,
+                ..linear(FEB_29_2024, end, 1000)
+            },
+        )
+    };
+    assert_eq!(yearly(FEB_28_2025 - 1), 0);
+    assert_eq!(yearly(FEB_28_2025), 250);
+    assert_eq!(yearly(end - 1), 750);
+    assert_eq!(yearly(end), 1000);
+}
+
+fn curve() -> Vec<Breakpoint> {
+    // 100 at 1000, 400 more linearly until 1600, the rest until 3000.
+    vec![
+        Breakpoint {
+            timestamp: 1000,
+            cumulative: 100,
+        },
+        Breakpoint {
+            timestamp: 1600,
+            cumulative: 400,
+        },
+        Breakpoint {
+            timestamp: 3000,
+            cumulative: 1000,
+        },
+    ]
+}
+
+#[test]
+fn test_calculate_piecewise() {
+    let curve = curve();
+    assert_eq!(calculate_piecewise(0, &curve), 0);
+    assert_eq!(calculate_piecewise(999, &curve), 0);
+    assert_eq!(calculate_piecewise(1000, &curve), 100);
+    assert_eq!(calculate_piecewise(1300, &curve), 250);
+    assert_eq!(calculate_piecewise(1600, &curve), 400);
+    assert_eq!(calculate_piecewise(2300, &curve), 700);
+    assert_eq!(calculate_piecewise(2999, &curve), 999);
+    assert_eq!(calculate_piecewise(3000, &curve), 1000);
+    assert_eq!(calculate_piecewise(u64::MAX, &curve), 1000);
+    assert_eq!(calculate_piecewise(1000, &[]), 0);
+}
+
+#[test]
+fn test_breakpoints_sanity() {
+    let curve = curve();
+    assert!(breakpoints_sanity(1000, 3000, 1000, &curve));
+    assert!(breakpoints_sanity(500, 3000, 1000, &curve));
+
+    // Has to end at the stream's end with the whole amount.
+    assert!(!breakpoints_sanity(1000, 3001, 1000, &curve));
+    assert!(!breakpoints_sanity(1000, 3000, 1001, &curve));
+    // Can't start before the stream does.
+    assert!(!breakpoints_sanity(1001, 3000, 1000, &curve));
+    assert!(!breakpoints_sanity(1000, 3000, 1000, &[]));
+
+    // Timestamps strictly increase, amounts never decrease.
+    let mut bad = curve.clone();
+    bad[1].timestamp = 1000;
+    assert!(!breakpoints_sanity(1000, 3000, 1000, &bad));
+    let mut bad = curve.clone();
+    bad[1].cumulative = 99;
+    assert!(!breakpoints_sanity(1000, 3000, 1000, &bad));
+
+    let many: Vec<Breakpoint> = (1..=MAX_BREAKPOINTS as u64 + 1)
+        .map(|i| Breakpoint {
+            timestamp: i,
+            cumulative: i,
+        })
+        .collect();
+    let end = many.len() as u64;
+    assert!(breakpoints_sanity(
+        0,
+        end - 1,
+        end - 1,
+        &many[..MAX_BREAKPOINTS]
+    ));
+    assert!(!breakpoints_sanity(0, end, end, &many));
+}
+
+#[test]
+fn test_calculate_rate_streamed() {
+    assert_eq!(calculate_rate_streamed(0, 100, 7, 1000), 0);
+    assert_eq!(calculate_rate_streamed(100, 100, 7, 1000), 0);
+    assert_eq!(calculate_rate_streamed(110, 100, 7, 1000), 70);
+    // Capped at the deposited balance.
+    assert_eq!(calculate_rate_streamed(300, 100, 7, 1000), 1000);
+    assert_eq!(calculate_rate_streamed(u64::MAX, 0, u64::MAX, 1000), 1000);
+}
+
+#[test]
+fn test_calculate_rate_duration() {
+    assert_eq!(calculate_rate_duration(0, 7), 0);
+    assert_eq!(calculate_rate_duration(70, 7), 10);
+    // Rounded up to the second the last funds unlock in.
+    assert_eq!(calculate_rate_duration(1000, 7), 143);
+    assert_eq!(calculate_rate_streamed(243, 100, 7, 1000), 1000);
+    assert_eq!(calculate_rate_duration(u64::MAX, u64::MAX), 1);
+    assert_eq!(calculate_rate_duration(u64::MAX, 1), u64::MAX);
+}
+
+fn stream(params: StreamParams) -> StreamFlow {
+    let key = Pubkey::new_unique();
+    StreamFlow::new(&params, &key, &key, &key)
+}
+
+fn fixed() -> StreamParams {
+    StreamParams {
+        start_time: 1000,
+        end_time: 3000,
+        amount: 1000,
+        cliff_time: 0,
+        cliff_amount: 0,
+        period: 0,
+        amount_per_period: 0,
+        period_unit: PeriodUnit::Seconds,
+        breakpoints: vec![],
+        rate: 0,
+        transfer_by_sender_only: false,
+        cancelable_by_sender: true,
+        cancelable_by_recipient: false,
+        rent_destination: None,
+        crank_fee_bps: 0,
+    }
+}
+
+#[test]
+fn test_stream_sanity() {
+    assert!(stream_sanity(1000, &stream(fixed()), &[]));
+    assert!(stream_sanity(1000, &stream(fixed()), &curve()));
+    assert!(!stream_sanity(1001, &stream(fixed()), &[]));
+
+    let open = StreamParams {
+        end_time: 0,
+        rate: 10,
+        ..fixed()
+    };
+    assert!(stream_sanity(1000, &stream(open.clone()), &[]));
+    // Open-ended streams have neither an end, nor any other schedule.
+    assert!(!stream_sanity(1001, &stream(open.clone()), &[]));
+    assert!(!stream_sanity(1000, &stream(open.clone()), &curve()));
+    for params in [
+        StreamParams {
+            end_time: 3000,
+            ..open.clone()
+        },
+        StreamParams {
+            cliff_time: 2000,
+            ..open.clone()
+        },
+        StreamParams {
+            period: 10,
+            amount_per_period: 10,
+            ..open.clone()
+        },
+    ] {
+        assert!(!stream_sanity(1000, &stream(params), &[]));
+    }
+}
+
+#[test]
+fn test_crank_fee_sanity() {
+    for (crank_fee_bps, valid) in [(0, true), (MAX_CRANK_FEE_BPS, true), (101, false)] {
+        for params in [
+            fixed(),
+            StreamParams {
+                end_time: 0,
+                rate: 10,
+                ..fixed()
+            },
+        ] {
+            let sf = stream(StreamParams {
+                crank_fee_bps,
+                ..params
+            });
+            assert_eq!(stream_sanity(1000, &sf, &[]), valid);
+        }
+    }
+}
+
+#[test]
+fn test_period_sanity() {
+    assert!(period_sanity(1000, 0, 0));
+    assert!(period_sanity(1000, 10, 100));
+    assert!(period_sanity(1000, 10, 1000));
+    assert!(!period_sanity(1000, 10, 0));
+    assert!(!period_sanity(1000, 0, 100));
+    assert!(!period_sanity(1000, 10, 1001));
+}
+
+#[test]
+fn test_cliff_sanity() {
+    assert!(cliff_sanity(100, 200, 1000, 0, 0));
+    assert!(cliff_sanity(100, 200, 1000, 100, 1000));
+    assert!(cliff_sanity(100, 200, 1000, 200, 0));
+    assert!(!cliff_sanity(100, 200, 1000, 99, 0));
+    assert!(!cliff_sanity(100, 200, 1000, 201, 0));
+    assert!(!cliff_sanity(100, 200, 1000, 150, 1001));
+}
+
+proptest! {
+    #[test]
+    fn prop_never_exceeds_amount(
+        now in any::<u64>(),
+        start in any::<u64>(),
+        end in any::<u64>(),
+        amount in any::<u64>(),
+        cliff in any::<u64>(),
+        cliff_amount in any::<u64>(),
+        period in any::<u64>(),
+        amount_per_period in any::<u64>(),
+    ) {
+        let schedule = LinearSchedule { cliff, cliff_amount, period, amount_per_period, ..linear(start, end, amount) };
+        prop_assert!(calculate_streamed(now, &schedule) <= amount);
+    }
+
+    #[test]
+    fn prop_monotonic_in_time(
+        start in any::<u64>(),
+        len in 1..u64::MAX,
+        amount in any::<u64>(),
+        cliff_offset in any::<u64>(),
+        cliff_amount in any::<u64>(),
+        period in 0..u64::MAX,
+        amount_per_period in any::<u64>(),
+        a in any::<u64>(),
+        b in any::<u64>(),
+    ) {
+        // Either continuous, or stepped with a non-zero step.
+        let amount_per_period = if period == 0 { 0 } else { amount_per_period % amount.saturating_add(1) };
+        let end = start.saturating_add(len);
+        let cliff = start.saturating_add(cliff_offset % len);
+        let cliff_amount = cliff_amount % amount.saturating_add(1);
+        let (t0, t1) = if a <= b { (a, b) } else { (b, a) };
+        let schedule = LinearSchedule { cliff, cliff_amount, period, amount_per_period, ..linear(start, end, amount) };
+        prop_assert!(calculate_streamed(t0, &schedule) <= calculate_streamed(t1, &schedule));
+    }
+
+    #[test]
+    fn prop_matches_exact_ratio(
+        start in 0..u64::MAX / 2,
+        len in 1..u64::MAX / 2,
+        elapsed in any::<u64>(),
+        amount in any::<u64>(),
+    ) {
+        let end = start + len;
+        let now = start + elapsed % len;
+        let expected = (elapsed % len) as u128 * amount as u128 / len as u128;
+        prop_assert_eq!(calculate_streamed(now, &linear(start, end, amount)) as u128, expected);
+    }
+
+    #[test]
+    fn prop_elapsed_months_brackets_now(
+        from in 0..u64::MAX / 2,
+        elapsed in 0..u64::MAX / 2,
+    ) {
+        let now = from + elapsed;
+        let months = elapsed_months(from, now);
+        prop_assert!(add_months(from, months) <= now);
+        prop_assert!(add_months(from, months + 1) > now);
+    }
+
+    #[test]
+    fn prop_piecewise_monotonic_and_bounded(
+        steps in proptest::collection::vec((1..u64::MAX / 64, any::<u32>()), 1..MAX_BREAKPOINTS),
+        a in any::<u64>(),
+        b in any::<u64>(),
+    ) {
+        let mut timestamp = 0;
+        let mut cumulative = 0;
+        let curve: Vec<Breakpoint> = steps
+            .into_iter()
+            .map(|(dt, da)| {
+                timestamp += dt;
+                cumulative += da as u64;
+                Breakpoint { timestamp, cumulative }
+            })
+            .collect();
+
+        let (t0, t1) = if a <= b { (a, b) } else { (b, a) };
+        let (u0, u1) = (calculate_piecewise(t0, &curve), calculate_piecewise(t1, &curve));
+        prop_assert!(u0 <= u1);
+        prop_assert!(u1 <= cumulative);
+    }
+}
+
+#[test]
+fn test_boundary_values() {
+    // Reversed timestamps are rejected rather than underflowing.
+    assert!(!duration_sanity(0, 200, 100));
+    assert!(!duration_sanity(0, u64::MAX, 0));
+    assert!(duration_sanity(0, u64::MAX - 1, u64::MAX));
+
+    let huge = StreamParams {
+        start_time: u64::MAX - 1000,
+        end_time: u64::MAX,
+        amount: u64::MAX,
+        ..fixed()
+    };
+    assert!(stream_sanity(0, &stream(huge.clone()), &[]));
+    let sf = stream(huge);
+    assert_eq!(sf.unlocked(u64::MAX - 500, None), u64::MAX / 2);
+    assert_eq!(sf.unlocked(u64::MAX, None), u64::MAX);
+
+    assert_eq!(
+        calculate_streamed(
+            u64::MAX - 1,
+            &LinearSchedule {
+                period: 1,
+                amount_per_period: u64::MAX,
+                ..linear(0, u64::MAX, u64::MAX)
+            }
+        ),
+        u64::MAX
+    );
+    assert_eq!(
+        calculate_streamed(
+            u64::MAX - 1,
+            &LinearSchedule {
+                period: 1,
+                amount_per_period: u64::MAX,
+                unit: PeriodUnit::Months,
+                ..linear(0, u64::MAX, u64::MAX)
+            }
+        ),
+        u64::MAX
+    );
+
+    let breakpoints = [
+        Breakpoint {
+            timestamp: u64::MAX - 2,
+            cumulative: 0,
+        },
+        Breakpoint {
+            timestamp: u64::MAX,
+            cumulative: u64::MAX,
+        },
+    ];
+    assert_eq!(
+        calculate_piecewise(u64::MAX - 1, &breakpoints),
+        u64::MAX / 2
+    );
+    assert_eq!(calculate_piecewise(u64::MAX, &breakpoints), u64::MAX);
+
+    assert_eq!(
+        calculate_rate_streamed(u64::MAX, 0, u64::MAX, u64::MAX),
+        u64::MAX
+    );
+    assert_eq!(add_months(u64::MAX, 1), u64::MAX);
+    assert!(elapsed_months(0, u64::MAX) > 0);
+}